@@ -0,0 +1,51 @@
+//! Opt-in reward tokens of players. Games in other tokens are rejected for them
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /// Accept games only in `accepted_tokens` (NEAR is "near"). `None` accepts any whitelisted token.
+    /// Checked on new invites and game start, open invites in other tokens can't be started
+    pub fn set_accepted_tokens(&mut self, accepted_tokens: Option<Vec<TokenContractId>>) {
+        self.internal_bump_update_seq();
+        let account_id = env::predecessor_account_id();
+        match accepted_tokens {
+            Some(accepted_tokens) => {
+                assert!(!accepted_tokens.is_empty(), "Accepted tokens can't be empty, use null to accept any token");
+                assert!(
+                    accepted_tokens.len() <= MAX_ACCEPTED_TOKENS,
+                    "Too many accepted tokens. Max is {}", MAX_ACCEPTED_TOKENS
+                );
+                for token_id in accepted_tokens.iter() {
+                    assert!(
+                        *token_id == near_token_id() || self.whitelisted_tokens.get(token_id).is_some(),
+                        "Token {} is not whitelisted", token_id
+                    );
+                }
+                self.accepted_tokens.insert(&account_id, &accepted_tokens);
+            },
+            None => {
+                self.accepted_tokens.remove(&account_id);
+            },
+        }
+    }
+
+    /// `None` if any whitelisted token is accepted
+    pub fn get_accepted_tokens(&self, account_id: AccountId) -> Option<Vec<TokenContractId>> {
+        self.accepted_tokens.get(&account_id)
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_accepts_token(&self, account_id: &AccountId, token_id: &TokenContractId) -> bool {
+        self.accepted_tokens
+            .get(account_id)
+            .is_none_or(|accepted_tokens| accepted_tokens.contains(token_id))
+    }
+
+    pub(crate) fn internal_assert_accepts_token(&self, account_id: &AccountId, token_id: &TokenContractId) {
+        assert!(
+            self.internal_accepts_token(account_id, token_id),
+            "@{} doesn't accept games in {}", account_id, token_id
+        );
+    }
+}
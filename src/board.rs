@@ -9,6 +9,55 @@ pub enum Winner {
     Tie
 }
 
+impl Winner {
+    /// the other piece wins, tie is kept. Used in misère mode
+    pub fn inverted(self) -> Winner {
+        match self {
+            Winner::X => Winner::O,
+            Winner::O => Winner::X,
+            Winner::Tie => Winner::Tie,
+        }
+    }
+}
+
+/// Which lines count for a win
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Default)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+#[serde(crate = "near_sdk::serde")]
+pub enum WinRule {
+    /// rows, columns and diagonals
+    #[default]
+    Standard,
+    /// only rows and columns
+    NoDiagonals,
+    /// only diagonals
+    DiagonalsOnly,
+}
+
+impl WinRule {
+    /// number of lines on the board counted by the rule
+    pub fn lines_num(self) -> u32 {
+        let orthogonal = 2 * BOARD_SIZE as u32;
+        match self {
+            WinRule::Standard => orthogonal + 2,
+            WinRule::NoDiagonals => orthogonal,
+            WinRule::DiagonalsOnly => 2,
+        }
+    }
+}
+
+impl From<Piece> for Winner {
+    fn from(piece: Piece) -> Self {
+        match piece {
+            Piece::X => Winner::X,
+            Piece::O => Winner::O,
+        }
+    }
+}
+
+/// maps tile position of transformed board to position on the original one
+type Symmetry = fn(usize, usize) -> (usize, usize);
+
 #[derive(Debug, Clone)]
 pub enum MoveError {
     /// The game was already over when a move was attempted
@@ -17,6 +66,8 @@ pub enum MoveError {
     InvalidPosition { row: usize, col: usize },
     /// The tile already contained another piece
     TileFilled { other_piece: Piece, row: usize, col: usize },
+    /// The column has no empty tiles left (gravity mode)
+    ColumnFilled { col: usize },
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
@@ -48,6 +99,7 @@ impl Board {
         if self.winner.is_some() {
             return Err(MoveError::GameAlreadyOver);
         }
+        // bounds are checked before any indexing
         if row >= self.tiles.len() || col >= self.tiles[0].len() {
             return Err(MoveError::InvalidPosition {row, col});
         }
@@ -57,9 +109,201 @@ impl Board {
         }
         Ok(())
     }
+    /// Tiles as rows of any length for views. Side length is `BOARD_SIZE` for now
+    pub fn to_rows(&self) -> Vec<Vec<Option<Piece>>> {
+        self.tiles.iter().map(|row| row.to_vec()).collect()
+    }
+    /// Compact board: row per string with 'x'/'o' and '.' for empty tile
+    pub fn to_compact(&self) -> Vec<String> {
+        self.tiles
+            .iter()
+            .map(|row| row
+                .iter()
+                .map(|tile| tile.map_or(".".to_string(), |piece| piece.to_string()))
+                .collect())
+            .collect()
+    }
+    /// X and O positions as bitmasks, bit `row * BOARD_SIZE + col` is set for the piece's tile.
+    /// Fits boards up to 8x8
+    pub fn to_bitboards(&self) -> (u64, u64) {
+        const _: () = assert!(BOARD_SIZE * BOARD_SIZE <= u64::BITS as usize);
+        let mut bitboards = (0u64, 0u64);
+        for (index, tile) in self.tiles.iter().flatten().enumerate() {
+            match tile {
+                Some(Piece::X) => bitboards.0 |= 1 << index,
+                Some(Piece::O) => bitboards.1 |= 1 << index,
+                None => {},
+            }
+        }
+        bitboards
+    }
+    /// Tiles from `to_bitboards` masks. Current piece and winner aren't encoded:
+    /// current piece is the one with fewer tiles (X if equal), no winner
+    pub fn from_bitboards(x: u64, o: u64) -> Self {
+        const TILES_NUM: usize = BOARD_SIZE * BOARD_SIZE;
+        assert_eq!(x & o, 0, "X and O are on the same tile");
+        assert_eq!((x | o) >> TILES_NUM, 0, "Bits out of board");
+        let mut tiles: [[Option<Piece>; BOARD_SIZE]; BOARD_SIZE] = Default::default();
+        for index in 0..TILES_NUM {
+            tiles[index / BOARD_SIZE][index % BOARD_SIZE] = if x >> index & 1 == 1 {
+                Some(Piece::X)
+            } else if o >> index & 1 == 1 {
+                Some(Piece::O)
+            } else {
+                None
+            };
+        }
+        let current_piece = if x.count_ones() > o.count_ones() { Piece::O } else { Piece::X };
+        Self { tiles, current_piece, winner: None }
+    }
+    /// Board as base-3 number (empty - 0, x - 1, o - 2), the minimum over 8 rotations and reflections.
+    /// Boards of the same shape up to symmetry have the same hash
+    pub fn canonical_hash(&self) -> u64 {
+        const LAST: usize = BOARD_SIZE - 1;
+        // tile of the transformed board at (row, col) is taken from the original one at `symmetry(row, col)`
+        let symmetries: [Symmetry; 8] = [
+            |row, col| (row, col),
+            |row, col| (col, LAST - row),
+            |row, col| (LAST - row, LAST - col),
+            |row, col| (LAST - col, row),
+            |row, col| (row, LAST - col),
+            |row, col| (LAST - row, col),
+            |row, col| (col, row),
+            |row, col| (LAST - col, LAST - row),
+        ];
+        symmetries
+            .iter()
+            .map(|symmetry| {
+                (0..BOARD_SIZE * BOARD_SIZE).fold(0u64, |hash, index| {
+                    let (row, col) = symmetry(index / BOARD_SIZE, index % BOARD_SIZE);
+                    let digit = match self.tiles[row][col] {
+                        None => 0,
+                        Some(Piece::X) => 1,
+                        Some(Piece::O) => 2,
+                    };
+                    hash * 3 + digit
+                })
+            })
+            .min()
+            .expect("symmetries are not empty")
+    }
+    /// Board heuristic as (x, o) number of open lines: lines counted by `win_rule`
+    /// with at least one piece and no opponent pieces, so they can still be completed
+    pub fn score_position(&self, win_rule: WinRule) -> (i32, i32) {
+        let mut lines: Vec<[Option<Piece>; BOARD_SIZE]> = vec![];
+        if win_rule != WinRule::DiagonalsOnly {
+            for index in 0..BOARD_SIZE {
+                lines.push(self.tiles[index]);
+                lines.push(std::array::from_fn(|row| self.tiles[row][index]));
+            }
+        }
+        if win_rule != WinRule::NoDiagonals {
+            lines.push(std::array::from_fn(|index| self.tiles[index][index]));
+            lines.push(std::array::from_fn(|index| self.tiles[index][BOARD_SIZE - 1 - index]));
+        }
+        lines.iter().fold((0, 0), |(x, o), line| {
+            let has_x = line.contains(&Some(Piece::X));
+            let has_o = line.contains(&Some(Piece::O));
+            match (has_x, has_o) {
+                (true, false) => (x + 1, o),
+                (false, true) => (x, o + 1),
+                _ => (x, o),
+            }
+        })
+    }
+    /// Lines counted by `win_rule` which go through (`row`, `col`)
+    fn lines_through(row: usize, col: usize, win_rule: WinRule) -> Vec<[(usize, usize); BOARD_SIZE]> {
+        let mut lines = vec![];
+        if win_rule != WinRule::DiagonalsOnly {
+            lines.push(std::array::from_fn(|index| (row, index)));
+            lines.push(std::array::from_fn(|index| (index, col)));
+        }
+        if win_rule != WinRule::NoDiagonals {
+            if row == col {
+                lines.push(std::array::from_fn(|index| (index, index)));
+            }
+            if row + col == BOARD_SIZE - 1 {
+                lines.push(std::array::from_fn(|index| (index, BOARD_SIZE - 1 - index)));
+            }
+        }
+        lines
+    }
+    /// Number of lines through (`row`, `col`) filled with the piece on this tile.
+    /// Lines ignored by `win_rule` aren't counted
+    pub fn lines_completed_at(&self, row: usize, col: usize, win_rule: WinRule) -> u32 {
+        let piece = match self.tiles[row][col] {
+            Some(piece) => piece,
+            None => return 0,
+        };
+        Board::lines_through(row, col, win_rule)
+            .iter()
+            .filter(|line| line.iter().all(|&(row, col)| self.tiles[row][col] == Some(piece)))
+            .count() as u32
+    }
+    /// Tiles (row, col) of the first line through (`row`, `col`) filled with the piece of this tile
+    pub fn winning_line_at(&self, row: usize, col: usize, win_rule: WinRule) -> Option<Vec<(u8, u8)>> {
+        let piece = self.tiles[row][col]?;
+        Board::lines_through(row, col, win_rule)
+            .into_iter()
+            .find(|line| line.iter().all(|&(row, col)| self.tiles[row][col] == Some(piece)))
+            .map(|line| line.iter().map(|&(row, col)| (row as u8, col as u8)).collect())
+    }
+    /// Some line through (`row`, `col`) has the piece of this tile on all tiles but one, which is empty
+    pub fn has_threat_at(&self, row: usize, col: usize, win_rule: WinRule) -> bool {
+        let piece = match self.tiles[row][col] {
+            Some(piece) => piece,
+            None => return false,
+        };
+        Board::lines_through(row, col, win_rule).iter().any(|line| {
+            let own = line.iter().filter(|&&(row, col)| self.tiles[row][col] == Some(piece)).count();
+            let empty = line.iter().filter(|&&(row, col)| self.tiles[row][col].is_none()).count();
+            own == BOARD_SIZE - 1 && empty == 1
+        })
+    }
+    /// Empty tiles (row, col) where `piece` completes a line counted by `win_rule`, in row-major order.
+    /// Gravity isn't considered, tile may be not playable yet
+    pub fn find_threats(&self, piece: Piece, win_rule: WinRule) -> Vec<(u8, u8)> {
+        (0..BOARD_SIZE * BOARD_SIZE)
+            .map(|index| (index / BOARD_SIZE, index % BOARD_SIZE))
+            .filter(|&(row, col)| self.tiles[row][col].is_none())
+            .filter(|&(row, col)| Board::lines_through(row, col, win_rule).iter().any(|line| {
+                line.iter().all(|&(r, c)| (r, c) == (row, col) || self.tiles[r][c] == Some(piece))
+            }))
+            .map(|(row, col)| (row as u8, col as u8))
+            .collect()
+    }
+    pub fn is_full(&self) -> bool {
+        self.tiles.iter().all(|row| row.iter().all(|tile| tile.is_some()))
+    }
+    /// Gravity mode. Returns the lowest empty row in `col` where the piece drops to
+    pub fn check_drop(&self, col: usize) -> Result<usize, MoveError> {
+        if self.winner.is_some() {
+            return Err(MoveError::GameAlreadyOver);
+        }
+        if col >= self.tiles[0].len() {
+            return Err(MoveError::InvalidPosition {row: 0, col});
+        }
+        (0..self.tiles.len())
+            .rev()
+            .find(|&row| self.tiles[row][col].is_none())
+            .ok_or(MoveError::ColumnFilled {col})
+    }
+    /// Sets winner implied by the last move at (`row`, `col`), see `has_winner_at`.
+    /// Returns tiles of the winning line if the move won
+    pub fn update_winner(&mut self, row: usize, col: usize, win_rule: WinRule) -> Option<Vec<(u8, u8)>> {
+        if self.winner.is_some() {
+            return None;
+        }
+        self.winner = self.has_winner_at(row, col, win_rule);
+        match self.winner {
+            Some(Winner::X | Winner::O) => self.winning_line_at(row, col, win_rule),
+            _ => None,
+        }
+    }
+    /// Winner implied by the last move at (`row`, `col`) without changing the board.
     /// To find a potential winner, we only need to check the row, column and (maybe) diagonal
-    /// that the last move was made in.
-    pub fn update_winner(&mut self, row: usize, col: usize) {
+    /// that the last move was made in. Lines ignored by `win_rule` are never checked.
+    pub fn has_winner_at(&self, row: usize, col: usize, win_rule: WinRule) -> Option<Winner> {
 
         let rows = self.tiles.len();
         let cols = self.tiles[0].len();
@@ -79,8 +323,11 @@ impl Board {
         // 1. (0, 0), (1, 1), (2, 2), (3, 3), (4, 4)
         // 2. (0, 4), (1, 3), (2, 2), (3, 1), (4, 0) 
 
+        let check_orthogonal = win_rule != WinRule::DiagonalsOnly;
+        let check_diagonals = win_rule != WinRule::NoDiagonals;
+
         // Define diagonals
-        let tiles_diagonal_1 = if row == col {
+        let tiles_diagonal_1 = if check_diagonals && row == col {
             // Diagonal 1
             [self.tiles[0][0], self.tiles[1][1], self.tiles[2][2], self.tiles[3][3], self.tiles[4][4]]
         }
@@ -90,7 +337,7 @@ impl Board {
             [None, None, None, None, None]
         };
 
-        let tiles_diagonal_2 = if (rows - row - 1) == col {
+        let tiles_diagonal_2 = if check_diagonals && (rows - row - 1) == col {
             // Diagonal 2
             [self.tiles[0][4], self.tiles[1][3], self.tiles[2][2], self.tiles[3][1], self.tiles[4][0]]
         }
@@ -114,20 +361,18 @@ impl Board {
         }
 
         // Check winner for all given diagonals and rows/columns
-        self.winner = self.winner
-            .or_else(|| check_winner(&tiles_row))
-            .or_else(|| check_winner(&tiles_col))
+        check_orthogonal.then(|| check_winner(&tiles_row)).flatten()
+            .or_else(|| check_orthogonal.then(|| check_winner(&tiles_col)).flatten())
             .or_else(|| check_winner(&tiles_diagonal_1))
-            .or_else(|| check_winner(&tiles_diagonal_2));
-
-        // Tie case
-        self.winner = self.winner.or_else(|| {
-            if self.tiles.iter().all(|row| row.iter().all(|tile| tile.is_some())) {
-                Some(Winner::Tie)
-            }
-            else {
-                None
-            }
-        });
+            .or_else(|| check_winner(&tiles_diagonal_2))
+            // Tie case
+            .or_else(|| {
+                if self.is_full() {
+                    Some(Winner::Tie)
+                }
+                else {
+                    None
+                }
+            })
     }
 }
\ No newline at end of file
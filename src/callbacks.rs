@@ -1,17 +1,50 @@
 use crate::*;
 
+/// All callbacks are `#[private]`. `assert_self` duplicates the guard
+/// so it's also checked in unit tests where `#[private]` is not applied
 #[near_bindgen]
 impl Contract {
     #[private]
-    pub fn transfer_deposit_callback(&mut self, user: AccountId, config: &GameConfig) {
+    pub fn transfer_deposit_callback(&mut self, user: AccountId, invite_id: InviteId, config: &GameConfig) {
+        assert_self();
+        self.internal_bump_update_seq();
         if promise_result_as_failed() {
             log!(
                 "transfer available deposit {} of {} token failed. recovering @{} state",
-                config.deposit,
+                config.total_deposit(),
                 config.token_id,
                 user.clone()
             );
-            self.available_players.insert(&user, config);
+            // player could open new invites while refund was in flight.
+            // Don't exceed invites limit, keep failed refund to claim it later
+            if self.internal_get_player_invites(&user).len() >= MAX_INVITES_PER_PLAYER {
+                self.internal_add_pending_refund(&user, &config.token_id, config.total_deposit());
+            } else {
+                self.internal_insert_available_player(&user, invite_id, config);
+            }
         }
     }
-}
\ No newline at end of file
+
+    /// keep failed transfers amounts in pending refunds (see `refunds.rs`).
+    /// Promise results are in the same order as `transfers`
+    #[private]
+    pub fn transfers_callback(&mut self, transfers: Vec<Transfer>) {
+        assert_self();
+        self.internal_bump_update_seq();
+        require!(
+            env::promise_results_count() == transfers.len() as u64,
+            "Contract expected a result for each transfer on the callback"
+        );
+        for (index, transfer) in transfers.iter().enumerate() {
+            if let PromiseResult::Failed = env::promise_result(index as u64) {
+                log!(
+                    "transfer {} of {} token to @{} failed. Amount is added to pending refunds",
+                    transfer.amount.0,
+                    transfer.token_id,
+                    transfer.receiver_id
+                );
+                self.internal_add_pending_refund(&transfer.receiver_id, &transfer.token_id, transfer.amount.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,44 @@
+//! Chat of game players. One message per player per turn, bounded number of messages.
+//! Messages are removed when the game is finished
+use crate::*;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct ChatMessage {
+    pub account_id: AccountId,
+    pub text: String,
+    /// `Game.total_turns` when message was posted
+    pub turn: u8,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Post message to active game chat. Callable by players only
+    pub fn post_message(&mut self, game_id: GameId, text: String) {
+        self.internal_bump_update_seq();
+        let game = self.internal_get_game(&game_id);
+        assert_eq!(game.game_state, GameState::Active, "Current game isn't active");
+        let account_id = env::predecessor_account_id();
+        assert!(game.contains_player_account_id(&account_id), "You are not in this game. GameId: {} ", game_id);
+        assert!(!text.trim().is_empty(), "Message is empty");
+        if let Err(error) = validate_text(&text, MAX_CHAT_MESSAGE_LEN) {
+            panic!("{}", error);
+        }
+
+        let mut messages = self.chats.get(&game_id).unwrap_or_default();
+        assert!(messages.len() < MAX_CHAT_MESSAGES_PER_GAME, "Chat is full");
+        assert!(
+            !messages.iter().any(|message| message.account_id == account_id && message.turn == game.total_turns),
+            "One message per turn"
+        );
+        Event::Chat { game_id, account_id: &account_id, text: &text }.emit();
+        messages.push(ChatMessage { account_id, text, turn: game.total_turns });
+        self.chats.insert(&game_id, &messages);
+    }
+
+    /// messages of active game, the oldest first
+    pub fn get_game_chat(&self, game_id: GameId) -> Vec<ChatMessage> {
+        self.chats.get(&game_id).unwrap_or_default()
+    }
+}
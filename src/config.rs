@@ -4,9 +4,44 @@ use crate::*;
 const MAX_GAME_DURATION_SEC: u32 = 2 * 60 * 60;
 /// 25 MINUTES in seconds
 const MIN_GAME_DURATION_SEC: u32 = 25 * 60;
+/// 30 seconds
+const MIN_READY_CHECK_SEC: u32 = 30;
+/// 30 MINUTES in seconds
+const MAX_READY_CHECK_SEC: u32 = 30 * 60;
+/// 1 DAY in seconds
+const MAX_PAYOUT_DELAY_SEC: u32 = 24 * 60 * 60;
+/// 7 DAYS in seconds
+pub (crate) const MAX_PENALTY_BAN_SEC: u32 = 7 * 24 * 60 * 60;
+/// penalty ban of the last tier is 2^10 times longer than the first one (up to `MAX_PENALTY_BAN_SEC`)
+const MAX_PENALTY_TIER: u8 = 10;
+/// 10 MINUTES in seconds
+const MAX_TURN_EXTENSION_SEC: u32 = 10 * 60;
+/// 25% of pot in BASIS_P
+const MAX_CONSOLATION_RATIO: u32 = BASIS_P / 4;
+/// 1 hour
+const MAX_REMATCH_COOLDOWN_SEC: u32 = 60 * 60;
+/// 1 hour
+const MIN_STORED_GAME_MAX_AGE_SEC: u32 = 60 * 60;
+/// 1 MINUTE in seconds
+const MAX_TIMEOUT_GRACE_SEC: u32 = 60;
+/// 1 MINUTE in seconds
+const MIN_TIME_TO_BE_AVAILABLE_SEC: u32 = 60;
+/// 7 DAYS in seconds
+const MAX_TIME_TO_BE_AVAILABLE_SEC: u32 = 7 * 24 * 60 * 60;
+
+/// Service fee for games finished in `max_turns` turns or less
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct FeeBucket {
+    pub max_turns: u8,
+    /// Service fee in BASIS_P (same bounds as `service_fee_percentage`)
+    pub service_fee_percentage: u32,
+}
 
 /// variables can be change after by owner
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
 #[serde(crate = "near_sdk::serde")]
 pub struct Config {
     /// Service fee in BASIS_P E.g 10% => 1000; 2% => 200
@@ -19,26 +54,215 @@ pub struct Config {
     /// `max_game_duration_sec` in seconds (0..3600) is required 
     pub max_game_duration_sec: u32,
     /// max number of stored games into contract
-    pub max_stored_games: u8
+    pub max_stored_games: u8,
+    /// Optional piecewise service fee by game length. Buckets are sorted by `max_turns`,
+    /// games longer than the last bucket pay flat `service_fee_percentage`
+    pub fee_schedule: Option<Vec<FeeBucket>>,
+    /// Optional max number of players waiting in available players list with the same token.
+    /// `DEFAULT_MAX_WAITING_PER_TOKEN` if not set
+    pub max_waiting_per_token: Option<u32>,
+    /// Optional time in seconds for opponent to accept proposed game.
+    /// `DEFAULT_READY_CHECK_SEC` if not set
+    pub ready_check_sec: Option<u32>,
+    /// Optional min time in seconds between turns for speed games. 0 if not set
+    pub min_turn_interval_sec: Option<u32>,
+    /// Optional part of service fee paid by player which is returned to him as claimable rakeback
+    /// in BASIS_P. `rakeback_ratio` + `referrer_ratio` can't exceed 100%. 0 if not set
+    pub rakeback_ratio: Option<u32>,
+    /// Optional verbose logs of internal timestamps and deposits. Disabled if not set
+    pub debug_logs: Option<bool>,
+    /// Optional dispute window in seconds before payout of games with pot above token threshold
+    /// (see `set_payout_delay_threshold`). Disabled if not set
+    pub payout_delay_sec: Option<u32>,
+    /// Optional min `total_turns` from which timed out game is won by player with more open lines
+    /// (see `Board::score_position`) instead of penalizing the staller. Disabled if not set
+    pub tie_breaker_min_turns: Option<u8>,
+    /// Optional cancellation with full refunds instead of penalty if the first move times out. Disabled if not set
+    pub first_move_grace_refund: Option<bool>,
+    /// Optional first move of `start_game` caller (or proposer) instead of random choice.
+    /// Meant for tests and testnet only. Disabled if not set
+    pub deterministic_first_move: Option<bool>,
+    /// Optional matchmaking ban in seconds after each penalty. Disabled if not set
+    pub penalty_ban_sec: Option<u32>,
+    /// Optional part of service fee in BASIS_P paid to the player who first had a line
+    /// with one empty tile left (see `Board::has_threat_at`). 0 if not set
+    pub first_threat_bonus: Option<u32>,
+    /// Optional rejection of games where player's referrer from stats is the opponent.
+    /// Referrer in game config is always checked. Disabled if not set
+    pub block_referrer_as_opponent: Option<bool>,
+    /// Optional time in seconds added to player's clock by `extend_turn`, once per game. Disabled if not set
+    pub turn_extension_sec: Option<u32>,
+    /// Optional price of turn extension in yoctoNEAR, goes to protocol fees. Free if not set
+    pub turn_extension_price: Option<U128>,
+    /// Optional part of pot in BASIS_P returned to the loser of decided game (0..25%). 0 if not set
+    pub consolation_ratio: Option<u32>,
+    /// Optional min number of played games of referrer to earn referrer fees.
+    /// Referrer part of service fee stays with the protocol until then. 0 if not set
+    pub min_referrer_games: Option<u32>,
+    /// Optional time in seconds after finishing a game before the player can make available
+    /// or start the next one. Disabled if not set
+    pub rematch_cooldown_sec: Option<u32>,
+    /// Optional age in seconds of stored games after which they are evicted even if
+    /// `max_stored_games` isn't reached. Disabled if not set
+    pub stored_game_max_age_sec: Option<u32>,
+    /// Optional time in seconds added to turn and game deadlines for late moves (e.g. delayed by
+    /// block production). Both `make_move` and `stop_game` use the extended deadline. 0 if not set
+    pub timeout_grace_sec: Option<u32>,
+    /// Optional max severity tier of repeat penalties. Ban of the n-th penalty is
+    /// `penalty_ban_sec * 2^min(n - 1, max_penalty_tier)`, up to 7 days. 0 (same ban for all penalties) if not set
+    pub max_penalty_tier: Option<u8>,
+    /// Optional time in seconds an invite stays in `available_players` before it's
+    /// removed and refunded. 1 day if not set
+    pub time_to_be_available_sec: Option<u32>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            // 10% total fees - 9.5% to referrer, 0.5% to cheddar distribution
+            service_fee_percentage: MAX_FEES,
+            // 95% refferer fees from 10% total fees
+            referrer_ratio: 9500,
+            // 1 hour for max_game_duration will be set
+            // also 30 min will be time budget of each player (max_game_duration / PLAYERS_NUM)
+            max_game_duration_sec: 60 * 60,
+            // 50 last games will be stored
+            max_stored_games: 50,
+            fee_schedule: None,
+            max_waiting_per_token: None,
+            ready_check_sec: None,
+            min_turn_interval_sec: None,
+            rakeback_ratio: None,
+            debug_logs: None,
+            payout_delay_sec: None,
+            tie_breaker_min_turns: None,
+            first_move_grace_refund: None,
+            deterministic_first_move: None,
+            penalty_ban_sec: None,
+            first_threat_bonus: None,
+            block_referrer_as_opponent: None,
+            turn_extension_sec: None,
+            turn_extension_price: None,
+            consolation_ratio: None,
+            min_referrer_games: None,
+            rematch_cooldown_sec: None,
+            stored_game_max_age_sec: None,
+            timeout_grace_sec: None,
+            max_penalty_tier: None,
+            time_to_be_available_sec: None,
+        }
+    }
 }
 
 impl Config {
     pub fn assert_valid(&self) {
         validate_fee(self.service_fee_percentage, self.referrer_ratio);
         validate_game_duration(self.max_game_duration_sec);
+        validate_max_stored_games(self.max_stored_games);
+        if let Some(fee_schedule) = &self.fee_schedule {
+            validate_fee_schedule(fee_schedule);
+        }
+        if let Some(max_waiting_per_token) = self.max_waiting_per_token {
+            validate_max_waiting_per_token(max_waiting_per_token);
+        }
+        if let Some(ready_check_sec) = self.ready_check_sec {
+            validate_ready_check_duration(ready_check_sec);
+        }
+        if let Some(min_turn_interval_sec) = self.min_turn_interval_sec {
+            validate_min_turn_interval(min_turn_interval_sec, self.max_game_duration_sec);
+        }
+        if let Some(rakeback_ratio) = self.rakeback_ratio {
+            validate_rakeback_ratio(rakeback_ratio, self.referrer_ratio);
+        }
+        if let Some(payout_delay_sec) = self.payout_delay_sec {
+            validate_payout_delay(payout_delay_sec);
+        }
+        if let Some(tie_breaker_min_turns) = self.tie_breaker_min_turns {
+            validate_tie_breaker_min_turns(tie_breaker_min_turns);
+        }
+        if let Some(first_threat_bonus) = self.first_threat_bonus {
+            validate_first_threat_bonus(first_threat_bonus, self.referrer_ratio, self.rakeback_ratio.unwrap_or(0));
+        }
+        if let Some(turn_extension_sec) = self.turn_extension_sec {
+            validate_turn_extension(turn_extension_sec);
+        }
+        if let Some(penalty_ban_sec) = self.penalty_ban_sec {
+            validate_penalty_ban(penalty_ban_sec);
+        }
+        if let Some(consolation_ratio) = self.consolation_ratio {
+            validate_consolation_ratio(consolation_ratio);
+        }
+        if let Some(rematch_cooldown_sec) = self.rematch_cooldown_sec {
+            validate_rematch_cooldown(rematch_cooldown_sec);
+        }
+        if let Some(stored_game_max_age_sec) = self.stored_game_max_age_sec {
+            validate_stored_game_max_age(stored_game_max_age_sec);
+        }
+        if let Some(timeout_grace_sec) = self.timeout_grace_sec {
+            validate_timeout_grace(timeout_grace_sec);
+        }
+        if let Some(max_penalty_tier) = self.max_penalty_tier {
+            validate_max_penalty_tier(max_penalty_tier);
+        }
+        if let Some(time_to_be_available_sec) = self.time_to_be_available_sec {
+            validate_time_to_be_available(time_to_be_available_sec);
+        }
+        validate_fee_parts(
+            self.service_fee_percentage,
+            self.fee_schedule.as_deref().unwrap_or_default(),
+            self.referrer_ratio,
+            self.rakeback_ratio.unwrap_or(0),
+            self.first_threat_bonus.unwrap_or(0)
+        );
     }
 }
 
 pub (crate) fn validate_fee(service_fee: u32, referrer_fee: u32) {
     assert!(
-        service_fee >= MIN_FEES && service_fee <= MAX_FEES, 
+        (MIN_FEES..=MAX_FEES).contains(&service_fee), 
         "fees need to be in range 0.1..10%"
     );
     assert!(
-        referrer_fee >= MIN_FEES && service_fee <= BASIS_P, 
+        (MIN_FEES..=BASIS_P).contains(&referrer_fee),
         "fees need to be in range 0.1..100% from total fees"
     );
 }
+/// protocol gets `service_fee * (BASIS_P - paid_out_ratio) / BASIS_P`.
+/// Service fee is never zero (see `MIN_FEES`), so protocol part must not be rounded to zero
+pub (crate) fn validate_protocol_fee(service_fee: u32, paid_out_ratio: u32) {
+    let protocol_fee = u64::from(service_fee) * u64::from(BASIS_P.saturating_sub(paid_out_ratio)) / u64::from(BASIS_P);
+    assert!(
+        protocol_fee > 0,
+        "referrer fee, rakeback and first threat bonus {} leave nothing to protocol from service fee {}", paid_out_ratio, service_fee
+    );
+}
+/// Referrer fee, rakeback and first threat bonus are paid from service fee, the rest goes to protocol.
+/// Checked for flat service fee and each fee schedule bucket, all fee setters go through it
+pub (crate) fn validate_fee_parts(
+    service_fee: u32,
+    fee_schedule: &[FeeBucket],
+    referrer_ratio: u32,
+    rakeback_ratio: u32,
+    first_threat_bonus: u32
+) {
+    let paid_out_ratio = referrer_ratio + rakeback_ratio + first_threat_bonus;
+    validate_protocol_fee(service_fee, paid_out_ratio);
+    for bucket in fee_schedule.iter() {
+        validate_protocol_fee(bucket.service_fee_percentage, paid_out_ratio);
+    }
+}
+pub (crate) fn validate_fee_schedule(fee_schedule: &[FeeBucket]) {
+    for bucket in fee_schedule.iter() {
+        assert!(
+            (MIN_FEES..=MAX_FEES).contains(&bucket.service_fee_percentage),
+            "fees need to be in range 0.1..10%"
+        );
+    }
+    assert!(
+        fee_schedule.windows(2).all(|pair| pair[0].max_turns < pair[1].max_turns),
+        "fee schedule buckets must be sorted by max_turns without duplicates"
+    );
+}
 pub (crate) fn validate_game_duration(duration_sec: u32) {
     assert!(
         duration_sec >= MIN_GAME_DURATION_SEC,
@@ -49,4 +273,114 @@ pub (crate) fn validate_game_duration(duration_sec: u32) {
         "max game duration must be less then 1 hour in seconds ({})",
         MAX_GAME_DURATION_SEC
     )
-}
\ No newline at end of file
+}
+
+pub (crate) fn validate_max_waiting_per_token(max_waiting_per_token: u32) {
+    assert!(max_waiting_per_token > 0, "max waiting entries per token must be positive");
+}
+
+pub (crate) fn validate_ready_check_duration(ready_check_sec: u32) {
+    assert!(
+        (MIN_READY_CHECK_SEC..=MAX_READY_CHECK_SEC).contains(&ready_check_sec),
+        "ready check duration need to be in range {}..{} seconds", MIN_READY_CHECK_SEC, MAX_READY_CHECK_SEC
+    );
+}
+
+/// min turn interval must be less than player's time budget (`max_game_duration` / `PLAYERS_NUM`)
+pub (crate) fn validate_min_turn_interval(min_turn_interval_sec: u32, max_game_duration_sec: u32) {
+    let per_player_budget_sec = max_game_duration_sec / PLAYERS_NUM as u32;
+    assert!(
+        min_turn_interval_sec < per_player_budget_sec,
+        "min turn interval need to be less than player time budget {} seconds", per_player_budget_sec
+    );
+}
+
+/// referrer fee and rakeback are both parts of service fee
+pub (crate) fn validate_rakeback_ratio(rakeback_ratio: u32, referrer_ratio: u32) {
+    assert!(
+        rakeback_ratio + referrer_ratio < BASIS_P,
+        "rakeback and referrer fee together need to be less than 100% from total fees"
+    );
+}
+
+/// first threat bonus is paid from service fee too
+pub (crate) fn validate_first_threat_bonus(first_threat_bonus: u32, referrer_ratio: u32, rakeback_ratio: u32) {
+    assert!(
+        first_threat_bonus + rakeback_ratio + referrer_ratio < BASIS_P,
+        "first threat bonus, rakeback and referrer fee together need to be less than 100% from total fees"
+    );
+}
+
+pub (crate) fn validate_max_stored_games(max_stored_games: u8) {
+    assert!(max_stored_games > 0, "max stored games must be positive");
+}
+
+pub (crate) fn validate_payout_delay(payout_delay_sec: u32) {
+    assert!(
+        payout_delay_sec <= MAX_PAYOUT_DELAY_SEC,
+        "payout delay need to be less then {} seconds", MAX_PAYOUT_DELAY_SEC
+    );
+}
+
+pub (crate) fn validate_tie_breaker_min_turns(tie_breaker_min_turns: u8) {
+    assert!(
+        (1..=MAX_NUM_TURNS).contains(&(tie_breaker_min_turns as u64)),
+        "tie breaker min turns need to be in range 1..{}", MAX_NUM_TURNS
+    );
+}
+
+pub (crate) fn validate_penalty_ban(penalty_ban_sec: u32) {
+    assert!(
+        penalty_ban_sec <= MAX_PENALTY_BAN_SEC,
+        "penalty ban need to be less then {} seconds", MAX_PENALTY_BAN_SEC
+    );
+}
+
+pub (crate) fn validate_turn_extension(turn_extension_sec: u32) {
+    assert!(
+        turn_extension_sec <= MAX_TURN_EXTENSION_SEC,
+        "turn extension need to be less then {} seconds", MAX_TURN_EXTENSION_SEC
+    );
+}
+
+pub (crate) fn validate_consolation_ratio(consolation_ratio: u32) {
+    assert!(
+        consolation_ratio <= MAX_CONSOLATION_RATIO,
+        "consolation ratio need to be in range 0..{} from pot", MAX_CONSOLATION_RATIO
+    );
+}
+
+pub (crate) fn validate_rematch_cooldown(rematch_cooldown_sec: u32) {
+    assert!(
+        rematch_cooldown_sec <= MAX_REMATCH_COOLDOWN_SEC,
+        "rematch cooldown need to be less then {} seconds", MAX_REMATCH_COOLDOWN_SEC
+    );
+}
+
+pub (crate) fn validate_stored_game_max_age(stored_game_max_age_sec: u32) {
+    assert!(
+        stored_game_max_age_sec == 0 || stored_game_max_age_sec >= MIN_STORED_GAME_MAX_AGE_SEC,
+        "stored game max age need to be 0 or at least {} seconds", MIN_STORED_GAME_MAX_AGE_SEC
+    );
+}
+
+pub (crate) fn validate_timeout_grace(timeout_grace_sec: u32) {
+    assert!(
+        timeout_grace_sec <= MAX_TIMEOUT_GRACE_SEC,
+        "timeout grace need to be less then {} seconds", MAX_TIMEOUT_GRACE_SEC
+    );
+}
+
+pub (crate) fn validate_max_penalty_tier(max_penalty_tier: u8) {
+    assert!(
+        max_penalty_tier <= MAX_PENALTY_TIER,
+        "max penalty tier need to be less then {}", MAX_PENALTY_TIER
+    );
+}
+
+pub (crate) fn validate_time_to_be_available(time_to_be_available_sec: u32) {
+    assert!(
+        (MIN_TIME_TO_BE_AVAILABLE_SEC..=MAX_TIME_TO_BE_AVAILABLE_SEC).contains(&time_to_be_available_sec),
+        "time to be available need to be in range {}..{} seconds", MIN_TIME_TO_BE_AVAILABLE_SEC, MAX_TIME_TO_BE_AVAILABLE_SEC
+    );
+}
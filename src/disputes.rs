@@ -0,0 +1,87 @@
+//! Dispute window. Transfers of games with pot above token threshold are deferred for `payout_delay`.
+//! Loser can dispute the result during the window (event for off-chain review),
+//! anyone can `finalize_payout` after it
+use crate::*;
+
+/// Deferred game transfers
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct DelayedPayout {
+    pub transfers: Vec<Transfer>,
+    /// players who can dispute: loser or both players for tie
+    pub disputable_by: Vec<AccountId>,
+    pub release_at: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct DelayedPayoutView {
+    pub transfers: Vec<Transfer>,
+    pub release_at_sec: u32,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// defer payouts of `token_id` games with pot above `threshold`. Delay is set by `set_payout_delay`
+    #[private]
+    pub fn set_payout_delay_threshold(&mut self, token_id: TokenContractId, threshold: U128) {
+        self.internal_bump_update_seq();
+        self.payout_delay_thresholds.insert(&token_id, &threshold.0);
+    }
+
+    #[private]
+    pub fn remove_payout_delay_threshold(&mut self, token_id: TokenContractId) {
+        self.internal_bump_update_seq();
+        self.payout_delay_thresholds.remove(&token_id);
+    }
+
+    /// flag suspected bug in finished game during dispute window. Funds are still released after it
+    pub fn dispute_payout(&mut self, game_id: GameId) {
+        self.internal_bump_update_seq();
+        let account_id = env::predecessor_account_id();
+        let payout = self.delayed_payouts.get(&game_id).expect("No delayed payout for the game");
+        assert!(env::block_timestamp() < payout.release_at, "Dispute window is over");
+        assert!(payout.disputable_by.contains(&account_id), "Only loser can dispute the payout");
+        Event::PayoutDisputed { game_id, account_id: &account_id }.emit();
+    }
+
+    /// release deferred transfers after dispute window. Callable by anyone
+    pub fn finalize_payout(&mut self, game_id: GameId) {
+        self.internal_bump_update_seq();
+        let payout = self.delayed_payouts.get(&game_id).expect("No delayed payout for the game");
+        assert!(
+            env::block_timestamp() >= payout.release_at,
+            "Too early to finalize payout. Release at {} sec", nano_to_sec(payout.release_at)
+        );
+        self.delayed_payouts.remove(&game_id);
+        self.internal_batch_transfer(payout.transfers);
+    }
+
+    pub fn get_delayed_payout(&self, game_id: GameId) -> Option<DelayedPayoutView> {
+        self.delayed_payouts.get(&game_id).map(|payout| DelayedPayoutView {
+            transfers: payout.transfers,
+            release_at_sec: nano_to_sec(payout.release_at),
+        })
+    }
+}
+
+impl Contract {
+    /// transfer game reward or defer it if pot is above token threshold
+    pub(crate) fn internal_transfer_or_delay(&mut self, game_id: &GameId, game: &Game, winner: Option<&AccountId>, transfers: Vec<Transfer>) {
+        let pot = game.reward.balance.0;
+        let is_delayed = self.payout_delay > 0 && self.payout_delay_thresholds
+            .get(&game.reward.token_id)
+            .is_some_and(|threshold| pot > threshold);
+        if !is_delayed {
+            self.internal_batch_transfer(transfers);
+            return;
+        }
+        let disputable_by = match winner {
+            Some(winner_id) => vec![game.other_player_account_id(winner_id)],
+            None => game.players.iter().map(|player| player.account_id.clone()).collect(),
+        };
+        let release_at = env::block_timestamp() + self.payout_delay;
+        log!("GameId: {}. Payout is delayed till {} sec", game_id, nano_to_sec(release_at));
+        self.delayed_payouts.insert(game_id, &DelayedPayout { transfers, disputable_by, release_at });
+    }
+}
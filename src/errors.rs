@@ -0,0 +1,62 @@
+//! Typed errors of game start checks (see `try_start_game`) and string arguments
+use crate::*;
+
+/// Why two players can't start a game. `Display` is the panic message of `start_game`
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub enum StartGameError {
+    Banned { account_id: AccountId, remaining_sec: u32 },
+    Cooldown { account_id: AccountId, remaining_sec: u32 },
+    OpponentNotReady,
+    SeveralInvites,
+    SelfPlay,
+    NotAvailable,
+    AlreadyPlaying { account_id: AccountId },
+    WrongOpponent,
+    MismatchedDeposits { you: U128, opponent: U128 },
+    MismatchedTokens,
+    MismatchedRules,
+    SelfReferral,
+    ReferrerIsOpponent,
+    TokenNotAccepted { account_id: AccountId, token_id: TokenContractId },
+}
+
+impl std::fmt::Display for StartGameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StartGameError::Banned { account_id, remaining_sec } => write!(f, "@{} is banned for penalties. {} sec left", account_id, remaining_sec),
+            StartGameError::Cooldown { account_id, remaining_sec } => write!(f, "@{} has just finished a game. {} sec of cooldown left", account_id, remaining_sec),
+            StartGameError::OpponentNotReady => write!(f, "Your opponent is not ready"),
+            StartGameError::SeveralInvites => write!(f, "Opponent has several invites. Choose one with invite_id"),
+            StartGameError::SelfPlay => write!(f, "Find a friend to play"),
+            StartGameError::NotAvailable => write!(f, "You are not in available players list!"),
+            StartGameError::AlreadyPlaying { account_id } => write!(f, "Player @{} already start another game", account_id),
+            StartGameError::WrongOpponent => write!(f, "Wrong account"),
+            StartGameError::MismatchedDeposits { you, opponent } => write!(f, "Mismatched deposits for players! You: {}, Opponent {}", you.0, opponent.0),
+            StartGameError::MismatchedTokens => write!(f, "Mismatch tokens! Choosen tokens for opponent and you must be the same"),
+            StartGameError::MismatchedRules => write!(f, "Mismatched game rules! Choosen rules for opponent and you must be the same"),
+            StartGameError::SelfReferral => write!(f, "Self referral is not allowed"),
+            StartGameError::ReferrerIsOpponent => write!(f, "Referrer can't be the opponent"),
+            StartGameError::TokenNotAccepted { account_id, token_id } => write!(f, "@{} doesn't accept games in {}", account_id, token_id),
+        }
+    }
+}
+
+/// Why string argument is rejected (see `validate_text`)
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub enum TextError {
+    TooLong { max_len: usize },
+    ControlChars,
+}
+
+impl std::fmt::Display for TextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextError::TooLong { max_len } => write!(f, "Text is too long. Max length is {}", max_len),
+            TextError::ControlChars => write!(f, "Text contains control characters"),
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! NEP-297 events
+use crate::*;
+
+const EVENT_STANDARD: &str = "cheddar-tic-tac-toe";
+const EVENT_VERSION: &str = "1.0.0";
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub(crate) enum Event<'a> {
+    GameProposed {
+        game_id: GameId,
+        proposer_id: &'a AccountId,
+        opponent_id: &'a AccountId,
+        expires_at_sec: u32,
+    },
+    GameAccepted {
+        game_id: GameId,
+    },
+    GameProposalCancelled {
+        game_id: GameId,
+    },
+    PayoutDisputed {
+        game_id: GameId,
+        account_id: &'a AccountId,
+    },
+    Chat {
+        game_id: GameId,
+        account_id: &'a AccountId,
+        text: &'a str,
+    },
+    SeatTransferred {
+        game_id: GameId,
+        old_account_id: &'a AccountId,
+        new_account_id: &'a AccountId,
+    },
+    /// `winner_id` is `None` if wins are equal
+    SeriesFinished {
+        series_id: SeriesId,
+        winner_id: Option<&'a AccountId>,
+    },
+    /// invite is added into available players list
+    PlayerAvailable {
+        account_id: &'a AccountId,
+        invite_id: InviteId,
+        token_id: &'a TokenContractId,
+        deposit: U128,
+        opponent_id: Option<&'a AccountId>,
+    },
+    /// invite is removed from available players list: cancelled, expired or taken into a game
+    PlayerUnavailable {
+        account_id: &'a AccountId,
+        invite_id: InviteId,
+        token_id: &'a TokenContractId,
+    },
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct EventLog<'a> {
+    standard: &'static str,
+    version: &'static str,
+    #[serde(flatten)]
+    event: &'a Event<'a>,
+}
+
+impl Event<'_> {
+    pub(crate) fn emit(&self) {
+        let log = EventLog {
+            standard: EVENT_STANDARD,
+            version: EVENT_VERSION,
+            event: self,
+        };
+        env::log_str(&format!(
+            "EVENT_JSON:{}",
+            near_sdk::serde_json::to_string(&log).expect("Failed to serialize event")
+        ));
+    }
+}
@@ -18,6 +18,10 @@ pub struct GameDeposit {
     pub balance: U128,
 }
 
+/// Current `Game` layout version, the first release layout is 1.
+/// Bump it with every `Game` layout change after release and keep the released layout in `legacy.rs`
+pub const GAME_VERSION: u8 = 2;
+
 #[derive(BorshSerialize, BorshDeserialize, Clone, Serialize, Deserialize)]
 #[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
 #[serde(crate = "near_sdk::serde")]
@@ -32,6 +36,22 @@ pub struct Game {
     pub initiated_at: u64,
     pub last_turn_timestamp: u64,
     pub current_duration: Duration,
+    pub rules: GameRules,
+    /// time budget left in nanoseconds by player index (see `Contract.per_player_budget`)
+    pub time_remaining: [Duration; PLAYERS_NUM],
+    /// `env::random_seed` of game creation. `seed[0] % 2` chose the first player
+    /// (see `Contract.internal_create_game`). Empty for legacy games
+    pub seed: Vec<u8>,
+    /// completed lines by player index, counted only if `rules.lines_to_win` > 1
+    pub lines_completed: [u32; PLAYERS_NUM],
+    /// the first player who had a line with one empty tile left (see `Contract.first_threat_bonus`)
+    pub first_threat_by: Option<AccountId>,
+    /// turn extension is bought by player index (see `Contract.extend_turn`)
+    pub extension_used: [bool; PLAYERS_NUM],
+    /// sum of bought extensions in nanoseconds, game duration limit is extended by it too
+    pub extended_by: Duration,
+    /// layout version (see `GAME_VERSION`)
+    pub version: u8,
 }
 
 impl Game {
@@ -42,11 +62,21 @@ impl Game {
     pub fn create_game(
         player_1: AccountId,
         player_2: AccountId,
-        reward: GameDeposit
+        reward: GameDeposit,
+        rules: GameRules,
+        per_player_budget: Duration,
     ) -> Game {
         assert_ne!(player_1, player_2, "Player 1 and Player 2 have the same AccountId: @{}", &player_1);
-        let (player_1, player_2) = Game::create_players(player_1, player_2);
-        let board = Board::new(&player_1, &player_2);
+        let (mut player_1, mut player_2) = Game::create_players(player_1, player_2);
+        // first move goes to the piece with fewer pre-placed tiles
+        if rules.handicap_pieces(player_1.piece) > rules.handicap_pieces(player_2.piece) {
+            std::mem::swap(&mut player_1.piece, &mut player_2.piece);
+        }
+        let mut board = Board::new(&player_1, &player_2);
+        // pre-placed tiles aren't moves, so they never trigger `update_winner`
+        for &(row, col, piece) in rules.handicap.iter() {
+            board.tiles[row as usize][col as usize] = Some(piece);
+        }
         let mut game = Game { 
             game_state: GameState::NotStarted, 
             players:Vec::with_capacity(PLAYERS_NUM),
@@ -59,10 +89,62 @@ impl Game {
             initiated_at: env::block_timestamp(),
             last_turn_timestamp: 0, 
             current_duration: 0,
+            rules,
+            time_remaining: [per_player_budget; PLAYERS_NUM],
+            seed: vec![],
+            lines_completed: [0; PLAYERS_NUM],
+            first_threat_by: None,
+            extension_used: [false; PLAYERS_NUM],
+            extended_by: 0,
+            version: GAME_VERSION,
         };
         game.set_players(player_1, player_2);
         game
     }
+    /// deserialize game stored in current or the first release layout (see `legacy.rs`)
+    /// and fill new fields with defaults. Legacy games get full `per_player_budget`
+    pub fn migrate(bytes: &[u8], per_player_budget: Duration) -> Game {
+        if let Ok(game) = Game::try_from_slice(bytes) {
+            if game.version == GAME_VERSION {
+                return game;
+            }
+        }
+        GameV1::try_from_slice(bytes)
+            .map(|game| game.into_game(per_player_budget))
+            .expect("Unknown game layout")
+    }
+    /// Sets board winner after the move of player with `mover_index` at (`row`, `col`).
+    /// If `rules.lines_to_win` > 1 completed lines are summed up and the game goes on
+    /// until the mover has enough of them. Full board without enough lines is a tie.
+    /// Win before `rules.min_moves_before_win` total moves doesn't count.
+    /// Also records `first_threat_by`: only lines through the last move could become threats.
+    /// Returns tiles of the line which won the game
+    pub fn update_winner(&mut self, row: usize, col: usize, mover_index: usize) -> Option<Vec<(u8, u8)>> {
+        if self.first_threat_by.is_none() && self.board.has_threat_at(row, col, self.rules.win_rule) {
+            self.first_threat_by = Some(self.players[mover_index].account_id.clone());
+        }
+        let target = self.rules.target_lines();
+        let winning_line = if target == 1 {
+            self.board.update_winner(row, col, self.rules.win_rule)
+        } else {
+            self.lines_completed[mover_index] += self.board.lines_completed_at(row, col, self.rules.win_rule);
+            if self.lines_completed[mover_index] >= target {
+                self.board.winner = Some(self.players[mover_index].piece.into());
+                self.board.winning_line_at(row, col, self.rules.win_rule)
+            } else {
+                if self.board.is_full() {
+                    self.board.winner = Some(Winner::Tie);
+                }
+                None
+            }
+        };
+        // too early win is ignored (see `GameRules.min_moves_before_win`)
+        if matches!(self.board.winner, Some(Winner::X | Winner::O)) && self.total_turns < self.rules.min_moves_before_win {
+            self.board.winner = self.board.is_full().then_some(Winner::Tie);
+            return None;
+        }
+        winning_line
+    }
     /// creates random piece for player1 and `other()` one for player2
     fn create_players(account_id_1: AccountId, account_id_2: AccountId) -> (Player, Player) {
         let piece_1 = Piece::random();
@@ -103,9 +185,9 @@ impl Game {
     }
 
     pub fn get_player_acc_by_piece(&self, piece: Piece) -> Option<&AccountId> {
-        if &piece == &self.players[0].piece {
+        if piece == self.players[0].piece {
             Some(&self.players[0].account_id)
-        } else if &piece == &self.players[1].piece {
+        } else if piece == self.players[1].piece {
             Some(&self.players[1].account_id)
         } else {
             panic!("No account with associated piece {:?}", piece)
@@ -126,15 +208,52 @@ impl Game {
         self.players[1 - index].account_id.clone()
     }
 
-    pub fn contains_player_account_id(&self, account_id: &AccountId) -> bool {
-        if &self.current_player_account_id() == account_id || &self.next_player_account_id() == account_id {
-            true
+    /// opponent of given player
+    pub fn other_player_account_id(&self, account_id: &AccountId) -> AccountId {
+        if &self.players[0].account_id == account_id {
+            self.players[1].account_id.clone()
+        } else if &self.players[1].account_id == account_id {
+            self.players[0].account_id.clone()
         } else {
-            false
+            panic!("Account @{} not in this game", account_id)
         }
     }
+
+    /// time spent by current player since the last move (or game start)
+    pub fn think_time(&self, ts: u64) -> Duration {
+        let turn_started_at = if self.last_turn_timestamp > 0 {
+            self.last_turn_timestamp
+        } else {
+            self.initiated_at
+        };
+        ts.saturating_sub(turn_started_at)
+    }
+
+    /// game duration limit with bought turn extensions
+    pub fn max_duration(&self, max_game_duration: Duration) -> Duration {
+        max_game_duration + self.extended_by
+    }
+
+    /// current player spent all their time budget and `grace`. Deadline is exclusive:
+    /// at `ts` equal to it the time is over for both `make_move` and `stop_game`
+    pub fn clock_expired(&self, ts: u64, grace: Duration) -> bool {
+        self.think_time(ts) >= self.time_remaining[self.current_player_index as usize] + grace
+    }
+
+    /// game duration limit with `grace` is over, deadline is exclusive like in `clock_expired`
+    pub fn duration_expired(&self, ts: u64, max_game_duration: Duration, grace: Duration) -> bool {
+        ts.saturating_sub(self.initiated_at) >= self.max_duration(max_game_duration) + grace
+    }
+
+    pub fn contains_player_account_id(&self, account_id: &AccountId) -> bool {
+        &self.current_player_account_id() == account_id || &self.next_player_account_id() == account_id
+    }
     pub fn reward(&self) -> GameDeposit {
         self.reward.clone()
     }
+    /// zero-stake game. Nothing is transferred on finish and players stats are kept as is
+    pub fn is_practice(&self) -> bool {
+        self.reward.balance.0 == 0
+    }
     
 }
\ No newline at end of file
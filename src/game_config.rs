@@ -1,5 +1,73 @@
 use crate::*;
 
+/// Game variant settings. Both players must agree on the same rules to start a game
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+#[serde(crate = "near_sdk::serde")]
+pub struct GameRules {
+    #[serde(default)]
+    pub win_rule: WinRule,
+    /// Connect-Four style: move sets only a column and the piece drops to the lowest empty row
+    #[serde(default)]
+    pub gravity: bool,
+    /// Misère: player who completes a line loses
+    #[serde(default)]
+    pub misere: bool,
+    /// completed lines needed to win. 0 and 1 mean classic game with a single line.
+    /// One move can complete several lines at once
+    #[serde(default)]
+    pub lines_to_win: u8,
+    /// (row, col, piece) placed on the board before the first move. The piece with fewer
+    /// pre-placed tiles moves first. Up to `MAX_HANDICAP_PIECES`
+    #[serde(default)]
+    pub handicap: Vec<(u8, u8, Piece)>,
+    /// total moves (both players) before a completed line counts. Lines completed earlier
+    /// are ignored, a line on exactly this move wins. 0 - no limit
+    #[serde(default)]
+    pub min_moves_before_win: u8,
+}
+
+impl GameRules {
+    /// completed lines needed to win, at least one
+    pub fn target_lines(&self) -> u32 {
+        self.lines_to_win.max(1) as u32
+    }
+    pub fn assert_valid(&self) {
+        assert!(
+            self.target_lines() <= self.win_rule.lines_num(),
+            "Too many lines to win. Max for {:?} is {}", self.win_rule, self.win_rule.lines_num()
+        );
+        assert!(
+            self.min_moves_before_win as usize <= BOARD_SIZE * BOARD_SIZE,
+            "Too many moves before win. Max is {}", BOARD_SIZE * BOARD_SIZE
+        );
+        assert!(
+            self.handicap.len() <= MAX_HANDICAP_PIECES,
+            "Too many handicap pieces. Max is {}", MAX_HANDICAP_PIECES
+        );
+        let mut tiles: [[Option<Piece>; BOARD_SIZE]; BOARD_SIZE] = Default::default();
+        for &(row, col, piece) in self.handicap.iter() {
+            let (row, col) = (row as usize, col as usize);
+            assert!(row < BOARD_SIZE && col < BOARD_SIZE, "Handicap tile ({}, {}) is out of board", row, col);
+            assert!(tiles[row][col].is_none(), "Handicap tiles overlap at ({}, {})", row, col);
+            tiles[row][col] = Some(piece);
+        }
+        if self.gravity {
+            for &(row, col, _) in self.handicap.iter() {
+                let (row, col) = (row as usize, col as usize);
+                assert!(
+                    row + 1 == BOARD_SIZE || tiles[row + 1][col].is_some(),
+                    "Handicap piece at ({}, {}) must lie on the bottom row or another piece", row, col
+                );
+            }
+        }
+    }
+    /// number of pre-placed tiles with `piece`
+    pub fn handicap_pieces(&self, piece: Piece) -> usize {
+        self.handicap.iter().filter(|&&(_, _, handicap_piece)| handicap_piece == piece).count()
+    }
+}
+
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
 #[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
 #[serde(crate = "near_sdk::serde")]
@@ -9,6 +77,11 @@ pub struct GameConfig {
     pub(crate) opponent_id: Option<AccountId>,
     pub(crate) referrer_id: Option<AccountId>,
     pub(crate) created_at: u64,
+    pub(crate) rules: GameRules,
+    /// prepaid deposit for automatic rematch, 0 if not set (see `rematch.rs`)
+    pub(crate) rematch_escrow: Balance,
+    /// account to send winnings to instead of the player
+    pub(crate) payout_to: Option<AccountId>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -20,6 +93,17 @@ pub struct GameConfigView {
     pub(crate) opponent_id: Option<AccountId>,
     pub(crate) referrer_id: Option<AccountId>,
     pub(crate) created_at: u32,
+    pub(crate) rules: GameRules,
+    pub(crate) rematch_escrow: U128,
+    pub(crate) payout_to: Option<AccountId>,
+    pub(crate) is_practice: bool,
+}
+
+impl GameConfig {
+    /// zero-stake invite (see `GameConfigNear.practice`)
+    pub(crate) fn is_practice(&self) -> bool {
+        self.deposit == 0
+    }
 }
 
 impl From<&GameConfig> for GameConfigView {
@@ -29,17 +113,39 @@ impl From<&GameConfig> for GameConfigView {
             deposit: gc.deposit.into(), 
             opponent_id: gc.opponent_id.clone(), 
             referrer_id: gc.referrer_id.clone(),
-            created_at: nano_to_sec(gc.created_at)
+            created_at: nano_to_sec(gc.created_at),
+            rules: gc.rules.clone(),
+            rematch_escrow: gc.rematch_escrow.into(),
+            payout_to: gc.payout_to.clone(),
+            is_practice: gc.is_practice(),
         }
     }
 }
 
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+/// `GameConfigView` with deposit token decimals to format `deposit` in UI
+#[derive(Serialize, Deserialize, Clone)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct HumanizedGameConfigView {
+    #[serde(flatten)]
+    pub(crate) config: GameConfigView,
+    /// `None` if decimals for the token weren't set by owner
+    pub(crate) decimals: Option<u8>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Default)]
 #[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
 #[serde(crate = "near_sdk::serde")]
 pub struct GameConfigNear {
     pub(crate) opponent_id: Option<AccountId>,
-    pub(crate) referrer_id: Option<AccountId>
+    pub(crate) referrer_id: Option<AccountId>,
+    pub(crate) rules: Option<GameRules>,
+    /// attached deposit is split in half: stake and rematch escrow (see `rematch.rs`)
+    pub(crate) auto_rematch: Option<bool>,
+    /// account to send winnings to instead of the player
+    pub(crate) payout_to: Option<AccountId>,
+    /// zero-stake game, no deposit must be attached. Matched with practice invites only
+    pub(crate) practice: Option<bool>,
 }
 
 impl GameConfig {
@@ -54,7 +160,10 @@ impl GameConfig {
             deposit, 
             opponent_id: None, 
             referrer_id: None,
-            created_at: env::block_timestamp()
+            created_at: env::block_timestamp(),
+            rules: GameRules::default(),
+            rematch_escrow: 0,
+            payout_to: None,
         }
     }
     /// `GameConfig` from transfer message
@@ -63,12 +172,20 @@ impl GameConfig {
         deposit: Balance,
         game_args: &GameConfigArgs
     ) -> Self {
+        let (deposit, rematch_escrow) = split_rematch_escrow(deposit, game_args.auto_rematch.unwrap_or(false));
         Self { 
             token_id: token_id.clone(), 
             deposit, 
             opponent_id: game_args.opponent_id.clone(), 
             referrer_id: game_args.referrer_id.clone(),
-            created_at: env::block_timestamp()
+            created_at: env::block_timestamp(),
+            rules: game_args.rules.clone().unwrap_or_default(),
+            rematch_escrow,
+            payout_to: game_args.payout_to.clone(),
         }
     }
+    /// stake with rematch escrow
+    pub fn total_deposit(&self) -> Balance {
+        self.deposit + self.rematch_escrow
+    }
 }
\ No newline at end of file
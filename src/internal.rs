@@ -5,38 +5,603 @@ impl Contract {
     /// Decimals must be set accurate because of counting min deposit!
     #[private]
     pub fn whitelist_token(&mut self, token_id: TokenContractId, min_deposit: U128) {
+        self.internal_bump_update_seq();
         assert!(self.whitelisted_tokens.insert(&token_id, &min_deposit.0).is_none());
     }
+    /// set max stake for whitelisted token or NEAR. `None` removes the cap
+    #[private]
+    pub fn set_max_deposit(&mut self, token_id: TokenContractId, max_deposit: Option<U128>) {
+        self.internal_bump_update_seq();
+        let min_deposit = if token_id == near_token_id() {
+            MIN_DEPOSIT_NEAR
+        } else {
+            self.whitelisted_tokens.get(&token_id).unwrap_or_else(|| panic!("Token {} is not whitelisted", &token_id))
+        };
+        match max_deposit {
+            Some(max_deposit) => {
+                assert!(max_deposit.0 >= min_deposit, "Max deposit {} is less than min deposit {}", max_deposit.0, min_deposit);
+                self.max_deposits.insert(&token_id, &max_deposit.0);
+            }
+            None => {
+                self.max_deposits.remove(&token_id);
+            }
+        }
+    }
+    /// stop (or resume) paying referrer fees in whitelisted token or NEAR.
+    /// Referrer part of service fee stays with the protocol
+    #[private]
+    pub fn set_referrer_eligible(&mut self, token_id: TokenContractId, referrer_eligible: bool) {
+        self.internal_bump_update_seq();
+        assert!(
+            token_id == near_token_id() || self.whitelisted_tokens.get(&token_id).is_some(),
+            "Token {} is not whitelisted", &token_id
+        );
+        if referrer_eligible {
+            self.referrer_ineligible_tokens.remove(&token_id);
+        } else {
+            self.referrer_ineligible_tokens.insert(&token_id);
+        }
+    }
+    /// set min number of played games of referrer to earn referrer fees, 0 to disable.
+    /// Referrers are still added to stats of new players
+    #[private]
+    pub fn set_min_referrer_games(&mut self, min_referrer_games: u32) {
+        self.internal_bump_update_seq();
+        self.min_referrer_games = min_referrer_games.into();
+    }
+    /// set time after finishing a game before the next one, 0 to disable.
+    /// Games finished while it's disabled don't start a cooldown
+    #[private]
+    pub fn set_rematch_cooldown(&mut self, rematch_cooldown_sec: u32) {
+        self.internal_bump_update_seq();
+        validate_rematch_cooldown(rematch_cooldown_sec);
+        self.rematch_cooldown = sec_to_nano(rematch_cooldown_sec);
+    }
+    /// set max severity tier of repeat penalties, 0 for the same ban after each penalty
+    #[private]
+    pub fn set_max_penalty_tier(&mut self, max_penalty_tier: u8) {
+        self.internal_bump_update_seq();
+        validate_max_penalty_tier(max_penalty_tier);
+        self.max_penalty_tier = max_penalty_tier;
+    }
+    /// set time invites stay available. Invites older than it are refunded on the next ping
+    #[private]
+    pub fn set_time_to_be_available(&mut self, time_to_be_available_sec: u32) {
+        self.internal_bump_update_seq();
+        validate_time_to_be_available(time_to_be_available_sec);
+        self.time_to_be_available = sec_to_nano(time_to_be_available_sec);
+    }
+    /// set time added to turn and game deadlines, 0 to disable
+    #[private]
+    pub fn set_timeout_grace(&mut self, timeout_grace_sec: u32) {
+        self.internal_bump_update_seq();
+        validate_timeout_grace(timeout_grace_sec);
+        self.timeout_grace = sec_to_nano(timeout_grace_sec);
+    }
+    /// set age of stored games to be evicted, 0 to disable. Expired stored games are evicted at once
+    #[private]
+    pub fn set_stored_game_max_age(&mut self, stored_game_max_age_sec: u32) {
+        self.internal_bump_update_seq();
+        validate_stored_game_max_age(stored_game_max_age_sec);
+        self.stored_game_max_age = sec_to_nano(stored_game_max_age_sec);
+        self.internal_evict_expired_stored_games();
+    }
+    /// set token decimals used by `get_available_players_humanized`. NEAR has `NEAR_DECIMALS`
+    #[private]
+    pub fn set_token_decimals(&mut self, token_id: TokenContractId, decimals: u8) {
+        self.internal_bump_update_seq();
+        assert!(self.whitelisted_tokens.get(&token_id).is_some(), "Token {} is not whitelisted", &token_id);
+        self.token_decimals.insert(&token_id, &decimals);
+    }
     /// set accuracy, service fees need to be in range [0.1..10%]
     /// also referrer_fee need to be [0..50%] from service fee
     #[private]
     pub fn set_service_fee(&mut self, service_fee: u32, referrer_fee: u32) -> bool {
+        self.internal_bump_update_seq();
         validate_fee(service_fee, referrer_fee);
+        validate_rakeback_ratio(self.rakeback_ratio, referrer_fee);
+        validate_first_threat_bonus(self.first_threat_bonus, referrer_fee, self.rakeback_ratio);
+        validate_fee_parts(service_fee, &self.fee_schedule, referrer_fee, self.rakeback_ratio, self.first_threat_bonus);
         self.service_fee_percentage = service_fee;
         self.referrer_ratio = referrer_fee;
         true
     }
+    /// set service fee buckets by game length, each fee needs to be in range [0.1..10%].
+    /// Empty schedule means flat `service_fee_percentage` for all games
+    #[private]
+    pub fn set_fee_schedule(&mut self, fee_schedule: Vec<FeeBucket>) -> bool {
+        self.internal_bump_update_seq();
+        validate_fee_schedule(&fee_schedule);
+        validate_fee_parts(self.service_fee_percentage, &fee_schedule, self.referrer_ratio, self.rakeback_ratio, self.first_threat_bonus);
+        self.fee_schedule = fee_schedule;
+        true
+    }
+    /// set max number of available players with the same token
+    #[private]
+    pub fn set_max_waiting_per_token(&mut self, max_waiting_per_token: u32) -> bool {
+        self.internal_bump_update_seq();
+        validate_max_waiting_per_token(max_waiting_per_token);
+        self.max_waiting_per_token = max_waiting_per_token;
+        true
+    }
+    /// set time for opponent to accept proposed game (see `propose_game`)
+    #[private]
+    pub fn set_ready_check_duration(&mut self, ready_check_sec: u32) -> bool {
+        self.internal_bump_update_seq();
+        validate_ready_check_duration(ready_check_sec);
+        self.ready_check_duration = sec_to_nano(ready_check_sec);
+        true
+    }
+    /// set min time between turns, 0 to disable
+    #[private]
+    pub fn set_min_turn_interval(&mut self, min_turn_interval_sec: u32) -> bool {
+        self.internal_bump_update_seq();
+        validate_min_turn_interval(min_turn_interval_sec, nano_to_sec(self.max_game_duration));
+        self.min_turn_interval = sec_to_nano(min_turn_interval_sec);
+        true
+    }
+    /// set part of service fee returned to players as rakeback, 0 to disable
+    #[private]
+    pub fn set_rakeback_ratio(&mut self, rakeback_ratio: u32) -> bool {
+        self.internal_bump_update_seq();
+        validate_rakeback_ratio(rakeback_ratio, self.referrer_ratio);
+        validate_first_threat_bonus(self.first_threat_bonus, self.referrer_ratio, rakeback_ratio);
+        validate_fee_parts(self.service_fee_percentage, &self.fee_schedule, self.referrer_ratio, rakeback_ratio, self.first_threat_bonus);
+        self.rakeback_ratio = rakeback_ratio;
+        true
+    }
+    /// set part of service fee paid to the player who first made a threat, 0 to disable
+    #[private]
+    pub fn set_first_threat_bonus(&mut self, first_threat_bonus: u32) -> bool {
+        self.internal_bump_update_seq();
+        validate_first_threat_bonus(first_threat_bonus, self.referrer_ratio, self.rakeback_ratio);
+        validate_fee_parts(self.service_fee_percentage, &self.fee_schedule, self.referrer_ratio, self.rakeback_ratio, first_threat_bonus);
+        self.first_threat_bonus = first_threat_bonus;
+        true
+    }
+    /// set max number of stored games. If lowered, the oldest stored games are evicted
+    #[private]
+    pub fn set_max_stored_games(&mut self, max_stored_games: u8) -> bool {
+        self.internal_bump_update_seq();
+        validate_max_stored_games(max_stored_games);
+        self.max_stored_games = max_stored_games;
+        while self.get_stored_games_num() > max_stored_games {
+            self.internal_evict_oldest_stored_game();
+        }
+        true
+    }
+    /// set dispute window before large payouts, 0 to disable (see `disputes.rs`)
+    #[private]
+    pub fn set_payout_delay(&mut self, payout_delay_sec: u32) -> bool {
+        self.internal_bump_update_seq();
+        validate_payout_delay(payout_delay_sec);
+        self.payout_delay = sec_to_nano(payout_delay_sec);
+        true
+    }
+    /// cancel game with full refunds instead of penalty if the first move times out
+    #[private]
+    pub fn set_first_move_grace_refund(&mut self, first_move_grace_refund: bool) {
+        self.internal_bump_update_seq();
+        self.first_move_grace_refund = first_move_grace_refund;
+    }
+    /// set matchmaking ban after each penalty, 0 to disable. Current bans are kept
+    #[private]
+    pub fn set_penalty_ban(&mut self, penalty_ban_sec: u32) {
+        self.internal_bump_update_seq();
+        validate_penalty_ban(penalty_ban_sec);
+        self.penalty_ban = sec_to_nano(penalty_ban_sec);
+    }
+    /// reject games where player's referrer from stats is the opponent
+    #[private]
+    pub fn set_block_referrer_as_opponent(&mut self, block_referrer_as_opponent: bool) {
+        self.internal_bump_update_seq();
+        self.block_referrer_as_opponent = block_referrer_as_opponent;
+    }
+    /// set time added by `extend_turn` and its price in yoctoNEAR. 0 seconds disables extensions
+    #[private]
+    pub fn set_turn_extension(&mut self, turn_extension_sec: u32, turn_extension_price: U128) {
+        self.internal_bump_update_seq();
+        validate_turn_extension(turn_extension_sec);
+        self.turn_extension = sec_to_nano(turn_extension_sec);
+        self.turn_extension_price = turn_extension_price.0;
+    }
+    /// set part of pot returned to the loser, 0 for winner-take-all
+    #[private]
+    pub fn set_consolation_ratio(&mut self, consolation_ratio: u32) {
+        self.internal_bump_update_seq();
+        validate_consolation_ratio(consolation_ratio);
+        self.consolation_ratio = consolation_ratio;
+    }
+    /// initiator always moves first instead of random choice. For tests and testnet only
+    #[private]
+    pub fn set_deterministic_first_move(&mut self, deterministic_first_move: bool) {
+        self.internal_bump_update_seq();
+        self.deterministic_first_move = deterministic_first_move;
+    }
+    /// set min `total_turns` to resolve timed out games by open lines. `None` disables it
+    #[private]
+    pub fn set_tie_breaker_min_turns(&mut self, tie_breaker_min_turns: Option<u8>) {
+        self.internal_bump_update_seq();
+        if let Some(tie_breaker_min_turns) = tie_breaker_min_turns {
+            validate_tie_breaker_min_turns(tie_breaker_min_turns);
+        }
+        self.tie_breaker_min_turns = tie_breaker_min_turns;
+    }
+    /// enable verbose logs
+    #[private]
+    pub fn set_debug_logs(&mut self, debug_logs: bool) {
+        self.internal_bump_update_seq();
+        self.debug_logs = debug_logs;
+    }
     /// set accuracy, max_duration need to be in range [100..3600] seconds
     #[private]
     pub fn set_max_duration(&mut self, max_duration: u32) -> bool {
+        self.internal_bump_update_seq();
         validate_game_duration(max_duration);
+        validate_min_turn_interval(nano_to_sec(self.min_turn_interval), max_duration);
         self.max_game_duration = sec_to_nano(max_duration);
-        self.max_turn_duration = self.max_game_duration / MAX_NUM_TURNS;
+        self.per_player_budget = self.max_game_duration / PLAYERS_NUM as u64;
         true
     }
 }
 
 impl Contract {
 
-    pub (crate) fn internal_get_available_player(&self, account_id: &AccountId) -> GameConfig {
-        self.available_players.get(account_id).expect("You are not in available players list!")
+    pub (crate) fn internal_bump_update_seq(&mut self) {
+        self.update_seq += 1;
+    }
+
+    /// logs only if `debug_logs` is enabled. Message is formatted lazily
+    pub (crate) fn internal_debug_log<F: FnOnce() -> String>(&self, message: F) {
+        if self.debug_logs {
+            log!("{}", message());
+        }
+    }
+
+    /// `None` if decimals for the token weren't set by owner
+    pub (crate) fn internal_get_token_decimals(&self, token_id: &TokenContractId) -> Option<u8> {
+        if *token_id == near_token_id() {
+            Some(NEAR_DECIMALS)
+        } else {
+            self.token_decimals.get(token_id)
+        }
+    }
+
+    /// all open invites of `account_id` ordered by id
+    pub (crate) fn internal_get_player_invites(&self, account_id: &AccountId) -> Vec<(InviteId, GameConfig)> {
+        let mut invites: Vec<(InviteId, GameConfig)> = self.available_players
+            .iter()
+            .filter(|((player_id, _), _)| player_id == account_id)
+            .map(|((_, invite_id), config)| (invite_id, config))
+            .collect();
+        invites.sort_by_key(|(invite_id, _)| *invite_id);
+        invites
+    }
+
+    pub (crate) fn internal_assert_invites_limit(&self, account_id: &AccountId) {
+        assert!(
+            self.internal_get_player_invites(account_id).len() < MAX_INVITES_PER_PLAYER,
+            "Too many open invites. Max {}", MAX_INVITES_PER_PLAYER
+        );
+    }
+
+    /// rematch escrow isn't counted, same as for min deposit
+    pub (crate) fn internal_exceeds_max_deposit(&self, token_id: &TokenContractId, deposit: Balance) -> bool {
+        self.max_deposits.get(token_id).is_some_and(|max_deposit| deposit > max_deposit)
+    }
+
+    pub (crate) fn internal_assert_max_deposit(&self, token_id: &TokenContractId, deposit: Balance) {
+        assert!(
+            !self.internal_exceeds_max_deposit(token_id, deposit),
+            "Deposit is too big. Max deposit for {} is {}", token_id, self.max_deposits.get(token_id).unwrap_or_default()
+        );
+    }
+
+    pub (crate) fn internal_assert_waiting_capacity(&self, token_id: &TokenContractId) {
+        let waiting = self.waiting_counts.get(token_id).unwrap_or(0);
+        assert!(
+            waiting < self.max_waiting_per_token,
+            "Too many players are waiting with {}. Try later", token_id
+        );
+    }
+
+    /// invite can be listed again within `make_available` limits (invites per player, waiting capacity)
+    pub (crate) fn internal_has_invite_room(&self, account_id: &AccountId, config: &GameConfig) -> bool {
+        let has_capacity = if config.is_practice() {
+            self.practice_waiting < MAX_PRACTICE_WAITING
+        } else {
+            self.waiting_counts.get(&config.token_id).unwrap_or(0) < self.max_waiting_per_token
+        };
+        has_capacity && self.internal_get_player_invites(account_id).len() < MAX_INVITES_PER_PLAYER
+    }
+
+    /// free practice invites have own cap, so they can't fill `max_waiting_per_token` of NEAR
+    pub (crate) fn internal_assert_practice_capacity(&self) {
+        assert!(
+            self.practice_waiting < MAX_PRACTICE_WAITING,
+            "Too many practice games are waiting. Try later"
+        );
+    }
+
+    /// all changes of `available_players` go through this and `internal_remove_available_player`
+    /// to keep `waiting_counts` and `practice_waiting` in sync
+    pub (crate) fn internal_insert_available_player(&mut self, account_id: &AccountId, invite_id: InviteId, config: &GameConfig) {
+        if let Some(prev_config) = self.available_players.insert(&(account_id.clone(), invite_id), config) {
+            self.internal_update_waiting_count(&prev_config, false);
+        }
+        self.internal_update_waiting_count(config, true);
+        Event::PlayerAvailable {
+            account_id,
+            invite_id,
+            token_id: &config.token_id,
+            deposit: config.deposit.into(),
+            opponent_id: config.opponent_id.as_ref(),
+        }.emit();
+    }
+
+    /// insert config as new invite. Returns its id
+    pub (crate) fn internal_add_invite(&mut self, account_id: &AccountId, config: &GameConfig) -> InviteId {
+        config.rules.assert_valid();
+        let invite_id = self.next_invite_id;
+        self.next_invite_id += 1;
+        self.internal_insert_available_player(account_id, invite_id, config);
+        invite_id
+    }
+
+    pub (crate) fn internal_remove_available_player(&mut self, account_id: &AccountId, invite_id: InviteId) -> Option<GameConfig> {
+        let config = self.available_players.remove(&(account_id.clone(), invite_id))?;
+        self.internal_update_waiting_count(&config, false);
+        Event::PlayerUnavailable { account_id, invite_id, token_id: &config.token_id }.emit();
+        Some(config)
+    }
+
+    fn internal_update_waiting_count(&mut self, config: &GameConfig, increase: bool) {
+        if config.is_practice() {
+            self.practice_waiting = if increase { self.practice_waiting + 1 } else { self.practice_waiting.saturating_sub(1) };
+            return;
+        }
+        let token_id = &config.token_id;
+        let waiting = self.waiting_counts.get(token_id).unwrap_or(0);
+        if increase {
+            self.waiting_counts.insert(token_id, &(waiting + 1));
+        } else if waiting > 1 {
+            self.waiting_counts.insert(token_id, &(waiting - 1));
+        } else {
+            self.waiting_counts.remove(token_id);
+        }
+    }
+
+    /// `start_game` and `try_start_game`
+    pub (crate) fn internal_start_game(&mut self, player_2_id: AccountId, invite_id: Option<InviteId>) -> Result<GameId, StartGameError> {
+        self.internal_bump_update_seq();
+
+        let player_1_id = env::predecessor_account_id();
+        let (player_1_config, player_2_config) = self.internal_take_players(&player_1_id, &player_2_id, invite_id)?;
+        let game_id = self.internal_create_game(&player_1_id, &player_2_id, &player_2_config);
+        self.internal_activate_game(&game_id, &player_1_id, player_1_config, &player_2_id, player_2_config);
+        Ok(game_id)
+    }
+
+    /// Check both players are ready to play together and remove their invites from available players list.
+    /// `invite_id` is opponent's invite, it may be omitted if opponent has only one.
+    /// Player 1 invite is chosen by opponent's token, deposit and rules.
+    /// Nothing is changed on error. Returns their game configs
+    pub (crate) fn internal_take_players(
+        &mut self,
+        player_1_id: &AccountId,
+        player_2_id: &AccountId,
+        invite_id: Option<InviteId>,
+    ) -> Result<(GameConfig, GameConfig), StartGameError> {
+        for account_id in [player_1_id, player_2_id] {
+            self.internal_check_not_restricted(account_id)?;
+        }
+        let (player_2_invite_id, player_2_config) = match invite_id {
+            Some(invite_id) => self.available_players
+                .get(&(player_2_id.clone(), invite_id))
+                .map(|config| (invite_id, config))
+                .ok_or(StartGameError::OpponentNotReady)?,
+            None => {
+                let mut invites = self.internal_get_player_invites(player_2_id);
+                if invites.is_empty() {
+                    return Err(StartGameError::OpponentNotReady);
+                }
+                if invites.len() > 1 {
+                    return Err(StartGameError::SeveralInvites);
+                }
+                invites.remove(0)
+            }
+        };
+        if player_1_id == player_2_id {
+            return Err(StartGameError::SelfPlay);
+        }
+
+        // Get predecessor's available deposit. Mismatched invite is checked below to report the difference
+        let mut player_1_invites = self.internal_get_player_invites(player_1_id);
+        if player_1_invites.is_empty() {
+            return Err(StartGameError::NotAvailable);
+        }
+        let matching_index = player_1_invites
+            .iter()
+            .position(|(_, config)| config.token_id == player_2_config.token_id
+                && config.deposit == player_2_config.deposit
+                && config.rules == player_2_config.rules)
+            .unwrap_or(0);
+        let (player_1_invite_id, player_1_config) = player_1_invites.swap_remove(matching_index);
+
+        for account_id in [player_1_id, player_2_id] {
+            if self.internal_has_active_game(account_id) {
+                return Err(StartGameError::AlreadyPlaying { account_id: account_id.clone() });
+            }
+        }
+        if player_2_config.opponent_id.as_ref().is_some_and(|opponent_id| opponent_id != player_1_id) {
+            return Err(StartGameError::WrongOpponent);
+        }
+
+        // Deposits from two players must be equal
+        if player_1_config.deposit != player_2_config.deposit {
+            return Err(StartGameError::MismatchedDeposits {
+                you: player_1_config.deposit.into(),
+                opponent: player_2_config.deposit.into(),
+            });
+        }
+        if player_1_config.token_id != player_2_config.token_id {
+            return Err(StartGameError::MismatchedTokens);
+        }
+        if player_1_config.rules != player_2_config.rules {
+            return Err(StartGameError::MismatchedRules);
+        }
+        // accepted tokens could be changed after the invite
+        for account_id in [player_1_id, player_2_id] {
+            self.internal_check_accepts_token(account_id, &player_1_config.token_id)?;
+        }
+        check_referrer(player_1_id, player_1_config.referrer_id.as_ref(), Some(player_2_id))?;
+        check_referrer(player_2_id, player_2_config.referrer_id.as_ref(), Some(player_1_id))?;
+        if self.block_referrer_as_opponent {
+            // referrer from the first game is kept in stats
+            check_referrer(player_1_id, self.internal_get_stats(player_1_id).referrer_id.as_ref(), Some(player_2_id))?;
+            check_referrer(player_2_id, self.internal_get_stats(player_2_id).referrer_id.as_ref(), Some(player_1_id))?;
+        }
+
+        self.internal_remove_available_player(player_1_id, player_1_invite_id);
+        self.internal_remove_available_player(player_2_id, player_2_invite_id);
+        // one active game per account, so other invites of both players are refunded
+        for account_id in [player_1_id, player_2_id] {
+            for (invite_id, _) in self.internal_get_player_invites(account_id) {
+                self.internal_refund_available_deposit(account_id, invite_id, 0);
+            }
+        }
+        Ok((player_1_config, player_2_config))
+    }
+
+    /// Player isn't banned for penalties and has no cooldown after the last game
+    pub (crate) fn internal_check_not_restricted(&self, account_id: &AccountId) -> Result<(), StartGameError> {
+        if let Some(remaining) = self.is_banned(account_id.clone()) {
+            return Err(StartGameError::Banned { account_id: account_id.clone(), remaining_sec: nano_to_sec(remaining) });
+        }
+        if let Some(remaining) = self.get_remaining_cooldown(account_id.clone()) {
+            return Err(StartGameError::Cooldown { account_id: account_id.clone(), remaining_sec: nano_to_sec(remaining) });
+        }
+        Ok(())
+    }
+
+    pub (crate) fn internal_check_accepts_token(&self, account_id: &AccountId, token_id: &TokenContractId) -> Result<(), StartGameError> {
+        if !self.internal_accepts_token(account_id, token_id) {
+            return Err(StartGameError::TokenNotAccepted { account_id: account_id.clone(), token_id: token_id.clone() });
+        }
+        Ok(())
+    }
+
+    /// Game start checks of player who doesn't make new invite (rematch, next series game)
+    pub (crate) fn internal_check_player_eligible(&self, account_id: &AccountId, token_id: &TokenContractId) -> Result<(), StartGameError> {
+        self.internal_check_not_restricted(account_id)?;
+        self.internal_check_accepts_token(account_id, token_id)
+    }
+
+    pub (crate) fn internal_check_players_eligible(
+        &self,
+        player_ids: [&AccountId; PLAYERS_NUM],
+        token_id: &TokenContractId
+    ) -> Result<(), StartGameError> {
+        for account_id in player_ids {
+            self.internal_check_player_eligible(account_id, token_id)?;
+        }
+        Ok(())
+    }
+
+    /// Create not started game. First move is chosen randomly
+    pub (crate) fn internal_create_game(&mut self, player_1_id: &AccountId, player_2_id: &AccountId, config: &GameConfig) -> GameId {
+        let game_id = self.next_game_id;
+        let token_id = config.token_id.clone();
+        // deposit * 2
+        let balance = match config.deposit.checked_mul(2) {
+            Some(value) => value,
+            None => panic!("multiplication overflow, too big deposit amount"),
+        };
+
+        let reward = GameDeposit {
+            token_id: token_id.clone(),
+            balance: balance.into()
+        };
+        self.internal_debug_log(|| format!("game reward:{} in token {:?} ", balance, token_id));
+        
+        let seed = near_sdk::env::random_seed();
+        // `player_1_id` is the initiator
+        let initiator_first = self.deterministic_first_move || seed[0] % 2 == 1;
+        let (first_id, second_id) = if initiator_first {
+            (player_1_id, player_2_id)
+        } else {
+            (player_2_id, player_1_id)
+        };
+        let mut game = Game::create_game(
+            first_id.clone(),
+            second_id.clone(),
+            reward,
+            config.rules.clone(),
+            self.per_player_budget
+        );
+        // kept to verify first player choice after the game
+        game.seed = seed;
+
+        self.games.insert(&game_id, &game);
+        self.next_game_id += 1;
+        game_id
+    }
+
+    /// Make created game active. Game duration is counted from this moment
+    pub (crate) fn internal_activate_game(
+        &mut self,
+        game_id: &GameId,
+        player_1_id: &AccountId,
+        player_1_config: GameConfig,
+        player_2_id: &AccountId,
+        player_2_config: GameConfig,
+    ) {
+        let mut game = self.internal_get_game(game_id);
+        game.change_state(GameState::Active);
+        game.initiated_at = env::block_timestamp();
+        self.games.insert(game_id, &game);
+        self.internal_hold_rematch_escrows(game_id, vec![
+            (player_1_id.clone(), player_1_config.rematch_escrow),
+            (player_2_id.clone(), player_2_config.rematch_escrow),
+        ]);
+        let mut payout_targets: Vec<(AccountId, AccountId)> = Vec::new();
+        for (player_id, payout_to) in [(player_1_id, &player_1_config.payout_to), (player_2_id, &player_2_config.payout_to)] {
+            if let Some(payout_to) = payout_to.as_ref().filter(|payout_to| *payout_to != player_id) {
+                payout_targets.push((player_id.clone(), payout_to.clone()));
+            }
+        }
+        if !payout_targets.is_empty() {
+            self.payout_targets.insert(game_id, &payout_targets);
+        }
+
+        if let Some(referrer_id) = player_1_config.referrer_id {
+            self.internal_add_referrer(player_1_id, &referrer_id);
+        }
+        if let Some(referrer_id) = player_2_config.referrer_id {
+            self.internal_add_referrer(player_2_id, &referrer_id);
+        }
+
+        // practice game isn't counted in stats and volume
+        if game.is_practice() {
+            return;
+        }
+        let token_id = player_1_config.token_id;
+        self.internal_update_stats(Some(&token_id), player_1_id, UpdateStatsAction::AddPlayedGame, None, None);
+        self.internal_update_stats(Some(&token_id), player_2_id, UpdateStatsAction::AddPlayedGame, None, None);
+        self.internal_update_stats(Some(&token_id), player_1_id, UpdateStatsAction::AddWageredAmount, None, Some(player_1_config.deposit));
+        self.internal_update_stats(Some(&token_id), player_2_id, UpdateStatsAction::AddWageredAmount, None, Some(player_2_config.deposit));
+        let volume = self.total_volume.get(&token_id).unwrap_or(0);
+        self.total_volume.insert(&token_id, &(volume + player_1_config.deposit + player_2_config.deposit));
     }
 
-    pub (crate) fn internal_ping_expired_games(&mut self, ts: u64) {
+    /// Stop active games with expired duration. `skip_game_id` is checked by the caller
+    pub (crate) fn internal_ping_expired_games(&mut self, ts: u64, skip_game_id: Option<&GameId>) {
         let expired_games_ids: Vec<GameId> = self.games
             .iter()
-            .filter(|(_, game)| {
-                ts - game.initiated_at > self.max_game_duration
+            .filter(|(game_id, game)| {
+                skip_game_id != Some(game_id)
+                    && game.game_state == GameState::Active
+                    && game.duration_expired(ts, self.max_game_duration, self.timeout_grace)
             })
             .map(|(game_id, _) | game_id)
             .collect();
@@ -44,33 +609,25 @@ impl Contract {
             for game_id in expired_games_ids.iter() {
                 let game = self.internal_get_game(game_id);
                 self.internal_stop_expired_game(game_id, game.current_player_account_id());
-                log!("GameId: {}. Game duration expired. Required:{} Current:{} ", game_id, self.max_game_duration, ts - game.initiated_at);
+                log!("GameId: {}. Game duration expired. Required:{} Current:{} ", game_id, game.max_duration(self.max_game_duration), ts - game.initiated_at);
             }
         }
         self.last_update_timestamp = ts;
     }
 
     pub (crate) fn internal_ping_expired_players(&mut self, ts: u64) {
-        let expired_players: Vec<(AccountId, GameConfig)> = self.available_players
+        let expired_players: Vec<((AccountId, InviteId), GameConfig)> = self.available_players
             .iter()
             .filter(|(_, config)| {
-                ts - config.created_at > MAX_TIME_TO_BE_AVAILABLE
+                ts - config.created_at > self.time_to_be_available
             })
-            .map(|(account_id, config)| (account_id.clone(), config))
             .collect();
         if !expired_players.is_empty() {
-            for (account_id, config) in expired_players.iter() {
-                let token_id = config.token_id.clone();
-                self.available_players.remove(&account_id);
-
-                self.internal_transfer(&token_id, &account_id, config.deposit.into())
-                    .then(Self::ext(env::current_account_id())
-                    .with_static_gas(CALLBACK_GAS)
-                    .transfer_deposit_callback(account_id.clone(), config)
-                );
+            for ((account_id, invite_id), config) in expired_players.iter() {
+                self.internal_refund_available_deposit(account_id, *invite_id, 0);
                 log!(
                     "Remove expired player @{}, refund {} of {}",
-                    account_id, config.deposit, config.token_id
+                    account_id, config.total_deposit(), config.token_id
                 );
             }
         }
@@ -83,7 +640,7 @@ impl Contract {
         receiver_id: &AccountId,
         amount: U128
     ) -> Promise {
-        if token_id == &AccountId::new_unchecked("near".into()) {
+        if token_id == &near_token_id() {
             Promise::new(receiver_id.clone()).transfer(amount.0)
         } else {
             ext_ft::ext(token_id.clone())
@@ -96,25 +653,45 @@ impl Contract {
         &mut self,
         game_id: &GameId,
         winner: Option<&AccountId>,
-    )  -> U128 {
-        let reward = self.internal_get_game_reward(game_id);
+    )  -> GameDeposit {
+        let game = self.internal_get_game(game_id);
+        let reward = game.reward();
         let players_deposit = reward.balance;
         let token_id = reward.token_id.clone();
-        let fees_amount = players_deposit.0
-            .checked_div(BASIS_P.into())
-            .unwrap_or(0)
-            .checked_mul(self.service_fee_percentage as u128)
-            .unwrap_or(0);
+        let service_fee_percentage = self.internal_service_fee(game.total_turns);
+        let (fees_amount, winner_reward, consolation) = if winner.is_some() {
+            let split = split_win_pot(players_deposit.0, service_fee_percentage, self.consolation_ratio);
+            (split.fee, split.winner_reward, split.consolation)
+        } else {
+            let (fee, refund) = split_pot(players_deposit.0, service_fee_percentage, PLAYERS_NUM as u128);
+            (fee, refund, 0)
+        };
         assert!(fees_amount > 0, "Incorrect fees computing");
+        let total_fees = self.total_fees.get(&token_id).unwrap_or(0);
+        self.total_fees.insert(&token_id, &(total_fees + fees_amount));
+        let mut transfers = Vec::new();
 
-        let winner_reward: Balance = players_deposit.0 - fees_amount;
+        let referrer_id = winner.and_then(|winner_id| self.internal_eligible_referrer(&token_id, winner_id));
+        let fee_split = self.internal_split_fee(fees_amount, referrer_id.is_some(), game.first_threat_by.is_some());
+        self.internal_accrue_rakeback(game_id, &token_id, fee_split.rakeback_each);
+        self.internal_pay_first_threat_bonus(game_id, &game, &token_id, fee_split.first_threat_bonus, &mut transfers);
 
-        if let Some(winner_id) = winner {
-            log!("Winner is {}. Reward: {}", winner_id, winner_reward);
+        let game_reward = if let Some(winner_id) = winner {
+            if consolation > 0 {
+                self.internal_pay_consolation(game_id, &game, winner_id, &token_id, consolation, &mut transfers);
+            }
+            let payout = self.internal_winner_payout(&token_id, winner_reward);
+            log!("Winner is {}. Reward: {} of {}", winner_id, payout.balance.0, payout.token_id);
 
-            self.internal_transfer(&token_id, winner_id, winner_reward.into());
+            transfers.push(Transfer {
+                receiver_id: self.internal_get_payout_to(game_id, winner_id),
+                token_id: payout.token_id.clone(),
+                amount: payout.balance,
+            });
 
-            self.internal_distribute_fee(&token_id, fees_amount, winner_id);
+            if let Some(referrer_id) = referrer_id {
+                self.internal_pay_referrer_fee(&token_id, &referrer_id, fee_split.referrer_fee, &mut transfers);
+            }
             self.internal_update_stats(
                 Some(&token_id), 
                 winner_id, 
@@ -123,91 +700,188 @@ impl Contract {
                 None
             );
             self.internal_update_stats(
-                Some(&token_id), 
+                Some(&payout.token_id), 
                 winner_id, 
                 UpdateStatsAction::AddTotalReward, 
                 None, 
-                Some(winner_reward)
+                Some(payout.balance.0)
             );
-            winner_reward.into()
+            payout
         } else {
-            let refund_amount = match winner_reward.checked_div(PLAYERS_NUM as u128) {
-                Some(amount) => amount,
-                None => panic!("Failed divide deposit to refund GameDeposit (GameResult::Tie)")
-            };
-            assert!(
-                refund_amount.checked_mul(PLAYERS_NUM as u128) < Some(reward.balance.0),
+            // `winner_reward` is a refund of each player here
+            let refund_amount = winner_reward;
+            assert_eq!(
+                refund_amount * PLAYERS_NUM as u128 + fees_amount,
+                reward.balance.0,
                 "Incorrect Tie refund amount calculation"
             );
             log!("Tie. Refund: {}", refund_amount);
             self.internal_tie_refund(
                 game_id, 
                 &token_id, 
-                refund_amount
+                refund_amount,
+                &mut transfers
             );
-            refund_amount.into()
-        }
+            GameDeposit { token_id, balance: refund_amount.into() }
+        };
+
+        self.internal_transfer_or_delay(game_id, &game, winner, transfers);
+        game_reward
     }
 
-    pub (crate) fn internal_distribute_fee(
+    /// part of pot back to the loser (see `Config.consolation_ratio`). Paid in stake token
+    pub (crate) fn internal_pay_consolation(
         &mut self,
+        game_id: &GameId,
+        game: &Game,
+        winner_id: &AccountId,
         token_id: &TokenContractId,
-        service_fee: Balance,
-        account_id: &AccountId
-    ) -> Balance {
-        // potential referrer fee
-        let stats = self.internal_get_stats(account_id);
-        let referrer_fee = if let Some(referrer_id) = stats.referrer_id {
-            let computed_referrer_fee = service_fee
-                .checked_div(BASIS_P.into())
-                .unwrap_or(0)
-                .checked_mul(self.referrer_ratio as u128)
-                .unwrap_or(0);
-            
-            if computed_referrer_fee > 0 {
-                log!("Affiliate reward for @{} is {}", referrer_id, computed_referrer_fee);
-                self.internal_update_stats(
-                    Some(token_id), 
-                    &referrer_id, 
-                    UpdateStatsAction::AddAffiliateReward, 
-                    None, 
-                    Some(computed_referrer_fee)
-                );
-                // transfer fee to referrer
-                self.internal_transfer(&token_id, &referrer_id, computed_referrer_fee.into());
-            }
+        consolation: Balance,
+        transfers: &mut Vec<Transfer>
+    ) {
+        let loser_id = game.other_player_account_id(winner_id);
+        log!("Consolation for @{} is {}", loser_id, consolation);
+        transfers.push(Transfer {
+            receiver_id: self.internal_get_payout_to(game_id, &loser_id),
+            token_id: token_id.clone(),
+            amount: consolation.into(),
+        });
+        self.internal_update_stats(
+            Some(token_id), 
+            &loser_id, 
+            UpdateStatsAction::AddTotalReward, 
+            None, 
+            Some(consolation)
+        );
+    }
+
+    /// `bonus` part of service fee (see `FeeSplit`) to the first player who had a line with one empty tile left
+    pub (crate) fn internal_pay_first_threat_bonus(
+        &self,
+        game_id: &GameId,
+        game: &Game,
+        token_id: &TokenContractId,
+        bonus: Balance,
+        transfers: &mut Vec<Transfer>
+    ) {
+        let account_id = match game.first_threat_by.as_ref() {
+            Some(account_id) if bonus > 0 => account_id,
+            _ => return,
+        };
+        log!("First threat bonus for @{} is {}", account_id, bonus);
+        transfers.push(Transfer {
+            receiver_id: self.internal_get_payout_to(game_id, account_id),
+            token_id: token_id.clone(),
+            amount: bonus.into(),
+        });
+    }
+
+    /// service fee for game finished in `total_turns` turns
+    /// from `fee_schedule` bucket or flat `service_fee_percentage`
+    pub (crate) fn internal_service_fee(&self, total_turns: u8) -> u32 {
+        self.fee_schedule
+            .iter()
+            .find(|bucket| total_turns <= bucket.max_turns)
+            .map(|bucket| bucket.service_fee_percentage)
+            .unwrap_or(self.service_fee_percentage)
+    }
 
-            computed_referrer_fee
+    /// referrer of `account_id` who earns referrer fees in `token_id` (see `Config.min_referrer_games`)
+    pub (crate) fn internal_eligible_referrer(&self, token_id: &TokenContractId, account_id: &AccountId) -> Option<AccountId> {
+        if self.referrer_ineligible_tokens.contains(token_id) {
+            log!("{} doesn't pay referrer fees", token_id);
+            return None;
+        }
+        let referrer_id = self.internal_get_stats(account_id).referrer_id?;
+        let referrer_games = self.internal_get_stats(&referrer_id).games_num;
+        if referrer_games < self.min_referrer_games {
+            log!("Referrer @{} played {} of {} games to earn fees", referrer_id, referrer_games, self.min_referrer_games);
+            return None;
+        }
+        Some(referrer_id)
+    }
+
+    /// Parts of `service_fee` paid out of it, the rest stays with the protocol.
+    /// Shared by `internal_distribute_reward` and `preview_payout`
+    pub (crate) fn internal_split_fee(&self, service_fee: Balance, pays_referrer: bool, has_first_threat: bool) -> FeeSplit {
+        let rakeback_each = service_fee / PLAYERS_NUM as u128 / BASIS_P as u128 * self.rakeback_ratio as u128;
+        let first_threat_bonus = if has_first_threat {
+            service_fee / BASIS_P as u128 * self.first_threat_bonus as u128
         } else {
             0
         };
+        let referrer_fee = if pays_referrer { referrer_fee(service_fee, self.referrer_ratio) } else { 0 };
+        FeeSplit {
+            rakeback_each,
+            first_threat_bonus,
+            referrer_fee,
+            protocol_fee: service_fee - rakeback_each * PLAYERS_NUM as u128 - first_threat_bonus - referrer_fee,
+        }
+    }
 
-        referrer_fee
+    pub (crate) fn internal_pay_referrer_fee(
+        &mut self,
+        token_id: &TokenContractId,
+        referrer_id: &AccountId,
+        referrer_fee: Balance,
+        transfers: &mut Vec<Transfer>
+    ) {
+        if referrer_fee == 0 {
+            return;
+        }
+        log!("Affiliate reward for @{} is {}", referrer_id, referrer_fee);
+        self.internal_update_stats(
+            Some(token_id), 
+            referrer_id, 
+            UpdateStatsAction::AddAffiliateReward, 
+            None, 
+            Some(referrer_fee)
+        );
+        transfers.push(Transfer {
+            receiver_id: referrer_id.clone(),
+            token_id: token_id.clone(),
+            amount: referrer_fee.into(),
+        });
     }
 
     pub (crate) fn internal_tie_refund(
         &mut self, 
         game_id: &GameId, 
         token_id: &TokenContractId, 
-        refund_amount: Balance
+        refund_amount: Balance,
+        transfers: &mut Vec<Transfer>
     ) {
         let (player1, player2) = self.internal_get_game_players(game_id);
-        self.internal_transfer(&token_id, &player1, refund_amount.into());
-        self.internal_transfer(&token_id, &player2, refund_amount.into());
+        for player_id in [player1, player2] {
+            transfers.push(Transfer {
+                receiver_id: self.internal_get_payout_to(game_id, &player_id),
+                token_id: token_id.clone(),
+                amount: refund_amount.into(),
+            });
+        }
+    }
+
+    pub (crate) fn internal_give_up(&mut self, game_id: &GameId, account_id: &AccountId) {
+        let game: Game = self.internal_get_game(game_id);
+        assert_eq!(game.game_state, GameState::Active, "Current game isn't active");
+
+        let (player1, player2) = game.get_player_accounts();
+
+        let winner = if account_id == &player1 {
+            player2
+        } else if account_id == &player2 {
+            player1
+        } else {
+            panic!("You are not in this game. GameId: {} ", game_id)
+        };
+
+        self.internal_finish_game(game_id, game, GameResult::Win(winner, WinReason::GiveUp), None);
     }
 
     pub (crate) fn internal_stop_expired_game(&mut self, game_id: &GameId, looser: AccountId) {
-        let mut game: Game = self.internal_get_game(&game_id);
+        let game: Game = self.internal_get_game(game_id);
         assert_eq!(game.game_state, GameState::Active, "Current game isn't active");
         
-        self.internal_update_stats(
-            None, 
-            &looser, 
-            UpdateStatsAction::AddPenaltyGame, 
-            None, 
-            None
-        );
-        
         let (player1, player2) = self.internal_get_game_players(game_id);
         
         let winner = if looser == player1{
@@ -218,38 +892,136 @@ impl Contract {
             panic!("Account @{} not in this game. GameId: {} ", looser, game_id)
         };
 
-        let balance = self.internal_distribute_reward(game_id, Some(&winner));
+        let game_result = self.internal_timeout_result(&game, winner);
+        if !matches!(game_result, GameResult::Cancelled) && !game.is_practice() {
+            self.internal_update_stats(
+                None, 
+                &looser, 
+                UpdateStatsAction::AddPenaltyGame, 
+                None, 
+                None
+            );
+        }
+        self.internal_finish_game(game_id, game, game_result, None);
+    }
+
+    /// Result of timed out game. On near-full board (see `tie_breaker_min_turns`) player
+    /// with more open lines wins, otherwise or if they are equal - `winner` by timeout
+    /// First move timeout is cancellation if `first_move_grace_refund` is set
+    pub (crate) fn internal_timeout_result(&self, game: &Game, winner: AccountId) -> GameResult {
+        // nobody moved yet
+        if self.first_move_grace_refund && game.last_turn_timestamp == 0 {
+            log!("First move timed out. Game is cancelled, stakes are refunded");
+            return GameResult::Cancelled;
+        }
+        let is_near_full = self.tie_breaker_min_turns
+            .is_some_and(|min_turns| game.total_turns >= min_turns);
+        if !is_near_full {
+            return GameResult::Win(winner, WinReason::Timeout);
+        }
+        let (x_score, o_score) = game.board.score_position(game.rules.win_rule);
+        let leading_piece = match x_score.cmp(&o_score) {
+            std::cmp::Ordering::Greater => Piece::X,
+            std::cmp::Ordering::Less => Piece::O,
+            std::cmp::Ordering::Equal => return GameResult::Win(winner, WinReason::Timeout),
+        };
+        let leader = game.players
+            .iter()
+            .find(|player| player.piece == leading_piece)
+            .expect("Player with piece not found")
+            .account_id
+            .clone();
+        log!("Tie breaker. Open lines x:{} o:{}", x_score, o_score);
+        GameResult::Win(leader, WinReason::TieBreaker)
+    }
+
+    /// Finish the game: distribute reward (or tie refund) by game result,
+    /// store it into `stored_games`, remove from active games and settle rematch.
+    /// `winning_line` is tiles of the line which finished the game, if any
+    pub (crate) fn internal_finish_game(
+        &mut self,
+        game_id: &GameId,
+        mut game: Game,
+        game_result: GameResult,
+        winning_line: Option<Vec<(u8, u8)>>
+    ) {
         game.change_state(GameState::Finished);
         self.internal_update_game(game_id, &game);
 
-        let game_to_store = GameLimitedView{
-            game_result: views::GameResult::Win(winner),
-            player1,
-            player2,
-            reward_or_tie_refund: GameDeposit { 
-                token_id: game.reward().token_id, 
-                balance 
-            },
-            board: game.board.tiles
+        let series_progress = self.internal_record_series_game(game_id, &game, &game_result);
+        let reward = match (&series_progress, &game_result) {
+            // pot is carried to the next game of the series
+            (SeriesProgress::Continues(_), _) => game.reward(),
+            // nothing to transfer
+            _ if game.is_practice() => game.reward(),
+            (SeriesProgress::Over(series_winner), _) => self.internal_distribute_reward(game_id, series_winner.as_ref()),
+            (SeriesProgress::Cancelled, _) | (SeriesProgress::NotInSeries, GameResult::Cancelled) => self.internal_refund_stakes(game_id, &game),
+            (SeriesProgress::NotInSeries, _) => self.internal_distribute_reward(game_id, game_result.winner()),
         };
-        self.internal_store_game(game_id, game_to_store);
+        let is_normal_finish = matches!(game_result, GameResult::Tie | GameResult::Win(_, WinReason::Line));
+        // series games go one after another, cooldown starts after the last one
+        let series_continues = matches!(series_progress, SeriesProgress::Continues(_));
 
+        self.internal_store_game(game_id, GameLimitedView::new(&game, game_result, reward, winning_line));
         self.internal_stop_game(game_id);
+        // rematch agreed by both players isn't blocked by cooldown of this game
+        self.internal_settle_rematch(game_id, &game, is_normal_finish);
+        if self.rematch_cooldown > 0 && !series_continues {
+            for player in game.players.iter() {
+                self.last_game_finished_at.insert(&player.account_id, &env::block_timestamp());
+            }
+        }
+        if let SeriesProgress::Continues(series_id) = series_progress {
+            self.internal_start_next_series_game(series_id, game_id, &game);
+        }
+        self.payout_targets.remove(game_id);
+    }
+
+    /// full stake refund to each player without service fee. Returns stake of each player
+    pub (crate) fn internal_refund_stakes(&mut self, game_id: &GameId, game: &Game) -> GameDeposit {
+        let reward = game.reward();
+        let stake = reward.balance.0 / PLAYERS_NUM as u128;
+        let transfers = game.players
+            .iter()
+            .map(|player| Transfer {
+                receiver_id: self.internal_get_payout_to(game_id, &player.account_id),
+                token_id: reward.token_id.clone(),
+                amount: stake.into(),
+            })
+            .collect();
+        self.internal_batch_transfer(transfers);
+        GameDeposit { token_id: reward.token_id.clone(), balance: stake.into() }
+    }
+
+    /// account to send `player_id` winnings to. Stats are still credited to the player
+    pub (crate) fn internal_get_payout_to(&self, game_id: &GameId, player_id: &AccountId) -> AccountId {
+        self.payout_targets
+            .get(game_id)
+            .and_then(|targets| targets.into_iter().find(|(account_id, _)| account_id == player_id))
+            .map(|(_, payout_to)| payout_to)
+            .unwrap_or_else(|| player_id.clone())
+    }
+
+    /// agent allowed to move instead of `player_id` (see `set_move_delegate`)
+    pub (crate) fn internal_get_move_delegate(&self, game_id: &GameId, player_id: &AccountId) -> Option<AccountId> {
+        self.move_delegates
+            .get(game_id)
+            .and_then(|delegates| delegates.into_iter().find(|(account_id, _)| account_id == player_id))
+            .map(|(_, agent)| agent)
     }
 
     pub(crate) fn is_account_exists(&self, account_id: &AccountId) -> bool {
-        if let Some(_stats) = self.stats.get(account_id) {
-            true
-        } else {
-            false
-        }
+        self.stats.get(account_id).is_some()
     }
 
     pub (crate) fn internal_check_player_available(&mut self, account_id: &AccountId) {
-        let has_games_started = self.games
+        assert!(!self.internal_has_active_game(account_id), "Player @{} already start another game", &account_id)
+    }
+
+    pub (crate) fn internal_has_active_game(&self, account_id: &AccountId) -> bool {
+        self.games
             .iter()
-            .any(|(_game_id, game)| game.contains_player_account_id(account_id));
-        assert!(!has_games_started, "Player @{} already start another game", &account_id)
+            .any(|(_game_id, game)| game.contains_player_account_id(account_id))
     }
 
     pub (crate) fn internal_add_referrer(&mut self, player_id: &AccountId, referrer_id: &AccountId) {
@@ -268,16 +1040,29 @@ impl Contract {
             .expect("Game not found")
     }
 
+    /// `stop_game` time condition: game duration or current player's time budget is over.
+    /// `make_move` at the same `ts` loses by timeout, so a move and a stop never both succeed
+    pub (crate) fn internal_is_stoppable(&self, game: &Game, ts: u64) -> bool {
+        game.duration_expired(ts, self.max_game_duration, self.timeout_grace)
+            || game.clock_expired(ts, self.timeout_grace)
+    }
+
+    /// Removes finished game with everything kept for it while active. Together with
+    /// `payout_targets`, rematch escrow and series cleanup in `internal_finish_game` only
+    /// the stored result and stats are left, so freed storage stake is unlocked on contract balance
     pub (crate) fn internal_stop_game(&mut self, game_id: &GameId) {
         let game = self.games
             .get(game_id)
             .expect("Game not found");
         assert_eq!(game.game_state, GameState::Finished, "Cannot stop. Game in progress");
         self.games.remove(game_id);
+        self.internal_clear_viewers(game_id);
+        self.chats.remove(game_id);
+        self.move_delegates.remove(game_id);
     }
 
     pub (crate) fn internal_update_game(&mut self, game_id: &GameId, game: &Game) {
-        self.games.insert(game_id, &game);
+        self.games.insert(game_id, game);
     }
 
     pub (crate) fn internal_get_game_players(&self, game_id: &GameId) -> (AccountId, AccountId) {
@@ -285,20 +1070,95 @@ impl Contract {
         game.get_player_accounts()
     }
 
-    pub (crate) fn internal_get_game_reward(&self, game_id: &GameId) -> GameDeposit {
-        let game = self.internal_get_game(game_id);
-        game.reward()
-    }
-
     pub (crate) fn get_stored_games_num(&self) -> u8 {
-        return self.stored_games.len() as _
+        self.stored_games.len() as _
     }
 
+    /// store finished game, the oldest stored games are evicted to keep `max_stored_games`
     pub (crate) fn internal_store_game(&mut self, game_id: &GameId, game: GameLimitedView) {
-        let current_games_stored = self.get_stored_games_num();
-        if current_games_stored + 1 == self.max_stored_games {
-            self.stored_games.remove(&(*game_id - current_games_stored as u64));
+        self.internal_evict_expired_stored_games();
+        while self.get_stored_games_num() >= self.max_stored_games {
+            self.internal_evict_oldest_stored_game();
         }
         self.stored_games.insert(game_id, &game);
+        self.stored_games_order.insert(&self.stored_games_tail, game_id);
+        self.stored_games_tail += 1;
+    }
+
+    /// `stored_games_order` keeps stored game ids by insertion number in
+    /// `stored_games_head..stored_games_tail` range, the oldest is at head
+    pub (crate) fn internal_evict_oldest_stored_game(&mut self) {
+        let game_id = self.stored_games_order
+            .remove(&self.stored_games_head)
+            .expect("Stored games order is broken");
+        self.stored_games.remove(&game_id);
+        self.stored_games_head += 1;
+    }
+
+    /// evict stored games older than `stored_game_max_age`. Games are stored in finish order,
+    /// so it stops at the first one which isn't expired
+    pub (crate) fn internal_evict_expired_stored_games(&mut self) {
+        if self.stored_game_max_age == 0 {
+            return;
+        }
+        let now = env::block_timestamp();
+        while self.stored_games_head < self.stored_games_tail {
+            let finished_at = self.stored_games_order
+                .get(&self.stored_games_head)
+                .and_then(|game_id| self.stored_games.get(&game_id))
+                .expect("Stored games order is broken")
+                .finished_at;
+            if finished_at + self.stored_game_max_age > now {
+                break;
+            }
+            self.internal_evict_oldest_stored_game();
+        }
+    }
+}
+
+/// Splits `pot` into service fee and equal amounts for `receivers_num` receivers
+/// (winner or both players in tie). Fee is rounded down and the winner gets the rest.
+/// Tie remainder which can't be split equally goes to the fee, so `fee + amount * receivers_num == pot`
+pub (crate) fn split_pot(pot: Balance, service_fee_percentage: u32, receivers_num: u128) -> (Balance, Balance) {
+    let fee = pot / BASIS_P as u128 * service_fee_percentage as u128;
+    let remainder = (pot - fee) % receivers_num;
+    let fee = fee + remainder;
+    (fee, (pot - fee) / receivers_num)
+}
+
+/// Win payout in stake token, shared by `internal_distribute_reward` and `preview_payout`
+pub (crate) struct WinSplit {
+    /// whole service fee (see `FeeSplit`)
+    pub fee: Balance,
+    pub winner_reward: Balance,
+    /// loser's part (see `Config.consolation_ratio`)
+    pub consolation: Balance,
+}
+
+/// Splits `pot` of a game with a winner. Consolation is taken from the winner's reward
+pub (crate) fn split_win_pot(pot: Balance, service_fee_percentage: u32, consolation_ratio: u32) -> WinSplit {
+    let (fee, reward) = split_pot(pot, service_fee_percentage, 1);
+    let consolation = pot / BASIS_P as u128 * consolation_ratio as u128;
+    WinSplit {
+        fee,
+        winner_reward: reward - consolation,
+        consolation,
     }
-}
\ No newline at end of file
+}
+
+/// Service fee parts, `rakeback_each * PLAYERS_NUM + first_threat_bonus + referrer_fee + protocol_fee`
+/// is the whole fee (see `Contract.internal_split_fee`)
+pub (crate) struct FeeSplit {
+    /// accrued to each player (see `rakeback.rs`)
+    pub rakeback_each: Balance,
+    /// paid to `Game.first_threat_by`
+    pub first_threat_bonus: Balance,
+    /// paid to the winner's referrer
+    pub referrer_fee: Balance,
+    pub protocol_fee: Balance,
+}
+
+/// referrer part of `service_fee`
+pub (crate) fn referrer_fee(service_fee: Balance, referrer_ratio: u32) -> Balance {
+    service_fee / BASIS_P as u128 * referrer_ratio as u128
+}
@@ -0,0 +1,177 @@
+//! Previous state layouts. Used only to migrate stored data into current structs
+use crate::*;
+
+/// `Game` layout of the first release
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct GameV1 {
+    pub game_state: GameState,
+    pub players: Vec<Player>,
+    pub current_piece: Piece,
+    pub current_player_index: u8,
+    pub reward: GameDeposit,
+    pub board: Board,
+    pub total_turns: u8,
+    pub initiated_at: u64,
+    pub last_turn_timestamp: u64,
+    pub current_duration: Duration,
+}
+
+impl GameV1 {
+    /// both players get full `per_player_budget`, seed of legacy game is unknown
+    pub fn into_game(self, per_player_budget: Duration) -> Game {
+        Game {
+            game_state: self.game_state,
+            players: self.players,
+            current_piece: self.current_piece,
+            current_player_index: self.current_player_index,
+            reward: self.reward,
+            board: self.board,
+            total_turns: self.total_turns,
+            initiated_at: self.initiated_at,
+            last_turn_timestamp: self.last_turn_timestamp,
+            current_duration: self.current_duration,
+            rules: GameRules::default(),
+            time_remaining: [per_player_budget; PLAYERS_NUM],
+            seed: vec![],
+            lines_completed: [0; PLAYERS_NUM],
+            first_threat_by: None,
+            extension_used: [false; PLAYERS_NUM],
+            extended_by: 0,
+            version: GAME_VERSION,
+        }
+    }
+}
+
+/// Borsh value of unknown layout. Takes the rest of the buffer as is
+/// to decode it later with the matching legacy struct
+pub struct RawBytes(pub Vec<u8>);
+
+impl BorshSerialize for RawBytes {
+    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_all(&self.0)
+    }
+}
+
+impl BorshDeserialize for RawBytes {
+    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+        let bytes = buf.to_vec();
+        *buf = &[];
+        Ok(RawBytes(bytes))
+    }
+}
+
+/// `GameConfig` layout before `rules`, `rematch_escrow` and `payout_to` were added
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct GameConfigV1 {
+    pub token_id: TokenContractId,
+    pub deposit: Balance,
+    pub opponent_id: Option<AccountId>,
+    pub referrer_id: Option<AccountId>,
+    pub created_at: u64,
+}
+
+impl From<GameConfigV1> for GameConfig {
+    fn from(config: GameConfigV1) -> Self {
+        GameConfig {
+            token_id: config.token_id,
+            deposit: config.deposit,
+            opponent_id: config.opponent_id,
+            referrer_id: config.referrer_id,
+            created_at: config.created_at,
+            rules: GameRules::default(),
+            rematch_escrow: 0,
+            payout_to: None,
+        }
+    }
+}
+
+/// `GameResult` layout before `WinReason` and `Cancelled` were added
+#[derive(BorshSerialize, BorshDeserialize)]
+pub enum GameResultV1 {
+    Win(AccountId),
+    Tie,
+}
+
+/// `GameLimitedView` layout before `winner`, `loser`, `canonical_hash` and `seed` were added
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct GameLimitedViewV1 {
+    pub game_result: GameResultV1,
+    pub player1: AccountId,
+    pub player2: AccountId,
+    pub reward_or_tie_refund: GameDeposit,
+    pub board: [[Option<Piece>; BOARD_SIZE]; BOARD_SIZE],
+}
+
+impl From<GameLimitedViewV1> for GameLimitedView {
+    fn from(view: GameLimitedViewV1) -> Self {
+        let (game_result, winner, loser) = match view.game_result {
+            GameResultV1::Win(winner) => {
+                let loser = if winner == view.player1 { view.player2.clone() } else { view.player1.clone() };
+                // legacy result doesn't keep the reason, `Line` is assumed
+                (GameResult::Win(winner.clone(), WinReason::Line), Some(winner), Some(loser))
+            },
+            GameResultV1::Tie => (GameResult::Tie, None, None),
+        };
+        let board = Board { tiles: view.board, current_piece: Piece::X, winner: None };
+        GameLimitedView {
+            game_result,
+            player1: view.player1,
+            player2: view.player2,
+            winner,
+            loser,
+            reward_or_tie_refund: view.reward_or_tie_refund,
+            board: board.to_rows(),
+            size: BOARD_SIZE as u8,
+            canonical_hash: board.canonical_hash(),
+            seed: vec![],
+            finished_at: 0,
+            is_practice: false,
+            winning_line: None,
+        }
+    }
+}
+
+/// `Stats` layout before `total_wagered` was added
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct StatsV1 {
+    pub referrer_id: Option<AccountId>,
+    pub affiliates: UnorderedSet<AffiliateId>,
+    pub games_num: u64,
+    pub victories_num: u64,
+    pub penalties_num: u64,
+    pub total_reward: UnorderedMap<TokenContractId, Balance>,
+    pub total_affiliate_reward: UnorderedMap<TokenContractId, Balance>,
+}
+
+impl StatsV1 {
+    pub fn into_stats(self, account_id: &AccountId) -> Stats {
+        Stats {
+            referrer_id: self.referrer_id,
+            affiliates: self.affiliates,
+            games_num: self.games_num,
+            victories_num: self.victories_num,
+            penalties_num: self.penalties_num,
+            total_reward: self.total_reward,
+            total_affiliate_reward: self.total_affiliate_reward,
+            total_wagered: UnorderedMap::new(StorageKey::TotalWagered { account_id: account_id.clone() }),
+        }
+    }
+}
+
+/// `Contract` layout of the first release. Games and stats are kept undecoded
+/// because they are converted separately (see `migration.rs`)
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct OldContract {
+    pub whitelisted_tokens: UnorderedMap<TokenContractId, MinDeposit>,
+    pub games: UnorderedMap<GameId, RawBytes>,
+    pub available_players: UnorderedMap<AccountId, GameConfigV1>,
+    pub stats: UnorderedMap<AccountId, RawBytes>,
+    pub next_game_id: GameId,
+    pub service_fee_percentage: u32,
+    pub max_game_duration: Duration,
+    pub referrer_ratio: u32,
+    pub last_update_timestamp: u64,
+    pub max_turn_duration: u64,
+    pub max_stored_games: u8,
+    pub stored_games: UnorderedMap<GameId, GameLimitedViewV1>,
+}
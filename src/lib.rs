@@ -1,6 +1,6 @@
 use near_sdk::{
     AccountId, Balance, BorshStorageKey, Gas, Duration, PanicOnDefault,
-    Promise, PromiseOrValue, PromiseResult, assert_one_yocto
+    Promise, PromiseOrValue, PromiseResult, assert_one_yocto, assert_self
 };
 use near_sdk::{
     env, ext_contract, log, near_bindgen, ONE_NEAR, ONE_YOCTO, require
@@ -8,31 +8,56 @@ use near_sdk::{
 use near_sdk::json_types::U128;
 use near_sdk::borsh::{self, BorshSerialize, BorshDeserialize};
 use near_sdk::serde::{Serialize, Deserialize};
-use near_sdk::collections::{UnorderedMap, UnorderedSet};
+use near_sdk::collections::{LookupMap, UnorderedMap, UnorderedSet};
 use stats::UserPenalties;
 use views::GameLimitedView;
 
+mod accepted_tokens;
 mod board;
+mod chat;
 mod callbacks;
 mod config;
+mod disputes;
+mod errors;
+mod events;
 mod game;
 mod game_config;
 mod internal;
+mod legacy;
+mod migration;
+mod notifications;
+mod payout;
 mod player;
+mod proposals;
+mod rakeback;
+mod refunds;
+mod rematch;
+mod series;
 mod stats;
 mod token_receiver;
 mod views;
+mod viewers;
 mod utils;
 
 use crate::board::*;
+use crate::chat::*;
+use crate::errors::*;
+use crate::series::*;
 use crate::config::*;
+use crate::events::*;
 use crate::game::*;
 use crate::game_config::*;
+use crate::legacy::*;
+use crate::payout::*;
 use crate::player::*;
+use crate::proposals::*;
+use crate::disputes::*;
+use crate::refunds::*;
+use crate::rematch::*;
 use crate::stats::*;
 use crate::token_receiver::*;
 use crate::utils::*;
-use crate::views::GameResult;
+use crate::views::{GameResult, WinReason};
 
 #[derive(BorshSerialize, BorshStorageKey)]
 pub enum StorageKey {
@@ -44,7 +69,35 @@ pub enum StorageKey {
     Stats,
     Affiliates {account_id : AccountId},
     TotalRewards {account_id : AccountId},
-    TotalAffiliateRewards {account_id : AccountId}
+    TotalAffiliateRewards {account_id : AccountId},
+    Viewers,
+    GameViewers {game_id : GameId},
+    PayoutRates,
+    PayoutReserves,
+    PendingRefunds,
+    WaitingCounts,
+    Proposals,
+    TotalWagered {account_id : AccountId},
+    ClaimableRakeback,
+    StoredGamesOrder,
+    TokenDecimals,
+    RematchEscrows,
+    PayoutTargets,
+    PayoutDelayThresholds,
+    DelayedPayouts,
+    MaxDeposits,
+    SeasonArchive {season: u32},
+    BannedUntil,
+    TotalVolume,
+    TotalFees,
+    GameChats,
+    ReferrerIneligibleTokens,
+    Series,
+    GameSeries,
+    LastGameFinishedAt,
+    MoveDelegates,
+    NotificationPrefs,
+    AcceptedTokens,
 }
 
 pub (crate) type MinDeposit = Balance;
@@ -55,7 +108,8 @@ pub struct Contract {
     /// Allowed game reward tokens as `TokenContractId` : `MinDeposit`
     whitelisted_tokens: UnorderedMap<TokenContractId, MinDeposit>,
     games: UnorderedMap<GameId, Game>,
-    available_players: UnorderedMap<AccountId, GameConfig>,
+    /// open invites. Player can have up to `MAX_INVITES_PER_PLAYER` of them
+    available_players: UnorderedMap<(AccountId, InviteId), GameConfig>,
     /* * */
     stats: UnorderedMap<AccountId, Stats>,
     /// `GameId` which will be set for next created `Game`
@@ -68,266 +122,402 @@ pub struct Contract {
     referrer_ratio: u32,
     /// system updates
     pub last_update_timestamp: u64,
-    /// max expected turn duration in nanoseconds (max_game_duration / max possible turns num)
-    max_turn_duration: u64,
+    /// time budget in nanoseconds of each player for the whole game (max_game_duration / PLAYERS_NUM)
+    per_player_budget: Duration,
     /// storage for printing results
     pub max_stored_games: u8,
-    pub stored_games: UnorderedMap<GameId, GameLimitedView>
+    pub stored_games: UnorderedMap<GameId, GameLimitedView>,
+    /// service fee buckets by game `total_turns` (see `config.rs`). Empty means flat `service_fee_percentage`
+    fee_schedule: Vec<FeeBucket>,
+    /// accounts watching active games
+    viewers: LookupMap<GameId, UnorderedSet<AccountId>>,
+    /// stake token -> token and rate to pay winner reward with (see `payout.rs`)
+    payout_rates: UnorderedMap<TokenContractId, PayoutRate>,
+    /// payout token balances funded by owner to pay converted rewards
+    payout_reserves: UnorderedMap<TokenContractId, Balance>,
+    /// failed transfers which could be claimed later (see `refunds.rs`)
+    pending_refunds: LookupMap<AccountId, Vec<(TokenContractId, Balance)>>,
+    /// max number of available players with the same token
+    max_waiting_per_token: u32,
+    /// number of available players by token
+    waiting_counts: UnorderedMap<TokenContractId, u32>,
+    /// time in nanoseconds for opponent to accept proposed game
+    ready_check_duration: Duration,
+    /// not started games waiting for opponent's acceptance
+    proposals: UnorderedMap<GameId, GameProposal>,
+    /// min time in nanoseconds between turns
+    min_turn_interval: Duration,
+    /// part of service fee returned to players in BASIS_P (see `config.rs`)
+    rakeback_ratio: u32,
+    /// accrued rakeback by account and token
+    claimable_rakeback: UnorderedMap<(AccountId, TokenContractId), Balance>,
+    /// stored game ids by insertion number, used to evict the oldest stored game
+    stored_games_order: LookupMap<u64, GameId>,
+    /// insertion number of the oldest stored game
+    stored_games_head: u64,
+    /// insertion number for next stored game
+    stored_games_tail: u64,
+    /// bumped on every state-mutating call, clients compare it to detect missed updates
+    update_seq: u64,
+    /// token decimals set by owner to humanize deposits in views. NEAR is always `NEAR_DECIMALS`
+    token_decimals: LookupMap<TokenContractId, u8>,
+    /// verbose logs, disabled by default to save gas (events are always emitted)
+    debug_logs: bool,
+    /// prepaid rematch escrows by active game (see `rematch.rs`)
+    rematch_escrows: LookupMap<GameId, Vec<(AccountId, Balance)>>,
+    /// (player, payout account) by active game for players with `payout_to`
+    payout_targets: LookupMap<GameId, Vec<(AccountId, AccountId)>>,
+    /// dispute window in nanoseconds before large payouts, 0 to disable (see `disputes.rs`)
+    payout_delay: Duration,
+    /// min pot by token to delay its payout
+    payout_delay_thresholds: LookupMap<TokenContractId, Balance>,
+    /// deferred transfers of finished games
+    delayed_payouts: LookupMap<GameId, DelayedPayout>,
+    /// `InviteId` which will be set for next open invite
+    next_invite_id: InviteId,
+    /// optional max stake by token, NEAR included
+    max_deposits: LookupMap<TokenContractId, Balance>,
+    /// min `total_turns` of timed out game to be resolved by open lines. `None` - disabled
+    tie_breaker_min_turns: Option<u8>,
+    /// first move timeout cancels the game with full refunds instead of penalty
+    first_move_grace_refund: bool,
+    /// game initiator always moves first (see `Config.deterministic_first_move`)
+    deterministic_first_move: bool,
+    /// stats season, previous ones are archived (see `archive_and_reset_stats`)
+    season: u32,
+    /// matchmaking ban in nanoseconds after each penalty, 0 to disable
+    penalty_ban: Duration,
+    /// end of matchmaking ban by account (see `is_banned`)
+    banned_until: LookupMap<AccountId, u64>,
+    /// part of service fee in BASIS_P paid to `Game.first_threat_by`, 0 to disable
+    first_threat_bonus: u32,
+    /// sum of stakes of all started games by token
+    total_volume: UnorderedMap<TokenContractId, Balance>,
+    /// sum of service fees of all finished games and turn extension prices by token
+    total_fees: UnorderedMap<TokenContractId, Balance>,
+    /// reject games with opponent as player's referrer in stats (see `Config.block_referrer_as_opponent`)
+    block_referrer_as_opponent: bool,
+    /// time in nanoseconds added by `extend_turn`, 0 if disabled
+    turn_extension: Duration,
+    /// price of turn extension in yoctoNEAR
+    turn_extension_price: Balance,
+    /// chat messages of active games (see `chat.rs`)
+    chats: LookupMap<GameId, Vec<ChatMessage>>,
+    /// part of pot in BASIS_P returned to the loser, 0 for winner-take-all
+    consolation_ratio: u32,
+    /// tokens which don't pay referrer fees, the referrer part stays with the protocol
+    referrer_ineligible_tokens: UnorderedSet<TokenContractId>,
+    /// series in progress (see `series.rs`)
+    series: UnorderedMap<SeriesId, SeriesState>,
+    /// series of active game
+    game_series: LookupMap<GameId, SeriesId>,
+    /// `SeriesId` which will be set for next started series
+    next_series_id: SeriesId,
+    /// referrer earns fees only after playing this number of games, 0 to disable
+    min_referrer_games: u64,
+    /// time in nanoseconds after finishing a game before the next one, 0 to disable
+    rematch_cooldown: Duration,
+    /// last finish time of account's game, kept only while cooldown is enabled (see `get_remaining_cooldown`)
+    last_game_finished_at: LookupMap<AccountId, u64>,
+    /// (player, agent) by active game. Agent can move instead of the player (see `set_move_delegate`)
+    move_delegates: LookupMap<GameId, Vec<(AccountId, AccountId)>>,
+    /// notification bitflags by account, `NOTIFY_ALL` isn't stored (see `notifications.rs`)
+    notification_prefs: LookupMap<AccountId, u8>,
+    /// stored games older than this (in nanoseconds) are evicted, 0 to disable
+    stored_game_max_age: Duration,
+    /// block timestamp of `new` or the last `migrate` (see `get_build_info`)
+    upgraded_at: u64,
+    /// time in nanoseconds added to turn and game deadlines, 0 to disable
+    timeout_grace: Duration,
+    /// opt-in reward tokens by account, not set - any token (see `accepted_tokens.rs`)
+    accepted_tokens: LookupMap<AccountId, Vec<TokenContractId>>,
+    /// max severity tier of repeat penalties, each tier doubles `penalty_ban`. 0 to disable
+    max_penalty_tier: u8,
+    /// next account index to archive in `archive_and_reset_stats`, 0 if no reset is in progress
+    season_reset_from: u64,
+    /// number of waiting practice invites, up to `MAX_PRACTICE_WAITING`
+    practice_waiting: u32,
+    /// time in nanoseconds after which invite is removed from `available_players` and refunded
+    time_to_be_available: Duration,
 }
 
 #[near_bindgen]
 impl Contract {
     #[init]
     pub fn new(config: Option<Config>) -> Self {
-        let (
-            service_fee_percentage, 
-            max_game_duration,
-            referrer_ratio,
-            max_stored_games
-        ) = if let Some(config) = config {
-            config.assert_valid();
-            (
-                config.service_fee_percentage,
-                sec_to_nano(config.max_game_duration_sec),
-                config.referrer_ratio,
-                config.max_stored_games
-            )
-        } else {
-            // default config
-            (
-                // 10% total fees - 9.5% to referrer, 0.5% to cheddar distribution
-                MAX_FEES,
-                // 1 hour for max_game_duration will be set
-                // also 400 sec will be max turn duration (max_game_duration / MAX_TURNS_NUM)
-                sec_to_nano(60 * 60),
-                // 95% refferer fees from 10% total fees
-                9500,
-                // 50 last games will be stored
-                50
-            )
-        };
+        // default config if not set (see `config.rs`)
+        let config = config.unwrap_or_default();
+        config.assert_valid();
+        let max_game_duration = sec_to_nano(config.max_game_duration_sec);
         Self {
             whitelisted_tokens: UnorderedMap::new(StorageKey::WhitelistedTokens),
             games: UnorderedMap::new(StorageKey::Games),
             available_players: UnorderedMap::new(StorageKey::Players),
             stats: UnorderedMap::new(StorageKey::Stats),
             next_game_id: 0,
-            service_fee_percentage,
+            service_fee_percentage: config.service_fee_percentage,
             max_game_duration,
-            referrer_ratio,
+            referrer_ratio: config.referrer_ratio,
             last_update_timestamp: 0,
-            max_turn_duration: max_game_duration / MAX_NUM_TURNS,
-            max_stored_games,
-            stored_games: UnorderedMap::new(StorageKey::StoredGames)
+            per_player_budget: max_game_duration / PLAYERS_NUM as u64,
+            max_stored_games: config.max_stored_games,
+            stored_games: UnorderedMap::new(StorageKey::StoredGames),
+            fee_schedule: config.fee_schedule.unwrap_or_default(),
+            viewers: LookupMap::new(StorageKey::Viewers),
+            payout_rates: UnorderedMap::new(StorageKey::PayoutRates),
+            payout_reserves: UnorderedMap::new(StorageKey::PayoutReserves),
+            pending_refunds: LookupMap::new(StorageKey::PendingRefunds),
+            max_waiting_per_token: config.max_waiting_per_token.unwrap_or(DEFAULT_MAX_WAITING_PER_TOKEN),
+            waiting_counts: UnorderedMap::new(StorageKey::WaitingCounts),
+            ready_check_duration: sec_to_nano(config.ready_check_sec.unwrap_or(DEFAULT_READY_CHECK_SEC)),
+            proposals: UnorderedMap::new(StorageKey::Proposals),
+            min_turn_interval: sec_to_nano(config.min_turn_interval_sec.unwrap_or(0)),
+            rakeback_ratio: config.rakeback_ratio.unwrap_or(0),
+            claimable_rakeback: UnorderedMap::new(StorageKey::ClaimableRakeback),
+            stored_games_order: LookupMap::new(StorageKey::StoredGamesOrder),
+            stored_games_head: 0,
+            stored_games_tail: 0,
+            update_seq: 0,
+            token_decimals: LookupMap::new(StorageKey::TokenDecimals),
+            debug_logs: config.debug_logs.unwrap_or(false),
+            rematch_escrows: LookupMap::new(StorageKey::RematchEscrows),
+            payout_targets: LookupMap::new(StorageKey::PayoutTargets),
+            payout_delay: sec_to_nano(config.payout_delay_sec.unwrap_or(0)),
+            payout_delay_thresholds: LookupMap::new(StorageKey::PayoutDelayThresholds),
+            delayed_payouts: LookupMap::new(StorageKey::DelayedPayouts),
+            next_invite_id: 0,
+            max_deposits: LookupMap::new(StorageKey::MaxDeposits),
+            tie_breaker_min_turns: config.tie_breaker_min_turns,
+            first_move_grace_refund: config.first_move_grace_refund.unwrap_or(false),
+            deterministic_first_move: config.deterministic_first_move.unwrap_or(false),
+            season: 0,
+            penalty_ban: sec_to_nano(config.penalty_ban_sec.unwrap_or(0)),
+            banned_until: LookupMap::new(StorageKey::BannedUntil),
+            first_threat_bonus: config.first_threat_bonus.unwrap_or(0),
+            total_volume: UnorderedMap::new(StorageKey::TotalVolume),
+            total_fees: UnorderedMap::new(StorageKey::TotalFees),
+            block_referrer_as_opponent: config.block_referrer_as_opponent.unwrap_or(false),
+            turn_extension: sec_to_nano(config.turn_extension_sec.unwrap_or(0)),
+            turn_extension_price: config.turn_extension_price.map(|price| price.0).unwrap_or(0),
+            chats: LookupMap::new(StorageKey::GameChats),
+            consolation_ratio: config.consolation_ratio.unwrap_or(0),
+            referrer_ineligible_tokens: UnorderedSet::new(StorageKey::ReferrerIneligibleTokens),
+            series: UnorderedMap::new(StorageKey::Series),
+            game_series: LookupMap::new(StorageKey::GameSeries),
+            next_series_id: 0,
+            min_referrer_games: config.min_referrer_games.unwrap_or(0).into(),
+            rematch_cooldown: sec_to_nano(config.rematch_cooldown_sec.unwrap_or(0)),
+            last_game_finished_at: LookupMap::new(StorageKey::LastGameFinishedAt),
+            move_delegates: LookupMap::new(StorageKey::MoveDelegates),
+            notification_prefs: LookupMap::new(StorageKey::NotificationPrefs),
+            stored_game_max_age: sec_to_nano(config.stored_game_max_age_sec.unwrap_or(0)),
+            upgraded_at: env::block_timestamp(),
+            timeout_grace: sec_to_nano(config.timeout_grace_sec.unwrap_or(0)),
+            accepted_tokens: LookupMap::new(StorageKey::AcceptedTokens),
+            max_penalty_tier: config.max_penalty_tier.unwrap_or(0),
+            season_reset_from: 0,
+            practice_waiting: 0,
+            time_to_be_available: config.time_to_be_available_sec.map(sec_to_nano).unwrap_or(DEFAULT_TIME_TO_BE_AVAILABLE),
         }
     }
 
     /// Make player available only with NEAR deposits
+    /// Returns id of created invite. Player can have several invites at once
     #[payable]
     pub fn make_available(
         &mut self,
         game_config: Option<GameConfigNear>,
-    ) {
+    ) -> InviteId {
+        self.internal_bump_update_seq();
         let cur_timestamp = env::block_timestamp();
         // checkpoint
         self.internal_ping_expired_players(cur_timestamp);
 
         let account_id: &AccountId = &env::predecessor_account_id();
-        assert!(self.available_players.get(account_id).is_none(), "Already in the waiting list the list");
+        self.internal_assert_not_banned(account_id);
+        self.internal_assert_no_cooldown(account_id);
+        self.internal_assert_invites_limit(account_id);
+        self.internal_assert_accepts_token(account_id, &near_token_id());
+
+        let GameConfigNear { opponent_id, referrer_id, rules, auto_rematch, payout_to, practice } = game_config.unwrap_or_default();
+        let practice = practice.unwrap_or(false);
+        if practice {
+            assert_eq!(env::attached_deposit(), 0, "Practice game doesn't take deposit");
+            assert!(!auto_rematch.unwrap_or(false), "Auto rematch isn't supported in practice game");
+        }
+        let (deposit, rematch_escrow) = split_rematch_escrow(env::attached_deposit(), auto_rematch.unwrap_or(false));
+        assert!(practice || deposit >= MIN_DEPOSIT_NEAR, "Deposit is too small. Attached: {}, Required: {}", deposit, MIN_DEPOSIT_NEAR);
+        self.internal_assert_max_deposit(&near_token_id(), deposit);
 
-        let deposit: Balance = env::attached_deposit();
-        assert!(deposit >= MIN_DEPOSIT_NEAR, "Deposit is too small. Attached: {}, Required: {}", deposit, MIN_DEPOSIT_NEAR);
+        if let Some(opponent_id) = opponent_id.as_ref() {
+            assert_not_self_play(account_id, opponent_id);
+        }
+        assert_valid_referrer(account_id, referrer_id.as_ref(), opponent_id.as_ref());
 
-        let (opponent_id, referrer_id) = if let Some(game_config) = game_config {
-            (game_config.opponent_id, game_config.referrer_id.clone())
+        if practice {
+            self.internal_assert_practice_capacity();
         } else {
-            (None, None)
-        };
-
-        self.available_players.insert(account_id,
+            self.internal_assert_waiting_capacity(&near_token_id());
+        }
+        let invite_id = self.internal_add_invite(account_id,
             &GameConfig {
-                token_id: AccountId::new_unchecked("near".into()),
+                token_id: near_token_id(),
                 deposit,
                 opponent_id,
                 referrer_id: referrer_id.clone(),
-                created_at: cur_timestamp
+                created_at: cur_timestamp,
+                rules: rules.unwrap_or_default(),
+                rematch_escrow,
+                payout_to,
             }
         );
         
-        self.internal_check_player_available(&account_id);
+        self.internal_check_player_available(account_id);
 
         if let Some(referrer_id) = referrer_id {
-            self.internal_add_referrer( &account_id, &referrer_id);
+            self.internal_add_referrer( account_id, &referrer_id);
         }
+        invite_id
     }
 
+    /// Cancel invite `invite_id` or all caller's invites if not set and refund deposits.
+    /// Attached 1 yocto is returned with the first NEAR refund. Invites storage is paid by
+    /// the contract, so nothing else is owed
     #[payable]
-    pub fn make_unavailable(&mut self) {
+    pub fn make_unavailable(&mut self, invite_id: Option<InviteId>) {
         assert_one_yocto();
+        self.internal_bump_update_seq();
         let account_id = env::predecessor_account_id();
-        match self.available_players.get(&account_id) {
-            Some(config) => {
-                // refund players deposit
-                let token_id = config.token_id.clone();
-                self.available_players.remove(&account_id);
-
-                self.internal_transfer(&token_id, &account_id, config.deposit.into())
-                    .then(Self::ext(env::current_account_id())
-                    .with_static_gas(CALLBACK_GAS)
-                    .transfer_deposit_callback(account_id, &config)
-                );
-            },
-            None => panic!("You are not available now")
+        let invite_ids = match invite_id {
+            Some(invite_id) => vec![invite_id],
+            None => {
+                let invites = self.internal_get_player_invites(&account_id);
+                assert!(!invites.is_empty(), "You are not available now");
+                invites.into_iter().map(|(invite_id, _)| invite_id).collect()
+            }
+        };
+        // refund players deposit
+        let mut security_deposit = env::attached_deposit();
+        for invite_id in invite_ids {
+            security_deposit = self.internal_refund_available_deposit(&account_id, invite_id, security_deposit);
         }
     }
 
-    pub fn start_game(&mut self, player_2_id: AccountId) -> GameId {
-        if let Some(player_2_config) = self.available_players.get(&player_2_id) {
-            // Check is game initiator (predecessor) player available to play as well
-            let player_1_id = env::predecessor_account_id();
-            assert_ne!(player_1_id.clone(), player_2_id.clone(), "Find a friend to play");
-
-            // Get predecessor's available deposit
-            let player_1_config = self.internal_get_available_player(&player_1_id);
-            let player_1_config_token = player_1_config.token_id;
-            let player_1_deposit = player_1_config.deposit;
-
-            self.internal_check_player_available(&player_1_id);
-            
-            if let Some(player_id) = player_2_config.opponent_id {
-                assert_eq!(player_id, player_1_id, "Wrong account");
-            }
-
-            // Deposits from two players must be equal
-            assert_eq!(
-                player_1_deposit, 
-                player_2_config.deposit, 
-                "Mismatched deposits for players! You: {}, Opponent {}",
-                player_1_deposit,
-                player_2_config.deposit
-            );
-
-            let game_id = self.next_game_id;
-            let token_id = player_2_config.token_id;
-
-            assert_eq!(token_id, player_1_config_token, "Mismatch tokens! Choosen tokens for opponent and you must be the same");
-            // deposit * 2
-            let balance = match player_2_config.deposit.checked_mul(2) {
-                Some(value) => value,
-                None => panic!("multiplication overflow, too big deposit amount"),
-            };
-
-            let reward = GameDeposit {
-                token_id: token_id.clone(),
-                balance: balance.into()
-            };
-            log!("game reward:{} in token {:?} ", balance, token_id.clone());
-            
-            let seed = near_sdk::env::random_seed();
-            let mut game = match seed[0] % 2 {
-                0 => {
-                    Game::create_game(
-                    player_2_id.clone(),
-                    player_1_id.clone(),
-                    reward
-                    )
-                },
-                _ => {
-                    Game::create_game(
-                    player_1_id.clone(),
-                    player_2_id.clone(),
-                    reward
-                    )
-                },
-            };
-
-            game.change_state(GameState::Active);
-            self.games.insert(&game_id, &game);
-
-            self.next_game_id += 1;
-            self.available_players.remove(&player_1_id);
-            self.available_players.remove(&player_2_id);
-
-            if let Some(referrer_id) = player_1_config.referrer_id {
-                self.internal_add_referrer(&player_1_id, &referrer_id);
-            }
-            if let Some(referrer_id) = player_2_config.referrer_id {
-                self.internal_add_referrer(&player_2_id, &referrer_id);
-            }
+    /// Start game immediately. See `propose_game` for start with ready check.
+    /// `invite_id` of `player_2_id` is required only if `player_2_id` has several invites.
+    /// Caller's invite with the same token, deposit and rules is used, other invites of both players are refunded
+    pub fn start_game(&mut self, player_2_id: AccountId, invite_id: Option<InviteId>) -> GameId {
+        self.internal_start_game(player_2_id, invite_id)
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
 
-            self.internal_update_stats(Some(&token_id), &player_1_id, UpdateStatsAction::AddPlayedGame, None, None);
-            self.internal_update_stats(Some(&token_id), &player_2_id, UpdateStatsAction::AddPlayedGame, None, None);
-            game_id
-        } else {
-            panic!("Your opponent is not ready");
-        }
+    /// Same as `start_game`, but returns `{"Err": reason}` instead of panic if the game can't be started.
+    /// Invites are kept then. Started game is returned as `{"Ok": game_id}`
+    #[handle_result]
+    pub fn try_start_game(
+        &mut self,
+        player_2_id: AccountId,
+        invite_id: Option<InviteId>
+    ) -> Result<Result<GameId, StartGameError>, near_sdk::Abort> {
+        Ok(self.internal_start_game(player_2_id, invite_id))
     }
 
     pub fn make_move(&mut self, game_id: &GameId, row: usize, col: usize) -> [[Option<Piece>; BOARD_SIZE]; BOARD_SIZE] {
+        self.internal_bump_update_seq();
         let cur_timestamp = env::block_timestamp();
-        //checkpoint
-        self.internal_ping_expired_games(cur_timestamp);
+        //checkpoint. Duration of this game is checked below, so the mover is the one who loses
+        self.internal_ping_expired_games(cur_timestamp, Some(game_id));
 
         let mut game = self.internal_get_game(game_id);
         let init_game_state = game.game_state;
 
-        assert_eq!(env::predecessor_account_id(), game.current_player_account_id(), "No access");
+        let predecessor_id = env::predecessor_account_id();
+        let current_player_id = game.current_player_account_id();
+        assert!(
+            predecessor_id == current_player_id
+                || self.internal_get_move_delegate(game_id, &current_player_id).as_ref() == Some(&predecessor_id),
+            "No access"
+        );
         assert_eq!(init_game_state, GameState::Active, "Current game isn't active");
+        // first move is exempt
+        if game.last_turn_timestamp > 0 {
+            assert!(
+                cur_timestamp - game.last_turn_timestamp >= self.min_turn_interval,
+                "Too fast move. Min turn interval is {} sec", nano_to_sec(self.min_turn_interval)
+            );
+        }
+
+        // the mover is always the current player (checked above). Time since the previous move
+        // is his own think time, so if it's expired the mover is the staller, not the opponent
+        let mover_id = game.current_player_account_id();
+
+        // chess clock. Think time is taken from player's budget, running out loses
+        if game.clock_expired(cur_timestamp, self.timeout_grace) {
+            log!(
+                "Time budget expired. Remaining:{} Spent:{} ",
+                game.time_remaining[game.current_player_index as usize], game.think_time(cur_timestamp)
+            );
+            // looser - mover
+            self.internal_stop_expired_game(game_id, mover_id);
+            return game.board.tiles;
+        }
+        // checked before the move, so the move can't win after the deadline
+        if game.duration_expired(cur_timestamp, self.max_game_duration, self.timeout_grace) {
+            log!(
+                "Game duration expired. Required:{} Current:{} ",
+                game.max_duration(self.max_game_duration), cur_timestamp - game.initiated_at
+            );
+            // looser - mover, their think time exceeded game duration
+            self.internal_stop_expired_game(game_id, mover_id);
+            return game.board.tiles;
+        }
+        // think time within grace is taken from the budget down to zero
+        let think_time = game.think_time(cur_timestamp);
+        let time_remaining = &mut game.time_remaining[game.current_player_index as usize];
+        *time_remaining = time_remaining.saturating_sub(think_time);
+
+        // in gravity mode `row` is ignored
+        let position = if game.rules.gravity {
+            game.board.check_drop(col).map(|row| (row, col))
+        } else {
+            game.board.check_move(row, col).map(|_| (row, col))
+        };
 
-        match game.board.check_move(row, col) {
-            Ok(_) => {
+        match position {
+            Ok((row, col)) => {
+                let mover_index = game.current_player_index as usize;
                 // fill board tile with current player piece
                 game.board.tiles[row][col] = Some(game.current_piece);
+                game.total_turns += 1;
                 // switch piece to other one
                 game.current_piece = game.current_piece.other();
                 // switch player
                 game.current_player_index = 1 - game.current_player_index;
-                game.board.update_winner(row, col);
+                let winning_line = game.update_winner(row, col, mover_index);
 
                 if let Some(winner) = game.board.winner {
-                    // change game state to Finished
-                    game.change_state(GameState::Finished);
-                    self.internal_update_game(game_id, &game);
+                    // in misère mode the line is completed by the loser
+                    let winner = if game.rules.misere { winner.inverted() } else { winner };
                     // get winner account, if there is Tie - refund to both players
                     // with crop service fee amount from it
-                    let winner_account:Option<&AccountId> = match winner {
-                        board::Winner::X => game.get_player_acc_by_piece(Piece::X),
-                        board::Winner::O => game.get_player_acc_by_piece(Piece::O),
-                        board::Winner::Tie => None,
+                    let game_result = match winner {
+                        board::Winner::X => GameResult::Win(game.get_player_acc_by_piece(Piece::X).cloned().expect("No account with X piece"), WinReason::Line),
+                        board::Winner::O => GameResult::Win(game.get_player_acc_by_piece(Piece::O).cloned().expect("No account with O piece"), WinReason::Line),
+                        board::Winner::Tie => GameResult::Tie,
                     };
-               
-                    let balance = if winner_account.is_some() {
+
+                    if let Some(winner_id) = game_result.winner() {
                         // SOME WINNER
-                        log!("\nGame over! {} won!", winner_account.unwrap());
-                        self.internal_distribute_reward(game_id, winner_account)
+                        log!("\nGame over! {} won!", winner_id);
                     } else {
                         // TIE
                         log!("\nGame over! Tie!");
-                        self.internal_distribute_reward(game_id, None)
-                    };
-
-                    let game_result = match winner_account {
-                        Some(winner) => GameResult::Win(winner.clone()),
-                        None => GameResult::Tie,
                     };
 
-                    let (player1, player2) = game.get_player_accounts();
-
-                    let game_to_store = GameLimitedView{
-                        game_result,
-                        player1,
-                        player2,
-                        reward_or_tie_refund: GameDeposit {
-                            token_id: game.reward().token_id,
-                            balance
-                        },
-                        board: game.board.tiles,
-                    };
+                    let tiles = game.board.tiles;
+                    self.internal_finish_game(game_id, game, game_result, winning_line);
 
-                    self.internal_store_game(game_id, game_to_store);
-                    self.internal_stop_game(game_id);
-                    
-                    return game.board.tiles;
+                    return tiles;
                 };
             },
             Err(e) => match e {
@@ -337,92 +527,146 @@ impl Contract {
                 MoveError::TileFilled { other_piece, row, col } => panic!(
                     "The tile row: {} col: {} already contained another piece: {:?}", row, col, other_piece
                 ),
+                MoveError::ColumnFilled { col } => panic!("The column {} is already filled", col),
             },
         }
         if game.game_state == GameState::Active {
-
-            game.total_turns += 1;
-            // previous turn timestamp
-            let previous_turn_timestamp = game.last_turn_timestamp;
             // this turn timestamp
             game.last_turn_timestamp = cur_timestamp;
             // this game duration 
             game.current_duration = cur_timestamp - game.initiated_at;
-
-            if previous_turn_timestamp == 0 {
-                if cur_timestamp - game.initiated_at > self.max_turn_duration {
-                    log!("Turn duration expired. Required:{} Current:{} ", self.max_turn_duration, cur_timestamp - game.initiated_at);
-                    // looser - current player
-                    self.internal_stop_expired_game(game_id, env::predecessor_account_id());
-                    return game.board.tiles;
-                } else {
-                    self.internal_update_game(game_id, &game);
-                    return game.board.tiles;
-                }
-            }
-
-            // expired turn time scenario - too long movement from current player
-            if game.last_turn_timestamp - previous_turn_timestamp > self.max_turn_duration {
-                log!("Turn duration expired. Required:{} Current:{} ", self.max_turn_duration, game.last_turn_timestamp - previous_turn_timestamp);
-                // looser - current player
-                self.internal_stop_expired_game(game_id, env::predecessor_account_id());
-                return game.board.tiles;
-            };
-
-            if game.current_duration <= self.max_game_duration {
-                self.internal_update_game(game_id, &game);
-                return game.board.tiles;
-            } else {
-                log!("Game duration expired. Required:{} Current:{} ", self.max_game_duration, game.current_duration);
-                // looser - current player
-                self.internal_stop_expired_game(game_id, env::predecessor_account_id());
-                return game.board.tiles;
-            }
+            self.internal_update_game(game_id, &game);
+            game.board.tiles
         } else {
             panic!("Something wrong with game id: {} state", game_id)
         }
 
     }
 
+    /// Concede active game, the opponent wins. Unlike `make_move` and `stop_game` it doesn't
+    /// depend on the turn: giving up only hurts the caller, so nobody waits for their turn to do it
     #[payable]
     pub fn give_up(&mut self, game_id: &GameId) {
         assert_one_yocto();
-        let mut game: Game = self.internal_get_game(&game_id);
+        self.internal_bump_update_seq();
+        self.internal_give_up(game_id, &env::predecessor_account_id());
+    }
+
+    /// Give up in all active games of predecessor, opponents win.
+    /// Processes up to `MAX_FORFEITS_PER_CALL` games per call
+    /// and returns number of games left to forfeit
+    #[payable]
+    pub fn forfeit_all_my_games(&mut self) -> u32 {
+        assert_one_yocto();
+        self.internal_bump_update_seq();
+        let account_id = env::predecessor_account_id();
+        let game_ids: Vec<GameId> = self.games
+            .iter()
+            .filter(|(_, game)| game.game_state == GameState::Active && game.contains_player_account_id(&account_id))
+            .map(|(game_id, _)| game_id)
+            .collect();
+        for game_id in game_ids.iter().take(MAX_FORFEITS_PER_CALL) {
+            self.internal_give_up(game_id, &account_id);
+        }
+        game_ids.len().saturating_sub(MAX_FORFEITS_PER_CALL) as u32
+    }
+
+    /// Hand off caller's seat in active game to `new_account`. Result, stats, rewards,
+    /// payout account and rematch escrow of the seat go to `new_account` from now on.
+    /// `new_account` can't be banned, on cooldown or not accept the game token
+    #[payable]
+    pub fn transfer_seat(&mut self, game_id: GameId, new_account: AccountId) {
+        assert_one_yocto();
+        self.internal_bump_update_seq();
+        let mut game = self.internal_get_game(&game_id);
         assert_eq!(game.game_state, GameState::Active, "Current game isn't active");
-        
         let account_id = env::predecessor_account_id();
+        assert!(game.contains_player_account_id(&account_id), "You are not in this game. GameId: {} ", game_id);
+        assert!(!game.contains_player_account_id(&new_account), "Account @{} is already in this game", new_account);
+        assert!(self.game_series.get(&game_id).is_none(), "Seat in series can't be transferred");
+        // new account joins the game as if starting it
+        self.internal_check_not_restricted(&new_account)
+            .and_then(|()| self.internal_check_accepts_token(&new_account, &game.reward.token_id))
+            .unwrap_or_else(|error| panic!("{}", error));
+
+        for player in game.players.iter_mut().filter(|player| player.account_id == account_id) {
+            player.account_id = new_account.clone();
+        }
+        if game.first_threat_by.as_ref() == Some(&account_id) {
+            game.first_threat_by = Some(new_account.clone());
+        }
+        self.games.insert(&game_id, &game);
 
-        let (player1, player2) = self.internal_get_game_players(game_id);
-        
-        let winner = if account_id == player1{
-            player2.clone()
-        } else if account_id == player2 {
-            player1.clone()
-        } else {
-            panic!("You are not in this game. GameId: {} ", game_id)
-        };
+        if let Some(mut payout_targets) = self.payout_targets.get(&game_id) {
+            for (player_id, _) in payout_targets.iter_mut().filter(|(player_id, _)| *player_id == account_id) {
+                *player_id = new_account.clone();
+            }
+            self.payout_targets.insert(&game_id, &payout_targets);
+        }
+        if let Some(mut escrows) = self.rematch_escrows.get(&game_id) {
+            for (player_id, _) in escrows.iter_mut().filter(|(player_id, _)| *player_id == account_id) {
+                *player_id = new_account.clone();
+            }
+            self.rematch_escrows.insert(&game_id, &escrows);
+        }
+        // agent was authorized by the previous owner of the seat
+        if let Some(mut delegates) = self.move_delegates.get(&game_id) {
+            delegates.retain(|(player_id, _)| *player_id != account_id);
+            self.move_delegates.insert(&game_id, &delegates);
+        }
+        Event::SeatTransferred { game_id, old_account_id: &account_id, new_account_id: &new_account }.emit();
+    }
 
-        let balance = self.internal_distribute_reward(game_id, Some(&winner));
-        game.change_state(GameState::Finished);
-        self.internal_update_game(game_id, &game);
-
-        let game_to_store = GameLimitedView{
-            game_result: GameResult::Win(winner),
-            player1,
-            player2,
-            reward_or_tie_refund: GameDeposit {
-                token_id: game.reward().token_id,
-                balance
-            },
-            board: game.board.tiles,
-        };
+    /// Allow `agent` (e.g. bot) to make moves instead of the caller in active game.
+    /// Replaces previous agent. Result, stats and rewards still go to the caller
+    #[payable]
+    pub fn set_move_delegate(&mut self, game_id: GameId, agent: AccountId) {
+        assert_one_yocto();
+        self.internal_bump_update_seq();
+        let game = self.internal_get_game(&game_id);
+        assert_eq!(game.game_state, GameState::Active, "Current game isn't active");
+        let account_id = env::predecessor_account_id();
+        assert!(game.contains_player_account_id(&account_id), "You are not in this game. GameId: {} ", game_id);
+        assert!(!game.contains_player_account_id(&agent), "Account @{} is already in this game", agent);
+
+        let mut delegates = self.move_delegates.get(&game_id).unwrap_or_default();
+        delegates.retain(|(player_id, _)| *player_id != account_id);
+        delegates.push((account_id, agent));
+        self.move_delegates.insert(&game_id, &delegates);
+    }
 
-        self.internal_store_game(game_id, game_to_store);
-        self.internal_stop_game(game_id);
+    /// Current player buys more time for the turn once per game. Attached deposit must be
+    /// equal to `turn_extension_price`, it goes to protocol fees
+    #[payable]
+    pub fn extend_turn(&mut self, game_id: GameId) {
+        self.internal_bump_update_seq();
+        assert!(self.turn_extension > 0, "Turn extension is disabled");
+        assert_eq!(
+            env::attached_deposit(), self.turn_extension_price,
+            "Turn extension price is {} yoctoNEAR", self.turn_extension_price
+        );
+        let mut game = self.internal_get_game(&game_id);
+        assert_eq!(game.game_state, GameState::Active, "Current game isn't active");
+        assert_eq!(env::predecessor_account_id(), game.current_player_account_id(), "No access");
+        let index = game.current_player_index as usize;
+        assert!(!game.extension_used[index], "Turn extension is already used");
+        // expired turn can be stopped by opponent, so it can't be extended
+        let cur_timestamp = env::block_timestamp();
+        assert!(!self.internal_is_stoppable(&game, cur_timestamp), "Time is over");
+
+        game.extension_used[index] = true;
+        game.time_remaining[index] += self.turn_extension;
+        game.extended_by += self.turn_extension;
+        self.internal_update_game(&game_id, &game);
+        if self.turn_extension_price > 0 {
+            let total_fees = self.total_fees.get(&near_token_id()).unwrap_or(0);
+            self.total_fees.insert(&near_token_id(), &(total_fees + self.turn_extension_price));
+        }
     }
 
     pub fn stop_game(&mut self, game_id: &GameId) {
-        let mut game: Game = self.internal_get_game(&game_id);
+        self.internal_bump_update_seq();
+        let mut game: Game = self.internal_get_game(game_id);
         assert_eq!(game.game_state, GameState::Active, "Current game isn't active");
 
         let account_id = env::predecessor_account_id();
@@ -431,16 +675,13 @@ impl Contract {
         let (player1, player2) = self.internal_get_game_players(game_id);
 
         game.current_duration = env::block_timestamp() - game.initiated_at;
-        log!("game.current_duration : {}", game.current_duration);
-        log!("env::block_timestamp() : {}", env::block_timestamp());
-        log!("game.initiated_at : {}", game.initiated_at);
-        log!("self.max_game_duration : {}", self.max_game_duration);
-        log!("game.last_turn_timestamp : {}", game.last_turn_timestamp);
-        log!("self.max_turn_duration :{} ", self.max_turn_duration);
-        assert!(
-            game.current_duration >= self.max_game_duration || env::block_timestamp() - game.last_turn_timestamp > self.max_turn_duration, 
-            "Too early to stop the game"
-        );
+        self.internal_debug_log(|| format!(
+            "game.current_duration : {}, env::block_timestamp() : {}, game.initiated_at : {}, \
+            self.max_game_duration : {}, game.last_turn_timestamp : {}, game.time_remaining : {:?}",
+            game.current_duration, env::block_timestamp(), game.initiated_at,
+            self.max_game_duration, game.last_turn_timestamp, game.time_remaining
+        ));
+        assert!(self.internal_is_stoppable(&game, env::block_timestamp()), "Too early to stop the game");
 
         let (winner, looser) = if account_id == player1 {
             (player1, player2)
@@ -450,30 +691,16 @@ impl Contract {
             panic!("You are not in this game. GameId: {} ", game_id)
         };
 
-        self.internal_update_stats(
-            Some(&game.reward().token_id), 
-            &looser, 
-            UpdateStatsAction::AddPenaltyGame, 
-            None, 
-            None);
-
-        let balance = self.internal_distribute_reward(game_id, Some(&winner));
-        game.change_state(GameState::Finished);
-        self.internal_update_game(game_id, &game);
-
-        let game_to_store = GameLimitedView{
-            game_result: GameResult::Win(winner.clone()),
-            player1: winner,
-            player2: looser,
-            reward_or_tie_refund: GameDeposit {
-                token_id: game.reward().token_id,
-                balance
-            },
-            board: game.board.tiles,
-        };
-
-        self.internal_store_game(game_id, game_to_store);
-        self.internal_stop_game(game_id);
+        let game_result = self.internal_timeout_result(&game, winner);
+        if !matches!(game_result, GameResult::Cancelled) && !game.is_practice() {
+            self.internal_update_stats(
+                Some(&game.reward().token_id), 
+                &looser, 
+                UpdateStatsAction::AddPenaltyGame, 
+                None, 
+                None);
+        }
+        self.internal_finish_game(game_id, game, game_result, None);
     }
 }
 
@@ -481,8 +708,10 @@ impl Contract {
 mod tests {
     use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
     use near_sdk::test_utils::VMContextBuilder;
-    use near_sdk::{testing_env, Balance};
-    use crate::views::GameView;
+    use near_sdk::{testing_env, Balance, RuntimeFeesConfig, VMConfig};
+    use crate::internal::split_pot;
+    use crate::notifications::{NOTIFY_ALL, NOTIFY_GAME_END, NOTIFY_TURN_REMINDER};
+    use crate::views::{DepositRangeView, GameResultKind, GameView, GamesCountsView, ProtocolStats, TurnInfo};
 
     use super::*;
 
@@ -519,7 +748,8 @@ mod tests {
                 service_fee_percentage: service_fee_percentage.unwrap(),
                 referrer_ratio: referrer_fee.unwrap_or(BASIS_P / 2),
                 max_game_duration_sec: max_game_duration_sec.unwrap(),
-                max_stored_games: 50u8
+                max_stored_games: 50u8,
+                ..Default::default()
             })
         };
 
@@ -552,10 +782,26 @@ mod tests {
             .predecessor_account_id(user.clone())
             .signer_account_id(user.clone())
             .build());
-        ctr.make_available(Some(GameConfigNear { 
-            opponent_id, 
-            referrer_id 
-        }));
+        make_available_near_config(ctx, ctr, user, amount, GameConfigNear {
+            opponent_id,
+            referrer_id,
+            ..Default::default()
+        });
+    }
+
+    fn make_available_near_config(
+        ctx: &mut VMContextBuilder,
+        ctr: &mut Contract,
+        user: &AccountId,
+        amount: Balance,
+        game_config: GameConfigNear,
+    ) {
+        testing_env!(ctx
+            .attached_deposit(amount)
+            .predecessor_account_id(user.clone())
+            .signer_account_id(user.clone())
+            .build());
+        ctr.make_available(Some(game_config));
     }
 
     fn make_available_ft(
@@ -583,7 +829,7 @@ mod tests {
             .predecessor_account_id(user.clone())
             .signer_account_id(user.clone())
             .build());
-        ctr.make_unavailable();
+        ctr.make_unavailable(None);
     }
 
     fn start_game(
@@ -595,7 +841,20 @@ mod tests {
         testing_env!(ctx
             .predecessor_account_id(user.clone())
             .build());
-        ctr.start_game(opponent.clone())
+        ctr.start_game(opponent.clone(), None)
+    }
+
+    /// start NEAR game between `user()` and `opponent()`
+    /// returns game id with players in game order
+    fn start_near_game(
+        ctx: &mut VMContextBuilder,
+        ctr: &mut Contract,
+    ) -> (GameId, AccountId, AccountId) {
+        make_available_near(ctx, ctr, &user(), ONE_NEAR, None, None);
+        make_available_near(ctx, ctr, &opponent(), ONE_NEAR, None, None);
+        let game_id = start_game(ctx, ctr, &user(), &opponent());
+        let (player_1, player_2) = ctr.internal_get_game_players(&game_id);
+        (game_id, player_1, player_2)
     }
 
     fn make_move(
@@ -625,13 +884,75 @@ mod tests {
             .attached_deposit(ONE_YOCTO)
             .block_timestamp(nanos)
             .build());
-        ctr.stop_game(&game_id)
+        ctr.stop_game(game_id)
+    }
+
+    fn give_up(
+        ctx: &mut VMContextBuilder,
+        ctr: &mut Contract,
+        user: &AccountId,
+        game_id: &GameId,
+    ) {
+        testing_env!(ctx
+            .predecessor_account_id(user.clone())
+            .attached_deposit(ONE_YOCTO)
+            .build());
+        ctr.give_up(game_id)
+    }
+
+    /// xorshift generator for property tests, deterministic to reproduce failures by seed
+    struct TestRng(u64);
+
+    impl TestRng {
+        fn next(&mut self, bound: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % bound as u64) as usize
+        }
+    }
+
+    fn random_board(rng: &mut TestRng) -> Board {
+        let mut board = Board { tiles: Default::default(), current_piece: Piece::X, winner: None };
+        for row in board.tiles.iter_mut() {
+            for tile in row.iter_mut() {
+                *tile = match rng.next(3) {
+                    0 => None,
+                    1 => Some(Piece::X),
+                    _ => Some(Piece::O),
+                };
+            }
+        }
+        board
     }
 
-    fn get_board_current_player(game: &Game) -> AccountId {
-        game.current_player_account_id()
+    /// brute-force scan of all board lines going through (`row`, `col`)
+    fn reference_winner_at(board: &Board, row: usize, col: usize, win_rule: WinRule) -> Option<Winner> {
+        let mut lines: Vec<Vec<(usize, usize)>> = vec![];
+        if win_rule != WinRule::DiagonalsOnly {
+            for i in 0..BOARD_SIZE {
+                lines.push((0..BOARD_SIZE).map(|j| (i, j)).collect());
+                lines.push((0..BOARD_SIZE).map(|j| (j, i)).collect());
+            }
+        }
+        if win_rule != WinRule::NoDiagonals {
+            lines.push((0..BOARD_SIZE).map(|i| (i, i)).collect());
+            lines.push((0..BOARD_SIZE).map(|i| (i, BOARD_SIZE - 1 - i)).collect());
+        }
+        let winner = lines
+            .iter()
+            .filter(|line| line.contains(&(row, col)))
+            .find_map(|line| {
+                let first = board.tiles[line[0].0][line[0].1];
+                line.iter().all(|&(r, c)| board.tiles[r][c] == first).then_some(first).flatten()
+            })
+            .map(|piece| if piece == Piece::X { Winner::X } else { Winner::O });
+        let is_full = board.tiles.iter().flatten().all(|tile| tile.is_some());
+        winner.or_else(|| is_full.then_some(Winner::Tie))
     }
 
+    const WIN_RULES: [WinRule; 3] = [WinRule::Standard, WinRule::NoDiagonals, WinRule::DiagonalsOnly];
+
     /// This function is used to print out the board in a human readable way
     fn print_tiles(tiles: &[[Option<Piece>; BOARD_SIZE]; BOARD_SIZE]) {
         // The result of this function will be something like the following:
@@ -662,39 +983,51 @@ mod tests {
     }
 
     fn game_basics() -> Result<(VMContextBuilder, Contract), std::io::Error> {
-        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 10)); // HERE
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25)); // HERE
         assert!(ctr.get_available_players().is_empty());
         whitelist_token(&mut ctr);
         assert_eq!(ctr.get_whitelisted_tokens(), Vec::from([
-            (acc_cheddar(), (ONE_CHEDDAR / 10).into())
+            (acc_cheddar(), DepositRangeView { min_deposit: U128(ONE_CHEDDAR / 10), max_deposit: None, referrer_eligible: true })
         ]));
+        assert_eq!(ctr.get_token_min_deposit(acc_cheddar()), Some(U128(ONE_CHEDDAR / 10)));
+        assert_eq!(ctr.get_token_min_deposit(near()), None);
 
         let gc1 = GameConfigArgs { 
             opponent_id: Some(opponent()), 
-            referrer_id: Some(referrer()) 
+            referrer_id: Some(referrer()),
+            ..Default::default()
         };
         let msg1 = near_sdk::serde_json::to_string(&gc1).expect("err serialize");
         let gc2 = GameConfigArgs { 
             opponent_id: Some(user()), 
-            referrer_id: None 
+            referrer_id: None,
+            ..Default::default()
         };
         let msg2 = near_sdk::serde_json::to_string(&gc2).expect("err serialize");
         make_available_ft(&mut ctx, &mut ctr, &user(), ONE_CHEDDAR, msg1);
         make_available_ft(&mut ctx, &mut ctr, &opponent(), ONE_CHEDDAR, msg2);
-        assert_eq!(ctr.get_available_players(), Vec::<(AccountId, GameConfigView)>::from([
-            (user(), GameConfigView { 
+        assert_eq!(ctr.get_available_players(), Vec::<(AccountId, InviteId, GameConfigView)>::from([
+            (user(), 0, GameConfigView { 
                 token_id: acc_cheddar(), 
                 deposit: U128(ONE_CHEDDAR), 
                 opponent_id: Some(opponent()), 
                 referrer_id: Some(referrer()),
-                created_at: 0
+                created_at: 0,
+                rules: GameRules::default(),
+                rematch_escrow: U128(0),
+                payout_to: None,
+                is_practice: false,
             }),
-            (opponent(), GameConfigView { 
+            (opponent(), 1, GameConfigView { 
                 token_id: acc_cheddar(), 
                 deposit: U128(ONE_CHEDDAR), 
                 opponent_id: Some(user()), 
                 referrer_id: None,
-                created_at: 0
+                created_at: 0,
+                rules: GameRules::default(),
+                rematch_escrow: U128(0),
+                payout_to: None,
+                is_practice: false,
             }),
         ]));
 
@@ -710,14 +1043,14 @@ mod tests {
         let game_cheddar = ctr.internal_get_game(&game_id_cheddar);
         let game_near = ctr.internal_get_game(&game_id_near);
         // cheddar
-        let player_1_c = game_cheddar.current_player_account_id().clone();
+        let _player_1_c = game_cheddar.current_player_account_id().clone();
         let player_2_c = game_cheddar.next_player_account_id().clone();
         // near
         let player_1_n = game_near.current_player_account_id().clone();
         let player_2_n = game_near.next_player_account_id().clone();
 
-        assert!(ctr.get_active_games().contains(&(game_id_cheddar, GameView::from(&game_cheddar))));
-        assert!(ctr.get_active_games().contains(&(game_id_near, GameView::from(&game_near))));
+        assert!(ctr.get_active_games().contains(&(game_id_cheddar, GameView::new(&game_cheddar, ctr.update_seq))));
+        assert!(ctr.get_active_games().contains(&(game_id_near, GameView::new(&game_near, ctr.update_seq))));
 
         // near game
         // 600000000000
@@ -771,33 +1104,41 @@ mod tests {
 
     #[test]
     fn test_whitelist_token() {
-        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 10));
+        let (_ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
         whitelist_token(&mut ctr);
         assert_eq!(ctr.get_whitelisted_tokens(), Vec::from([
-            (acc_cheddar(), U128(ONE_CHEDDAR / 10))
+            (acc_cheddar(), DepositRangeView { min_deposit: U128(ONE_CHEDDAR / 10), max_deposit: None, referrer_eligible: true })
         ]));
         assert!(ctr.get_available_players().is_empty());
     }
     #[test]
     fn make_available_unavailable_near() {
-        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 10));
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
         assert!(ctr.get_available_players().is_empty());
         make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, Some(referrer()));
         make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, Some(user()), None);
-        assert_eq!(ctr.get_available_players(), Vec::<(AccountId, GameConfigView)>::from([
-            (user(), GameConfigView { 
+        assert_eq!(ctr.get_available_players(), Vec::<(AccountId, InviteId, GameConfigView)>::from([
+            (user(), 0, GameConfigView { 
                 token_id: near(), 
                 deposit: U128(ONE_NEAR), 
                 opponent_id: None, 
                 referrer_id: Some(referrer()),
-                created_at: 0
+                created_at: 0,
+                rules: GameRules::default(),
+                rematch_escrow: U128(0),
+                payout_to: None,
+                is_practice: false,
             }),
-            (opponent(), GameConfigView { 
+            (opponent(), 1, GameConfigView { 
                 token_id: near(), 
                 deposit: U128(ONE_NEAR), 
                 opponent_id: Some(user()), 
                 referrer_id: None,
-                created_at: 0
+                created_at: 0,
+                rules: GameRules::default(),
+                rematch_escrow: U128(0),
+                payout_to: None,
+                is_practice: false,
             }),
         ]));
         make_unavailable(&mut ctx, &mut ctr, &user());
@@ -806,38 +1147,48 @@ mod tests {
     }
     #[test]
     fn test_make_available_unavailable() {
-        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 10));
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
         whitelist_token(&mut ctr);
         assert_eq!(ctr.get_whitelisted_tokens(), Vec::from([
-            (acc_cheddar(), (ONE_CHEDDAR / 10).into())
+            (acc_cheddar(), DepositRangeView { min_deposit: U128(ONE_CHEDDAR / 10), max_deposit: None, referrer_eligible: true })
         ]));
         assert!(ctr.get_available_players().is_empty());
         let gc1 = GameConfigArgs { 
             opponent_id: Some(opponent()), 
-            referrer_id: Some(referrer()) 
+            referrer_id: Some(referrer()),
+            ..Default::default()
         };
         let msg1 = near_sdk::serde_json::to_string(&gc1).expect("err serialize");
         let gc2 = GameConfigArgs { 
             opponent_id: Some(user()), 
-            referrer_id: None 
+            referrer_id: None,
+            ..Default::default()
         };
         let msg2 = near_sdk::serde_json::to_string(&gc2).expect("err serialize");
         make_available_ft(&mut ctx, &mut ctr, &user(), ONE_CHEDDAR, msg1);
         make_available_ft(&mut ctx, &mut ctr, &opponent(), ONE_CHEDDAR, msg2);
-        assert_eq!(ctr.get_available_players(), Vec::<(AccountId, GameConfigView)>::from([
-            (user(), GameConfigView { 
+        assert_eq!(ctr.get_available_players(), Vec::<(AccountId, InviteId, GameConfigView)>::from([
+            (user(), 0, GameConfigView { 
                 token_id: acc_cheddar(), 
                 deposit: U128(ONE_CHEDDAR), 
                 opponent_id: Some(opponent()), 
                 referrer_id: Some(referrer()),
-                created_at: 0
+                created_at: 0,
+                rules: GameRules::default(),
+                rematch_escrow: U128(0),
+                payout_to: None,
+                is_practice: false,
             }),
-            (opponent(), GameConfigView { 
+            (opponent(), 1, GameConfigView { 
                 token_id: acc_cheddar(), 
                 deposit: U128(ONE_CHEDDAR), 
                 opponent_id: Some(user()), 
                 referrer_id: None,
-                created_at: 0
+                created_at: 0,
+                rules: GameRules::default(),
+                rematch_escrow: U128(0),
+                payout_to: None,
+                is_practice: false,
             }),
         ]));
         make_unavailable(&mut ctx, &mut ctr, &user());
@@ -847,15 +1198,16 @@ mod tests {
     #[test]
     #[should_panic(expected="Mismatch tokens! Choosen tokens for opponent and you must be the same")]
     fn start_game_diff_tokens() {
-        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 10));
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
         whitelist_token(&mut ctr);
         assert_eq!(ctr.get_whitelisted_tokens(), Vec::from([
-            (acc_cheddar(), (ONE_CHEDDAR / 10).into())
+            (acc_cheddar(), DepositRangeView { min_deposit: U128(ONE_CHEDDAR / 10), max_deposit: None, referrer_eligible: true })
         ]));
         assert!(ctr.get_available_players().is_empty());
         let gc1 = GameConfigArgs { 
             opponent_id: Some(opponent()), 
-            referrer_id: Some(referrer()) 
+            referrer_id: Some(referrer()),
+            ..Default::default()
         };
         let msg1 = near_sdk::serde_json::to_string(&gc1).expect("err serialize");
 
@@ -865,38 +1217,48 @@ mod tests {
     }
     #[test]
     fn test_give_up() {
-        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 10));
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
         whitelist_token(&mut ctr);
         assert_eq!(ctr.get_whitelisted_tokens(), Vec::from([
-            (acc_cheddar(), (ONE_CHEDDAR / 10).into())
+            (acc_cheddar(), DepositRangeView { min_deposit: U128(ONE_CHEDDAR / 10), max_deposit: None, referrer_eligible: true })
         ]));
         assert!(ctr.get_available_players().is_empty());
         let gc1 = GameConfigArgs { 
             opponent_id: Some(opponent()), 
-            referrer_id: Some(referrer()) 
+            referrer_id: Some(referrer()),
+            ..Default::default()
         };
         let msg1 = near_sdk::serde_json::to_string(&gc1).expect("err serialize");
         let gc2 = GameConfigArgs { 
             opponent_id: Some(user()), 
-            referrer_id: None 
+            referrer_id: None,
+            ..Default::default()
         };
         let msg2 = near_sdk::serde_json::to_string(&gc2).expect("err serialize");
         make_available_ft(&mut ctx, &mut ctr, &user(), ONE_CHEDDAR, msg1);
         make_available_ft(&mut ctx, &mut ctr, &opponent(), ONE_CHEDDAR, msg2);
-        assert_eq!(ctr.get_available_players(), Vec::<(AccountId, GameConfigView)>::from([
-            (user(), GameConfigView { 
+        assert_eq!(ctr.get_available_players(), Vec::<(AccountId, InviteId, GameConfigView)>::from([
+            (user(), 0, GameConfigView { 
                 token_id: acc_cheddar(), 
                 deposit: U128(ONE_CHEDDAR), 
                 opponent_id: Some(opponent()), 
                 referrer_id: Some(referrer()),
-                created_at: 0 
+                created_at: 0,
+                rules: GameRules::default(),
+                rematch_escrow: U128(0),
+                payout_to: None,
+                is_practice: false,
             }),
-            (opponent(), GameConfigView { 
+            (opponent(), 1, GameConfigView { 
                 token_id: acc_cheddar(), 
                 deposit: U128(ONE_CHEDDAR), 
                 opponent_id: Some(user()), 
                 referrer_id: None,
-                created_at: 0 
+                created_at: 0,
+                rules: GameRules::default(),
+                rematch_escrow: U128(0),
+                payout_to: None,
+                is_practice: false,
             }),
         ]));
         testing_env!(ctx
@@ -921,20 +1283,22 @@ mod tests {
     }
     #[test]
     fn test_game_basics() {
-        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 10));
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
         whitelist_token(&mut ctr);
         assert_eq!(ctr.get_whitelisted_tokens(), Vec::from([
-            (acc_cheddar(), (ONE_CHEDDAR / 10).into())
+            (acc_cheddar(), DepositRangeView { min_deposit: U128(ONE_CHEDDAR / 10), max_deposit: None, referrer_eligible: true })
         ]));
         assert!(ctr.get_available_players().is_empty());
         let gc1 = GameConfigArgs { 
             opponent_id: Some(opponent()), 
-            referrer_id: Some(referrer()) 
+            referrer_id: Some(referrer()),
+            ..Default::default()
         };
         let msg1 = near_sdk::serde_json::to_string(&gc1).expect("err serialize");
         let gc2 = GameConfigArgs { 
             opponent_id: Some(user()), 
-            referrer_id: None 
+            referrer_id: None,
+            ..Default::default()
         };
         let msg2 = near_sdk::serde_json::to_string(&gc2).expect("err serialize");
         make_available_ft(&mut ctx, &mut ctr, &user(), ONE_CHEDDAR, msg1);
@@ -952,7 +1316,7 @@ mod tests {
         assert_eq!(player_2, game.players[1].account_id);
         assert_eq!(game.board.current_piece, game.players[0].piece);
 
-        assert!(ctr.get_active_games().contains(&(game_id, GameView::from(&game))));
+        assert!(ctr.get_active_games().contains(&(game_id, GameView::new(&game, ctr.update_seq))));
 
         let mut tiles = make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 1);
         print_tiles(&tiles);
@@ -1002,20 +1366,22 @@ mod tests {
 
     #[test]
     fn test_tie_scenario() {
-        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 10));
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
         whitelist_token(&mut ctr);
         assert_eq!(ctr.get_whitelisted_tokens(), Vec::from([
-            (acc_cheddar(), (ONE_CHEDDAR / 10).into())
+            (acc_cheddar(), DepositRangeView { min_deposit: U128(ONE_CHEDDAR / 10), max_deposit: None, referrer_eligible: true })
         ]));
         assert!(ctr.get_available_players().is_empty());
         let gc1 = GameConfigArgs { 
             opponent_id: Some(opponent()), 
-            referrer_id: None 
+            referrer_id: None,
+            ..Default::default()
         };
         let msg1 = near_sdk::serde_json::to_string(&gc1).expect("err serialize");
         let gc2 = GameConfigArgs { 
             opponent_id: Some(user()), 
-            referrer_id: None 
+            referrer_id: None,
+            ..Default::default()
         };
         let msg2 = near_sdk::serde_json::to_string(&gc2).expect("err serialize");
         make_available_ft(&mut ctx, &mut ctr, &user(), ONE_CHEDDAR, msg1);
@@ -1035,7 +1401,7 @@ mod tests {
         assert_eq!(player_2, game.players[1].account_id);
         assert_eq!(game.board.current_piece, game.players[0].piece);
 
-        assert!(ctr.get_active_games().contains(&(game_id, GameView::from(&game))));
+        assert!(ctr.get_active_games().contains(&(game_id, GameView::new(&game, ctr.update_seq))));
 
         let mut tiles = make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 0);
         print_tiles(&tiles);
@@ -1108,20 +1474,22 @@ mod tests {
     #[test]
     #[should_panic(expected="Too early to stop the game")]
     fn test_stop_game_too_early() {
-        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 10));
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
         whitelist_token(&mut ctr);
         assert_eq!(ctr.get_whitelisted_tokens(), Vec::from([
-            (acc_cheddar(), U128(ONE_CHEDDAR / 10))
+            (acc_cheddar(), DepositRangeView { min_deposit: U128(ONE_CHEDDAR / 10), max_deposit: None, referrer_eligible: true })
         ]));
         assert!(ctr.get_available_players().is_empty());
         let gc1 = GameConfigArgs { 
             opponent_id: Some(opponent()), 
-            referrer_id: None 
+            referrer_id: None,
+            ..Default::default()
         };
         let msg1 = near_sdk::serde_json::to_string(&gc1).expect("err serialize");
         let gc2 = GameConfigArgs { 
             opponent_id: Some(user()), 
-            referrer_id: None 
+            referrer_id: None,
+            ..Default::default()
         };
         let msg2 = near_sdk::serde_json::to_string(&gc2).expect("err serialize");
         make_available_ft(&mut ctx, &mut ctr, &user(), ONE_CHEDDAR, msg1);
@@ -1141,7 +1509,7 @@ mod tests {
         assert_eq!(player_2, game.players[1].account_id);
         assert_eq!(game.board.current_piece, game.players[0].piece);
 
-        assert!(ctr.get_active_games().contains(&(game_id, GameView::from(&game))));
+        assert!(ctr.get_active_games().contains(&(game_id, GameView::new(&game, ctr.update_seq))));
 
         let mut tiles = make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 0);
         print_tiles(&tiles);
@@ -1166,20 +1534,22 @@ mod tests {
     #[test]
     #[should_panic(expected="No access")]
     fn test_stop_game_wrong_access() {
-        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 10));
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
         whitelist_token(&mut ctr);
         assert_eq!(ctr.get_whitelisted_tokens(), Vec::from([
-            (acc_cheddar(), (ONE_CHEDDAR / 10).into())
+            (acc_cheddar(), DepositRangeView { min_deposit: U128(ONE_CHEDDAR / 10), max_deposit: None, referrer_eligible: true })
         ]));
         assert!(ctr.get_available_players().is_empty());
         let gc1 = GameConfigArgs { 
             opponent_id: Some(opponent()), 
-            referrer_id: None 
+            referrer_id: None,
+            ..Default::default()
         };
         let msg1 = near_sdk::serde_json::to_string(&gc1).expect("err serialize");
         let gc2 = GameConfigArgs { 
             opponent_id: Some(user()), 
-            referrer_id: None 
+            referrer_id: None,
+            ..Default::default()
         };
         let msg2 = near_sdk::serde_json::to_string(&gc2).expect("err serialize");
         make_available_ft(&mut ctx, &mut ctr, &user(), ONE_CHEDDAR, msg1);
@@ -1199,7 +1569,7 @@ mod tests {
         assert_eq!(player_2, game.players[1].account_id);
         assert_eq!(game.board.current_piece, game.players[0].piece);
 
-        assert!(ctr.get_active_games().contains(&(game_id, GameView::from(&game))));
+        assert!(ctr.get_active_games().contains(&(game_id, GameView::new(&game, ctr.update_seq))));
 
         let mut tiles = make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 0);
         print_tiles(&tiles);
@@ -1220,155 +1590,91 @@ mod tests {
         stop_game(&mut ctx, &mut ctr, &player_1, &game_id, 601);
     }
 
-    // #[test]
-    // fn test_expired_game() {
-    //     let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 10));
-    //     whitelist_token(&mut ctr);
-    //     assert_eq!(ctr.get_whitelisted_tokens(), Vec::from([
-    //         (acc_cheddar(), (ONE_CHEDDAR / 10).into())
-    //     ]));
-    //     assert!(ctr.get_available_players().is_empty());
-    //     let gc1 = GameConfigArgs { 
-    //         opponent_id: Some(opponent()), 
-    //         referrer_id: None 
-    //     };
-    //     let msg1 = near_sdk::serde_json::to_string(&gc1).expect("err serialize");
-    //     let gc2 = GameConfigArgs { 
-    //         opponent_id: Some(user()), 
-    //         referrer_id: None 
-    //     };
-    //     let msg2 = near_sdk::serde_json::to_string(&gc2).expect("err serialize");
-    //     make_available_ft(&mut ctx, &mut ctr, &user(), ONE_CHEDDAR, msg1);
-    //     make_available_ft(&mut ctx, &mut ctr, &opponent(), ONE_CHEDDAR, msg2);
-        
-    //     let game_id = start_game(&mut ctx, &mut ctr, &user(), &opponent());
-        
-    //     let game = ctr.internal_get_game(&game_id);
-    //     let player_1 = game.current_player_account_id().clone();
-    //     let player_2 = game.next_player_account_id().clone();
-
-    //     println!("( {} , {} )", player_1, player_2);
-
-    //     assert_ne!(player_1, game.next_player_account_id());
-    //     assert_ne!(game.players[0].piece, game.players[1].piece);
-    //     assert_eq!(player_1, game.players[0].account_id);
-    //     assert_eq!(player_2, game.players[1].account_id);
-    //     assert_eq!(game.board.current_piece, game.players[0].piece);
-
-    //     assert!(ctr.get_active_games().contains(&(game_id, game.clone())));
-
-    //     let mut tiles = make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 0);
-    //     print_tiles(&tiles);
-    //     tiles = make_move(&mut ctx, &mut ctr, &player_2, &game_id, 0, 1);
-    //     print_tiles(&tiles);
-    //     tiles = make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 2);
-    //     print_tiles(&tiles);
-    //     tiles = make_move(&mut ctx, &mut ctr, &player_2, &game_id, 2, 0);
-    //     print_tiles(&tiles);
-    //     tiles = make_move(&mut ctx, &mut ctr, &player_1, &game_id, 2, 1);
-    //     print_tiles(&tiles);
-    //     tiles = make_move(&mut ctx, &mut ctr, &player_2, &game_id, 2, 2);
-    //     print_tiles(&tiles);
-    //     tiles = make_move(&mut ctx, &mut ctr, &player_1, &game_id, 1, 0);
-    //     print_tiles(&tiles);
-    //     testing_env!(ctx
-    //         .predecessor_account_id(player_2.clone())
-    //         .block_timestamp(sec_to_nano(601))
-    //         .build()
-    //     );
-    //     // player2 turn too slow
-    //     ctr.make_move(&game_id, 1, 2);
-    //     assert!(ctr.get_stats(&player_1).victories_num == 1);
-    //     assert!(ctr.get_stats(&player_2).victories_num == 0);
-    //     assert_eq!(
-    //         ctr.get_stats(&player_1).total_reward,
-    //         Vec::from([
-    //             (
-    //                 acc_cheddar(),
-    //                 (2 * ONE_CHEDDAR - (2 * ONE_CHEDDAR / BASIS_P as u128 * MIN_FEES as u128)) 
-    //             )
-    //         ])
-    //     )
-    // }
-
-    // #[test]
-    // fn test_stop_game() {
-    //     let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  None);
-    //     whitelist_token(&mut ctr);
-    //     assert_eq!(ctr.get_whitelisted_tokens(), Vec::from([
-    //         (acc_cheddar(), (ONE_CHEDDAR / 10).into())
-    //     ]));
-    //     assert!(ctr.get_available_players().is_empty());
-    //     let gc1 = GameConfigArgs { 
-    //         opponent_id: Some(opponent()), 
-    //         referrer_id: None 
-    //     };
-    //     let msg1 = near_sdk::serde_json::to_string(&gc1).expect("err serialize");
-    //     let gc2 = GameConfigArgs { 
-    //         opponent_id: Some(user()), 
-    //         referrer_id: None 
-    //     };
-    //     let msg2 = near_sdk::serde_json::to_string(&gc2).expect("err serialize");
-    //     make_available_ft(&mut ctx, &mut ctr, &user(), ONE_CHEDDAR, msg1);
-    //     make_available_ft(&mut ctx, &mut ctr, &opponent(), ONE_CHEDDAR, msg2);
-        
-    //     let game_id = start_game(&mut ctx, &mut ctr, &user(), &opponent());
-        
-    //     let game = ctr.internal_get_game(&game_id);
-    //     let player_1 = game.current_player_account_id().clone();
-    //     let player_2 = game.next_player_account_id().clone();
-
-    //     println!("( {} , {} )", player_1, player_2);
-
-    //     assert_ne!(player_1, game.next_player_account_id());
-    //     assert_ne!(game.players[0].piece, game.players[1].piece);
-    //     assert_eq!(player_1, game.players[0].account_id);
-    //     assert_eq!(player_2, game.players[1].account_id);
-    //     assert_eq!(game.board.current_piece, game.players[0].piece);
-
-    //     assert!(ctr.get_active_games().contains(&(game_id, game.clone())));
-
-    //     let mut tiles = make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 0);
-    //     print_tiles(&tiles);
-    //     tiles = make_move(&mut ctx, &mut ctr, &player_2, &game_id, 0, 1);
-    //     print_tiles(&tiles);
-    //     tiles = make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 2);
-    //     print_tiles(&tiles);
-    //     tiles = make_move(&mut ctx, &mut ctr, &player_2, &game_id, 2, 0);
-    //     print_tiles(&tiles);
-    //     tiles = make_move(&mut ctx, &mut ctr, &player_1, &game_id, 2, 1);
-    //     print_tiles(&tiles);
-    //     tiles = make_move(&mut ctx, &mut ctr, &player_2, &game_id, 2, 2);
-    //     print_tiles(&tiles);
-    //     tiles = make_move(&mut ctx, &mut ctr, &player_1, &game_id, 1, 0);
-    //     print_tiles(&tiles);
-    //     tiles = make_move(&mut ctx, &mut ctr, &player_2, &game_id, 1, 2);
-    //     print_tiles(&tiles);
-        
-    //     stop_game(&mut ctx, &mut ctr, &player_2, &game_id, 601);
-        
-    //     let player_1_stats = ctr.get_stats(&opponent());
-    //     let player_2_stats = ctr.get_stats(&user());
-
-    //     assert!(
-    //         player_1_stats.games_played == player_2_stats.games_played
-    //     );
-    //     assert!(
-    //         player_2_stats.victories_num == 1 && player_1_stats.victories_num == 0
-    //     );
-    //     assert!(
-    //         !player_2_stats.total_reward.is_empty() && player_1_stats.total_reward.is_empty()
-    //     ); 
-    //     assert_eq!(
-    //         player_2_stats.total_reward,
-    //         Vec::from([
-    //             (
-    //                 acc_cheddar(),
-    //                 (2 * ONE_CHEDDAR - (2 * ONE_CHEDDAR / BASIS_P as u128 * MIN_FEES as u128)) 
-    //             )
-    //         ])
-    //     )
-    // }
+    /// FT game of `user` (initiator, moves first) and `opponent` after 7 moves, `opponent` turn
+    fn deterministic_ft_game(ctx: &mut VMContextBuilder, ctr: &mut Contract) -> GameId {
+        ctr.set_deterministic_first_move(true);
+        whitelist_token(ctr);
+        for (account_id, opponent_id) in [(user(), opponent()), (opponent(), user())] {
+            let gc = GameConfigArgs { opponent_id: Some(opponent_id), ..Default::default() };
+            let msg = near_sdk::serde_json::to_string(&gc).expect("err serialize");
+            make_available_ft(ctx, ctr, &account_id, ONE_CHEDDAR, msg);
+        }
+        let game_id = start_game(ctx, ctr, &user(), &opponent());
+        assert!(ctr.get_available_players().is_empty());
+
+        let game = ctr.internal_get_game(&game_id);
+        assert_eq!(game.current_player_account_id(), user());
+        assert_eq!(game.next_player_account_id(), opponent());
+        assert_ne!(game.players[0].piece, game.players[1].piece);
+        assert_eq!(game.players[0].account_id, user());
+        assert_eq!(game.board.current_piece, game.players[0].piece);
+        assert!(ctr.get_active_games().contains(&(game_id, GameView::new(&game, ctr.update_seq))));
+
+        for (account_id, row, col) in [
+            (user(), 0, 0), (opponent(), 0, 1), (user(), 0, 2), (opponent(), 2, 0),
+            (user(), 2, 1), (opponent(), 2, 2), (user(), 1, 0),
+        ] {
+            make_move(ctx, ctr, &account_id, &game_id, row, col);
+        }
+        assert_eq!(ctr.internal_get_game(&game_id).current_player_account_id(), opponent());
+        game_id
+    }
+
+    #[test]
+    fn test_expired_game() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let game_id = deterministic_ft_game(&mut ctx, &mut ctr);
+        testing_env!(ctx
+            .predecessor_account_id(opponent())
+            .block_timestamp(ctr.per_player_budget + 1)
+            .build()
+        );
+        // player2 turn too slow, the move isn't applied and player2 is penalized
+        let tiles = ctr.make_move(&game_id, 1, 2);
+        assert_eq!(tiles[1][2], None);
+        assert_eq!(ctr.get_stats(&opponent()).penalties_num, 1);
+        assert_eq!(ctr.get_stats(&user()).penalties_num, 0);
+        assert!(ctr.get_stats(&user()).victories_num == 1);
+        assert!(ctr.get_stats(&opponent()).victories_num == 0);
+        assert_eq!(
+            ctr.get_stats(&user()).total_reward,
+            Vec::from([
+                (
+                    acc_cheddar(),
+                    (2 * ONE_CHEDDAR - (2 * ONE_CHEDDAR / BASIS_P as u128 * MIN_FEES as u128)) 
+                )
+            ])
+        )
+    }
+
+    #[test]
+    fn test_stop_game() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let game_id = deterministic_ft_game(&mut ctx, &mut ctr);
+        make_move(&mut ctx, &mut ctr, &opponent(), &game_id, 1, 2);
+        // player1 stalls
+        let budget_sec = nano_to_sec(ctr.per_player_budget);
+        stop_game(&mut ctx, &mut ctr, &opponent(), &game_id, budget_sec + 1);
+
+        let player_1_stats = ctr.get_stats(&user());
+        let player_2_stats = ctr.get_stats(&opponent());
+        assert!(
+            player_1_stats.games_played == player_2_stats.games_played
+        );
+        assert!(
+            player_2_stats.victories_num == 1 && player_1_stats.victories_num == 0
+        );
+        assert_eq!(
+            player_2_stats.total_reward,
+            Vec::from([
+                (
+                    acc_cheddar(),
+                    (2 * ONE_CHEDDAR - (2 * ONE_CHEDDAR / BASIS_P as u128 * MIN_FEES as u128)) 
+                )
+            ])
+        );
+        assert_eq!(player_1_stats.penalties_num, 1);
+    }
 
     #[test]
     fn test_new_views() -> Result<(), std::io::Error>{
@@ -1388,14 +1694,14 @@ mod tests {
         assert_eq!(ctr.get_available_players().len(), 3);
 
         testing_env!(ctx
-            .block_timestamp(ctr.max_game_duration + MAX_TIME_TO_BE_AVAILABLE)
+            .block_timestamp(ctr.max_game_duration + ctr.time_to_be_available)
             .build()
         );
         assert_eq!(ctr.get_available_players().len(), 3);
 
         // test ping expired players
         testing_env!(ctx
-            .block_timestamp(ctr.max_game_duration + MAX_TIME_TO_BE_AVAILABLE + 2)
+            .block_timestamp(ctr.max_game_duration + ctr.time_to_be_available + 2)
             .build()
         );
         make_available_near(&mut ctx, &mut ctr, &"fourth".parse().unwrap(), ONE_NEAR, None, None);
@@ -1408,7 +1714,7 @@ mod tests {
         make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, None, None);
         make_available_near(&mut ctx, &mut ctr, &"third".parse().unwrap(), ONE_NEAR, None, None);
 
-        // first game starts at (max_game_duration + MAX_TIME_TO_BE_AVAILABLE +2) timestamp
+        // first game starts at (max_game_duration + time_to_be_available + 2) timestamp
         let first_game_id = start_game(&mut ctx, &mut ctr, &user(), &opponent());
         let first_game = ctr.internal_get_game(&first_game_id); 
         let current_player_first_game = first_game.current_player_account_id();
@@ -1421,13 +1727,14 @@ mod tests {
         let looser_num_penalties = future_looser_stats.penalties_num;
         
         // second game starts 12 minutes after first
+        let turn_duration = ctr.max_game_duration / MAX_NUM_TURNS;
         testing_env!(ctx
-            .block_timestamp(ctr.max_game_duration + MAX_TIME_TO_BE_AVAILABLE + 2 + ctr.max_turn_duration * 25 + 1)
+            .block_timestamp(ctr.max_game_duration + ctr.time_to_be_available + 2 + turn_duration * 25 + 1)
             .build()
         );
 
-        println!("game duration max - {}", ctr.max_game_duration);
-        println!("turn duration max - {}", ctr.max_turn_duration);
+        assert_eq!(ctr.max_game_duration, sec_to_nano(60 * 25));
+        assert_eq!(ctr.per_player_budget, ctr.max_game_duration / PLAYERS_NUM as u64);
 
         let second_game_id = start_game(&mut ctx, &mut ctr, &"third".parse().unwrap(), &"fourth".parse().unwrap());
         
@@ -1438,14 +1745,14 @@ mod tests {
         let next_player_second_game = second_game.next_player_account_id();
 
         testing_env!(ctx
-            .block_timestamp(second_game.initiated_at + ctr.max_turn_duration - 1)
+            .block_timestamp(second_game.initiated_at + turn_duration - 1)
             .build()
         );
         make_move(&mut ctx, &mut ctr, &current_player_second_game, &second_game_id, 0, 0);
         second_game = ctr.internal_get_game(&second_game_id); 
 
         testing_env!(ctx
-            .block_timestamp(second_game.initiated_at + (ctr.max_turn_duration - 1) + (ctr.max_turn_duration - 1))
+            .block_timestamp(second_game.initiated_at + (turn_duration - 1) + (turn_duration - 1))
             .build()
         );
         make_move(&mut ctx, &mut ctr, &next_player_second_game, &second_game_id, 0, 1);
@@ -1480,4 +1787,3171 @@ mod tests {
 
         Ok(())
     }
-}
\ No newline at end of file
+    #[test]
+    fn test_is_my_turn() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+        make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, None, None);
+        let game_id = start_game(&mut ctx, &mut ctr, &user(), &opponent());
+
+        let game = ctr.internal_get_game(&game_id);
+        let player_1 = game.current_player_account_id();
+        let player_2 = game.next_player_account_id();
+
+        assert_eq!(ctr.is_my_turn(&game_id, player_1.clone()), Some(true));
+        assert_eq!(ctr.is_my_turn(&game_id, player_2.clone()), Some(false));
+        assert_eq!(ctr.is_my_turn(&game_id, referrer()), None);
+        assert_eq!(ctr.is_my_turn(&(game_id + 1), player_1.clone()), None);
+
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 0);
+        assert_eq!(ctr.is_my_turn(&game_id, player_1), Some(false));
+        assert_eq!(ctr.is_my_turn(&game_id, player_2), Some(true));
+    }
+
+    #[test]
+    fn test_fee_schedule() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MAX_FEES), None,  Some(60 * 25));
+        ctr.set_fee_schedule(vec![FeeBucket { max_turns: 4, service_fee_percentage: MIN_FEES }]);
+
+        let near_reward = |ctr: &Contract, account_id: &AccountId| -> Balance {
+            ctr.get_stats(account_id).total_reward
+                .iter()
+                .find(|(token_id, _)| token_id == &near())
+                .map(|(_, balance)| *balance)
+                .unwrap_or(0)
+        };
+
+        // short game pays bucket fee
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+        make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, None, None);
+        let game_id = start_game(&mut ctx, &mut ctr, &user(), &opponent());
+        let (player_1, player_2) = ctr.internal_get_game_players(&game_id);
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 0);
+        make_move(&mut ctx, &mut ctr, &player_2, &game_id, 1, 0);
+        give_up(&mut ctx, &mut ctr, &player_1, &game_id);
+        assert_eq!(
+            near_reward(&ctr, &player_2),
+            2 * ONE_NEAR - (2 * ONE_NEAR / BASIS_P as u128) * MIN_FEES as u128
+        );
+
+        // long game pays flat service fee
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+        make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, None, None);
+        let game_id = start_game(&mut ctx, &mut ctr, &user(), &opponent());
+        let (player_1, player_2) = ctr.internal_get_game_players(&game_id);
+        let reward_before = near_reward(&ctr, &player_1);
+        for col in 0..3 {
+            make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, col);
+            make_move(&mut ctx, &mut ctr, &player_2, &game_id, 1, col);
+        }
+        give_up(&mut ctx, &mut ctr, &player_2, &game_id);
+        assert_eq!(
+            near_reward(&ctr, &player_1) - reward_before,
+            2 * ONE_NEAR - (2 * ONE_NEAR / BASIS_P as u128) * MAX_FEES as u128
+        );
+    }
+
+    #[test]
+    #[should_panic(expected="fee schedule buckets must be sorted by max_turns without duplicates")]
+    fn test_fee_schedule_unsorted() {
+        let (_ctx, mut ctr) = setup_contract(user(), Some(MAX_FEES), None,  Some(60 * 25));
+        ctr.set_fee_schedule(vec![
+            FeeBucket { max_turns: 10, service_fee_percentage: MIN_FEES },
+            FeeBucket { max_turns: 4, service_fee_percentage: MIN_FEES }
+        ]);
+    }
+
+    #[test]
+    fn test_protocol_fee_boundary() {
+        // 0.1% * (100% - 90%) = 0.01% is the smallest protocol part
+        Config { service_fee_percentage: MIN_FEES, referrer_ratio: 9000, ..Config::default() }.assert_valid();
+        Config { service_fee_percentage: MAX_FEES, referrer_ratio: BASIS_P - 10, ..Config::default() }.assert_valid();
+    }
+
+    #[test]
+    #[should_panic(expected="referrer fee, rakeback and first threat bonus 9001 leave nothing to protocol from service fee 10")]
+    fn test_protocol_fee_rounded_to_zero() {
+        Config { service_fee_percentage: MIN_FEES, referrer_ratio: 9001, ..Config::default() }.assert_valid();
+    }
+
+    #[test]
+    #[should_panic(expected="referrer fee, rakeback and first threat bonus 10000 leave nothing to protocol from service fee 1000")]
+    fn test_protocol_fee_full_referrer() {
+        Config { service_fee_percentage: MAX_FEES, referrer_ratio: BASIS_P, ..Config::default() }.assert_valid();
+    }
+
+    #[test]
+    #[should_panic(expected="rakeback and referrer fee together need to be less than 100% from total fees")]
+    fn test_rakeback_leaves_no_protocol_fee() {
+        let (_ctx, mut ctr) = setup_contract(user(), Some(MAX_FEES), Some(BASIS_P / 2),  Some(60 * 25));
+        ctr.set_rakeback_ratio(BASIS_P / 2);
+    }
+
+    #[test]
+    #[should_panic(expected="first threat bonus, rakeback and referrer fee together need to be less than 100% from total fees")]
+    fn test_first_threat_bonus_leaves_no_protocol_fee() {
+        let (_ctx, mut ctr) = setup_contract(user(), Some(MAX_FEES), Some(BASIS_P / 2),  Some(60 * 25));
+        ctr.set_rakeback_ratio(BASIS_P / 4);
+        ctr.set_first_threat_bonus(BASIS_P / 4);
+    }
+
+    #[test]
+    fn test_fee_parts_boundary() {
+        // 0.1% * (100% - 50% - 30% - 10%) = 0.01% is the smallest protocol part
+        Config {
+            service_fee_percentage: MIN_FEES,
+            referrer_ratio: 5000,
+            rakeback_ratio: Some(3000),
+            first_threat_bonus: Some(1000),
+            ..Config::default()
+        }.assert_valid();
+        let (_ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), Some(5000),  Some(60 * 25));
+        ctr.set_rakeback_ratio(3000);
+        ctr.set_first_threat_bonus(1000);
+    }
+
+    #[test]
+    #[should_panic(expected="referrer fee, rakeback and first threat bonus 9001 leave nothing to protocol from service fee 10")]
+    fn test_fee_parts_rounded_to_zero() {
+        let (_ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), Some(5000),  Some(60 * 25));
+        ctr.set_rakeback_ratio(3000);
+        ctr.set_first_threat_bonus(1001);
+    }
+
+    #[test]
+    #[should_panic(expected="referrer fee, rakeback and first threat bonus 9001 leave nothing to protocol from service fee 10")]
+    fn test_fee_schedule_with_rakeback_protocol_fee() {
+        let (_ctx, mut ctr) = setup_contract(user(), Some(MAX_FEES), Some(5000),  Some(60 * 25));
+        ctr.set_rakeback_ratio(4001);
+        ctr.set_fee_schedule(vec![FeeBucket { max_turns: 10, service_fee_percentage: MIN_FEES }]);
+    }
+
+    #[test]
+    #[should_panic(expected="fees need to be in range 0.1..100% from total fees")]
+    fn test_referrer_fee_above_basis_p() {
+        let (_ctx, mut ctr) = setup_contract(user(), Some(MAX_FEES), None,  Some(60 * 25));
+        ctr.set_service_fee(MAX_FEES, BASIS_P + 1);
+    }
+
+    #[test]
+    #[should_panic(expected="referrer fee, rakeback and first threat bonus 9500 leave nothing to protocol from service fee 10")]
+    fn test_fee_schedule_protocol_fee() {
+        let (_ctx, mut ctr) = setup_contract(user(), Some(MAX_FEES), None,  Some(60 * 25));
+        ctr.set_service_fee(MAX_FEES, 9500);
+        ctr.set_fee_schedule(vec![FeeBucket { max_turns: 10, service_fee_percentage: MIN_FEES }]);
+    }
+
+    #[test]
+    fn test_stored_game_winner_make_move() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        for col in 0..4 {
+            make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, col);
+            make_move(&mut ctx, &mut ctr, &player_2, &game_id, 1, col);
+        }
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 4);
+
+        let stored = ctr.get_game(&game_id);
+        assert_eq!(stored.game_result, GameResult::Win(player_1.clone(), WinReason::Line));
+        assert_eq!((stored.player1, stored.player2), (player_1.clone(), player_2.clone()));
+        assert_eq!((stored.winner, stored.loser), (Some(player_1), Some(player_2)));
+        assert_eq!(stored.winning_line, Some((0..BOARD_SIZE as u8).map(|col| (0, col)).collect()));
+    }
+
+    #[test]
+    fn test_stored_game_winner_give_up() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        give_up(&mut ctx, &mut ctr, &player_1, &game_id);
+
+        let stored = ctr.get_game(&game_id);
+        assert_eq!(stored.game_result, GameResult::Win(player_2.clone(), WinReason::GiveUp));
+        assert_eq!((stored.player1, stored.player2), (player_1.clone(), player_2.clone()));
+        assert_eq!((stored.winner, stored.loser), (Some(player_2), Some(player_1)));
+        assert_eq!(stored.winning_line, None);
+    }
+
+    #[test]
+    fn test_stored_game_winning_line() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        // player_1 completes the main diagonal in the center
+        let winning_moves = [(0, 0), (1, 1), (3, 3), (4, 4), (2, 2)];
+        let other_moves = [(0, 1), (0, 2), (0, 3), (1, 0)];
+        for (index, &(row, col)) in winning_moves.iter().enumerate() {
+            make_move(&mut ctx, &mut ctr, &player_1, &game_id, row, col);
+            if let Some(&(row, col)) = other_moves.get(index) {
+                make_move(&mut ctx, &mut ctr, &player_2, &game_id, row, col);
+            }
+        }
+
+        let stored = ctr.get_game(&game_id);
+        assert_eq!(stored.game_result, GameResult::Win(player_1, WinReason::Line));
+        let mut expected: Vec<(u8, u8)> = winning_moves.iter().map(|&(row, col)| (row as u8, col as u8)).collect();
+        expected.sort();
+        assert_eq!(stored.winning_line, Some(expected));
+    }
+
+    #[test]
+    fn test_board_rows_in_views() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, _) = start_near_game(&mut ctx, &mut ctr);
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 1, 3);
+        let piece = ctr.internal_get_game(&game_id).players[0].piece;
+
+        let view = ctr.get_active_games().into_iter().find(|(id, _)| *id == game_id).unwrap().1;
+        assert_eq!(view.size as usize, BOARD_SIZE);
+        assert_eq!(view.tiles.len(), BOARD_SIZE);
+        assert!(view.tiles.iter().all(|row| row.len() == BOARD_SIZE));
+        assert_eq!(view.tiles[1][3], Some(piece));
+        assert_eq!(view.tiles.iter().flatten().filter(|tile| tile.is_some()).count(), 1);
+
+        give_up(&mut ctx, &mut ctr, &player_1, &game_id);
+        let stored = ctr.get_game(&game_id);
+        assert_eq!(stored.size as usize, BOARD_SIZE);
+        assert_eq!(stored.board, view.tiles);
+    }
+
+    #[test]
+    fn test_timeout_before_deadline() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let budget = ctr.per_player_budget;
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        // one nanosecond before the deadline the move is accepted and stop isn't
+        testing_env!(ctx.predecessor_account_id(player_2.clone()).block_timestamp(budget - 1).build());
+        assert!(!ctr.internal_is_stoppable(&ctr.internal_get_game(&game_id), budget - 1));
+        testing_env!(ctx.predecessor_account_id(player_1.clone()).build());
+        ctr.make_move(&game_id, 0, 0);
+        let game = ctr.internal_get_game(&game_id);
+        assert_eq!(game.game_state, GameState::Active);
+        assert_eq!(game.time_remaining[0], 1);
+    }
+
+    #[test]
+    fn test_timeout_at_deadline() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let budget = ctr.per_player_budget;
+        // at the deadline the move loses, so does stop
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        testing_env!(ctx.predecessor_account_id(player_1.clone()).block_timestamp(budget).build());
+        assert!(ctr.internal_is_stoppable(&ctr.internal_get_game(&game_id), budget));
+        ctr.make_move(&game_id, 0, 0);
+        assert_eq!(ctr.get_game(&game_id).game_result, GameResult::Win(player_2, WinReason::Timeout));
+        assert_eq!(ctr.get_stats(&player_1).penalties_num, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "min turn interval need to be less than player time budget 750 seconds")]
+    fn test_min_turn_interval_above_budget() {
+        let (_ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_min_turn_interval(749);
+        ctr.set_min_turn_interval(750);
+    }
+
+    #[test]
+    fn test_game_duration_boundary() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, _, _) = start_near_game(&mut ctx, &mut ctr);
+        let max_duration = ctr.max_game_duration;
+        ctr.internal_ping_expired_games(max_duration - 1, None);
+        assert!(ctr.games.get(&game_id).is_some());
+        testing_env!(ctx.block_timestamp(max_duration).build());
+        ctr.internal_ping_expired_games(max_duration, None);
+        assert!(ctr.games.get(&game_id).is_none());
+    }
+
+    #[test]
+    fn test_move_after_game_duration() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        // clock isn't expired, only the game duration
+        let mut game = ctr.internal_get_game(&game_id);
+        game.time_remaining = [2 * ctr.max_game_duration; PLAYERS_NUM];
+        ctr.games.insert(&game_id, &game);
+
+        testing_env!(ctx.predecessor_account_id(player_1.clone()).block_timestamp(ctr.max_game_duration).build());
+        ctr.make_move(&game_id, 0, 0);
+        assert_eq!(ctr.get_game(&game_id).game_result, GameResult::Win(player_2, WinReason::Timeout));
+        assert_eq!(ctr.get_stats(&player_1).penalties_num, 1);
+        assert!(ctr.get_active_games().is_empty());
+    }
+
+    #[test]
+    fn test_timeout_grace() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_timeout_grace(5);
+        let grace = sec_to_nano(5);
+        let budget = ctr.per_player_budget;
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        testing_env!(ctx.predecessor_account_id(player_2.clone()).block_timestamp(budget + grace - 1).build());
+        assert!(!ctr.internal_is_stoppable(&ctr.internal_get_game(&game_id), budget + grace - 1));
+        testing_env!(ctx.predecessor_account_id(player_1.clone()).build());
+        ctr.make_move(&game_id, 0, 0);
+        // late move spends the whole budget, the next one loses at once
+        let game = ctr.internal_get_game(&game_id);
+        assert_eq!(game.game_state, GameState::Active);
+        assert_eq!(game.time_remaining[0], 0);
+        assert!(ctr.internal_is_stoppable(&game, budget + grace + budget + grace));
+        assert_eq!(ctr.get_config().timeout_grace_sec, Some(5));
+    }
+
+    #[test]
+    #[should_panic(expected = "Too early to stop the game")]
+    fn test_stop_game_before_deadline() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let budget = ctr.per_player_budget;
+        let (game_id, _, player_2) = start_near_game(&mut ctx, &mut ctr);
+        testing_env!(ctx.predecessor_account_id(player_2).block_timestamp(budget - 1).build());
+        ctr.stop_game(&game_id);
+    }
+
+    #[test]
+    fn test_stored_game_winner_stop_game() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        // first player never moves
+        let budget_sec = nano_to_sec(ctr.per_player_budget);
+        stop_game(&mut ctx, &mut ctr, &player_2, &game_id, budget_sec + 1);
+
+        let stored = ctr.get_game(&game_id);
+        assert_eq!(stored.game_result, GameResult::Win(player_2.clone(), WinReason::Timeout));
+        assert_eq!((stored.player1, stored.player2), (player_1.clone(), player_2.clone()));
+        assert_eq!((stored.winner, stored.loser), (Some(player_2), Some(player_1)));
+    }
+
+    #[test]
+    fn test_can_stop_game() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        let budget_sec = nano_to_sec(ctr.per_player_budget);
+        assert!(!ctr.can_stop_game(game_id, player_2.clone()));
+
+        testing_env!(ctx.block_timestamp(sec_to_nano(budget_sec + 1)).build());
+        // current player and outsider can't stop
+        assert!(!ctr.can_stop_game(game_id, player_1));
+        assert!(!ctr.can_stop_game(game_id, referrer()));
+        assert!(!ctr.can_stop_game(game_id + 1, player_2.clone()));
+        assert!(ctr.can_stop_game(game_id, player_2.clone()));
+
+        stop_game(&mut ctx, &mut ctr, &player_2, &game_id, budget_sec + 1);
+        assert!(!ctr.can_stop_game(game_id, player_2));
+    }
+
+    #[test]
+    fn test_first_move_grace_refund() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_first_move_grace_refund(true);
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        // first player never moves
+        let budget_sec = nano_to_sec(ctr.per_player_budget);
+        stop_game(&mut ctx, &mut ctr, &player_2, &game_id, budget_sec + 1);
+
+        assert_eq!(ctr.get_game(&game_id).game_result, GameResult::Cancelled);
+        let mut transfers = near_transfers();
+        transfers.sort();
+        let mut expected = vec![(player_1.clone(), ONE_NEAR), (player_2, ONE_NEAR)];
+        expected.sort();
+        assert_eq!(transfers, expected);
+        assert_eq!(ctr.get_stats(&player_1).penalties_num, 0);
+    }
+
+    #[test]
+    fn test_first_move_grace_refund_after_first_move() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_first_move_grace_refund(true);
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        ctx.block_timestamp(sec_to_nano(10));
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 0);
+        // second player never moves
+        let budget_sec = nano_to_sec(ctr.per_player_budget);
+        stop_game(&mut ctx, &mut ctr, &player_1, &game_id, budget_sec + 11);
+
+        assert_eq!(ctr.get_game(&game_id).game_result, GameResult::Win(player_1, WinReason::Timeout));
+        assert_eq!(ctr.get_stats(&player_2).penalties_num, 1);
+    }
+
+    #[test]
+    fn test_archive_and_reset_stats() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        give_up(&mut ctx, &mut ctr, &player_1, &game_id);
+        let stats = ctr.get_stats(&player_2);
+        assert_eq!(stats.victories_num, 1);
+
+        testing_env!(ctx.predecessor_account_id(ctx.context.current_account_id.clone()).build());
+        assert_eq!(ctr.archive_and_reset_stats(0, 100), 0);
+        assert_eq!(ctr.get_season(), 1);
+        let archived = ctr.get_stats_for_season(player_2.clone(), 0).unwrap();
+        assert_eq!((archived.games_played, archived.victories_num), (1, 1));
+        assert_eq!(archived.total_reward, stats.total_reward);
+
+        let live = ctr.get_stats_for_season(player_2.clone(), 1).unwrap();
+        assert_eq!((live.games_played, live.victories_num), (0, 0));
+        assert!(live.total_reward.is_empty() && live.total_wagered.is_empty());
+        assert_eq!(ctr.get_stats(&player_1).games_played, 0);
+        assert!(ctr.get_stats_for_season(referrer(), 0).is_none());
+    }
+
+    #[test]
+    fn test_archive_and_reset_stats_pages() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, _) = start_near_game(&mut ctx, &mut ctr);
+        give_up(&mut ctx, &mut ctr, &player_1, &game_id);
+        let third: AccountId = "third".parse().unwrap();
+        make_available_near(&mut ctx, &mut ctr, &third, ONE_NEAR, None, None);
+        make_available_near(&mut ctx, &mut ctr, &referrer(), ONE_NEAR, None, None);
+        let game_id = start_game(&mut ctx, &mut ctr, &third, &referrer());
+        give_up(&mut ctx, &mut ctr, &third, &game_id);
+        assert_eq!(ctr.stats.len(), 4);
+
+        testing_env!(ctx.predecessor_account_id(ctx.context.current_account_id.clone()).build());
+        assert_eq!(ctr.archive_and_reset_stats(0, 3), 3);
+        // season isn't over until the last page
+        assert_eq!(ctr.get_season(), 0);
+        assert_eq!(ctr.archive_and_reset_stats(3, 3), 0);
+        assert_eq!(ctr.get_season(), 1);
+        for account_id in [user(), opponent(), third, referrer()] {
+            assert_eq!(ctr.get_stats_for_season(account_id.clone(), 0).unwrap().games_played, 1);
+            assert_eq!(ctr.get_stats(&account_id).games_played, 0);
+        }
+        // the next season starts from the first page
+        assert_eq!(ctr.archive_and_reset_stats(0, 4), 0);
+        assert_eq!(ctr.get_season(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Reset of season 0 continues from 1")]
+    fn test_archive_and_reset_stats_repeated_page() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, _) = start_near_game(&mut ctx, &mut ctr);
+        give_up(&mut ctx, &mut ctr, &player_1, &game_id);
+        ctr.archive_and_reset_stats(0, 1);
+        ctr.archive_and_reset_stats(0, 1);
+    }
+
+    #[test]
+    fn test_penalty_ban() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_penalty_ban(600);
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        let budget_sec = nano_to_sec(ctr.per_player_budget);
+        stop_game(&mut ctx, &mut ctr, &player_2, &game_id, budget_sec + 1);
+
+        assert_eq!(ctr.is_banned(player_1.clone()), Some(sec_to_nano(600)));
+        assert_eq!(ctr.is_banned(player_2.clone()), None);
+        testing_env!(ctx.block_timestamp(sec_to_nano(budget_sec + 601)).build());
+        assert_eq!(ctr.is_banned(player_1.clone()), None);
+        make_available_near(&mut ctx, &mut ctr, &player_1, ONE_NEAR, None, None);
+    }
+
+    #[test]
+    fn test_penalty_severity_tiers() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_penalty_ban(600);
+        ctr.set_max_penalty_tier(2);
+        assert_eq!(ctr.get_config().max_penalty_tier, Some(2));
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        let budget_sec = nano_to_sec(ctr.per_player_budget);
+        stop_game(&mut ctx, &mut ctr, &player_2, &game_id, budget_sec + 1);
+        assert_eq!(ctr.is_banned(player_1.clone()), Some(sec_to_nano(600)));
+        assert_eq!(ctr.get_user_penalties(&player_1).severity_tier, 0);
+
+        // repeat penalties double the ban until the max tier
+        for (penalties_num, ban_sec) in [(2, 1200), (3, 2400), (4, 2400)] {
+            ctr.internal_update_stats(None, &player_1, UpdateStatsAction::AddPenaltyGame, None, None);
+            assert_eq!(ctr.is_banned(player_1.clone()), Some(sec_to_nano(ban_sec)));
+            let penalties = ctr.get_user_penalties(&player_1);
+            assert_eq!(penalties.penalties_num, penalties_num);
+            assert_eq!(penalties.severity_tier, 2.min(penalties_num - 1) as u8);
+        }
+        assert_eq!(ctr.get_user_penalties(&player_2).severity_tier, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "max penalty tier need to be less then 10")]
+    fn test_max_penalty_tier_too_big() {
+        let (_, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_max_penalty_tier(11);
+    }
+
+    #[test]
+    fn test_penalty_ban_capped() {
+        let (_, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_penalty_ban(24 * 60 * 60);
+        ctr.set_max_penalty_tier(10);
+        assert_eq!(ctr.internal_penalty_ban(3), sec_to_nano(4 * 24 * 60 * 60));
+        assert_eq!(ctr.internal_penalty_ban(4), sec_to_nano(MAX_PENALTY_BAN_SEC));
+    }
+
+    #[test]
+    #[should_panic(expected = "is banned for penalties")]
+    fn test_penalty_ban_make_available() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_penalty_ban(600);
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        let budget_sec = nano_to_sec(ctr.per_player_budget);
+        stop_game(&mut ctx, &mut ctr, &player_2, &game_id, budget_sec + 1);
+        make_available_near(&mut ctx, &mut ctr, &player_1, ONE_NEAR, None, None);
+    }
+
+    #[test]
+    fn test_move_delegate() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let agent: AccountId = "agent".parse().unwrap();
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        testing_env!(ctx.predecessor_account_id(player_1.clone()).attached_deposit(ONE_YOCTO).build());
+        ctr.set_move_delegate(game_id, agent.clone());
+
+        for col in 0..4 {
+            make_move(&mut ctx, &mut ctr, &agent, &game_id, 0, col);
+            make_move(&mut ctx, &mut ctr, &player_2, &game_id, 1, col);
+        }
+        // the player can still move too
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 2, 0);
+        make_move(&mut ctx, &mut ctr, &player_2, &game_id, 2, 1);
+        make_move(&mut ctx, &mut ctr, &agent, &game_id, 0, 4);
+
+        assert_eq!(ctr.get_game(&game_id).game_result, GameResult::Win(player_1.clone(), WinReason::Line));
+        assert_eq!(ctr.get_stats(&player_1).victories_num, 1);
+        assert_eq!(ctr.get_stats(&agent).games_played, 0);
+        assert!(ctr.move_delegates.get(&game_id).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "No access")]
+    fn test_move_delegate_on_opponent_turn() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let agent: AccountId = "agent".parse().unwrap();
+        let (game_id, player_1, _) = start_near_game(&mut ctx, &mut ctr);
+        testing_env!(ctx.predecessor_account_id(player_1.clone()).attached_deposit(ONE_YOCTO).build());
+        ctr.set_move_delegate(game_id, agent.clone());
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 0);
+        make_move(&mut ctx, &mut ctr, &agent, &game_id, 1, 1);
+    }
+
+    #[test]
+    fn test_notification_prefs() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        assert_eq!(ctr.get_notification_prefs(user()), NOTIFY_ALL);
+        testing_env!(ctx.predecessor_account_id(user()).build());
+        ctr.set_notification_prefs(NOTIFY_TURN_REMINDER | NOTIFY_GAME_END);
+        assert_eq!(ctr.get_notification_prefs(user()), 5);
+        assert_eq!(ctr.get_notification_prefs(opponent()), NOTIFY_ALL);
+        ctr.set_notification_prefs(0);
+        assert_eq!(ctr.get_notification_prefs(user()), 0);
+        ctr.set_notification_prefs(NOTIFY_ALL);
+        assert!(ctr.notification_prefs.get(&user()).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown notification flags. Max is 7")]
+    fn test_notification_prefs_unknown_flags() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        testing_env!(ctx.predecessor_account_id(user()).build());
+        ctr.set_notification_prefs(8);
+    }
+
+    fn start_practice_game(ctx: &mut VMContextBuilder, ctr: &mut Contract) -> (GameId, AccountId, AccountId) {
+        for account_id in [user(), opponent()] {
+            make_available_near_config(ctx, ctr, &account_id, 0, GameConfigNear { practice: Some(true), ..Default::default() });
+        }
+        let game_id = start_game(ctx, ctr, &user(), &opponent());
+        let (player_1, player_2) = ctr.internal_get_game_players(&game_id);
+        (game_id, player_1, player_2)
+    }
+
+    #[test]
+    fn test_practice_game() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, player_2) = start_practice_game(&mut ctx, &mut ctr);
+        let game = ctr.get_active_games().into_iter().find(|(id, _)| *id == game_id).unwrap().1;
+        assert!(game.is_practice);
+        assert_eq!(game.reward.balance, U128(0));
+
+        give_up(&mut ctx, &mut ctr, &player_1, &game_id);
+        assert!(near_transfers().is_empty());
+        let stored = ctr.get_game(&game_id);
+        assert!(stored.is_practice);
+        assert_eq!(stored.game_result, GameResult::Win(player_2.clone(), WinReason::GiveUp));
+        for account_id in [player_1, player_2] {
+            let stats = ctr.get_stats(&account_id);
+            assert_eq!((stats.games_played, stats.victories_num), (0, 0));
+        }
+        assert!(ctr.get_protocol_stats().total_volume_per_token.is_empty());
+        assert!(ctr.get_protocol_stats().total_fees_per_token.is_empty());
+    }
+
+    #[test]
+    fn test_practice_game_timeout() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_penalty_ban(600);
+        let (game_id, player_1, player_2) = start_practice_game(&mut ctx, &mut ctr);
+        let budget_sec = nano_to_sec(ctr.per_player_budget);
+        stop_game(&mut ctx, &mut ctr, &player_2, &game_id, budget_sec + 1);
+
+        assert_eq!(ctr.get_game(&game_id).game_result, GameResult::Win(player_2, WinReason::Timeout));
+        assert_eq!(ctr.get_stats(&player_1).penalties_num, 0);
+        assert_eq!(ctr.is_banned(player_1), None);
+        assert!(near_transfers().is_empty());
+    }
+
+    #[test]
+    fn test_practice_invite_matches_practice_only() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        make_available_near_config(&mut ctx, &mut ctr, &user(), 0, GameConfigNear { practice: Some(true), ..Default::default() });
+        make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, None, None);
+        assert!(ctr.get_available_players()[0].2.is_practice);
+        testing_env!(ctx.predecessor_account_id(user()).build());
+        assert!(matches!(
+            ctr.try_start_game(opponent(), None).unwrap().unwrap_err(),
+            StartGameError::MismatchedDeposits { .. }
+        ));
+    }
+
+    fn fill_practice_invites(ctx: &mut VMContextBuilder, ctr: &mut Contract) {
+        for account_id in [user(), opponent()] {
+            for _ in 0..MAX_INVITES_PER_PLAYER {
+                make_available_near_config(ctx, ctr, &account_id, 0, GameConfigNear { practice: Some(true), ..Default::default() });
+            }
+        }
+    }
+
+    #[test]
+    fn test_practice_invites_not_in_waiting_counts() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_max_waiting_per_token(2);
+        fill_practice_invites(&mut ctx, &mut ctr);
+        assert_eq!(ctr.practice_waiting, MAX_PRACTICE_WAITING);
+        assert!(ctr.get_waiting_counts().is_empty());
+
+        // NEAR lobby is still open for paid invites
+        make_available_near(&mut ctx, &mut ctr, &referrer(), ONE_NEAR, None, None);
+        assert_eq!(ctr.get_waiting_counts(), vec![(near_token_id(), 1)]);
+
+        make_unavailable(&mut ctx, &mut ctr, &user());
+        assert_eq!(ctr.practice_waiting, MAX_PRACTICE_WAITING / 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Too many practice games are waiting")]
+    fn test_practice_invites_cap() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        fill_practice_invites(&mut ctx, &mut ctr);
+        make_available_near_config(&mut ctx, &mut ctr, &referrer(), 0, GameConfigNear { practice: Some(true), ..Default::default() });
+    }
+
+    #[test]
+    #[should_panic(expected = "Practice game doesn't take deposit")]
+    fn test_practice_game_with_deposit() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        make_available_near_config(&mut ctx, &mut ctr, &user(), ONE_NEAR, GameConfigNear { practice: Some(true), ..Default::default() });
+    }
+
+    #[test]
+    fn test_find_compatible_opponents() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let third: AccountId = "third".parse().unwrap();
+        let fourth: AccountId = "fourth".parse().unwrap();
+        make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, None, None);
+        make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, None, None);
+        make_available_near(&mut ctx, &mut ctr, &third, ONE_NEAR, Some(user()), None);
+        make_available_near(&mut ctx, &mut ctr, &fourth, ONE_NEAR, Some(third.clone()), None);
+        make_available_near(&mut ctx, &mut ctr, &referrer(), 2 * ONE_NEAR, None, None);
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+
+        let find = |account_id: Option<AccountId>, limit| ctr.find_compatible_opponents(near_token_id(), U128(ONE_NEAR), limit, account_id);
+        assert_eq!(find(Some(user()), 10), vec![opponent(), third.clone()]);
+        assert_eq!(find(Some(user()), 1), vec![opponent()]);
+        assert_eq!(find(Some(third.clone()), 10), vec![opponent(), fourth, user()]);
+        assert_eq!(find(None, 10), vec![opponent(), user()]);
+        assert!(ctr.find_compatible_opponents(acc_cheddar(), U128(ONE_NEAR), 10, None).is_empty());
+
+        // busy players are skipped
+        make_available_near(&mut ctx, &mut ctr, &referrer(), ONE_NEAR, None, None);
+        testing_env!(ctx.predecessor_account_id(referrer()).attached_deposit(0).build());
+        ctr.start_game(opponent(), Some(0));
+        assert_eq!(ctr.find_compatible_opponents(near_token_id(), U128(ONE_NEAR), 10, Some(user())), vec![third]);
+    }
+
+    #[test]
+    fn test_accepted_tokens() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        whitelist_token(&mut ctr);
+        testing_env!(ctx.predecessor_account_id(user()).build());
+        ctr.set_accepted_tokens(Some(vec![acc_cheddar()]));
+        assert_eq!(ctr.get_accepted_tokens(user()), Some(vec![acc_cheddar()]));
+        assert_eq!(ctr.get_accepted_tokens(opponent()), None);
+
+        // FT deposit in accepted token works, opponent accepts any token
+        make_available_ft(&mut ctx, &mut ctr, &user(), ONE_CHEDDAR, "".to_string());
+        make_available_ft(&mut ctx, &mut ctr, &opponent(), ONE_CHEDDAR, "".to_string());
+        testing_env!(ctx.predecessor_account_id(opponent()).build());
+        ctr.set_accepted_tokens(Some(vec![near_token_id()]));
+        // opponent changed mind after the invite
+        assert!(ctr.find_compatible_opponents(acc_cheddar(), U128(ONE_CHEDDAR), 10, Some(user())).is_empty());
+        testing_env!(ctx.predecessor_account_id(user()).build());
+        assert_eq!(
+            ctr.try_start_game(opponent(), None).unwrap().unwrap_err(),
+            StartGameError::TokenNotAccepted { account_id: opponent(), token_id: acc_cheddar() }
+        );
+        testing_env!(ctx.predecessor_account_id(opponent()).build());
+        ctr.set_accepted_tokens(None);
+        start_game(&mut ctx, &mut ctr, &user(), &opponent());
+    }
+
+    #[test]
+    fn test_accepted_tokens_ft_refund() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        whitelist_token(&mut ctr);
+        testing_env!(ctx.predecessor_account_id(user()).build());
+        ctr.set_accepted_tokens(Some(vec![near_token_id()]));
+        testing_env!(ctx.predecessor_account_id(acc_cheddar()).build());
+        let refund = ctr.ft_on_transfer(user(), U128(ONE_CHEDDAR), "".to_string());
+        assert!(matches!(refund, PromiseOrValue::Value(U128(amount)) if amount == ONE_CHEDDAR));
+        assert!(ctr.get_available_players().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "@user doesn't accept games in near")]
+    fn test_accepted_tokens_near_invite() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        whitelist_token(&mut ctr);
+        testing_env!(ctx.predecessor_account_id(user()).build());
+        ctr.set_accepted_tokens(Some(vec![acc_cheddar()]));
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Token unknown is not whitelisted")]
+    fn test_accepted_tokens_not_whitelisted() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        testing_env!(ctx.predecessor_account_id(user()).build());
+        ctr.set_accepted_tokens(Some(vec!["unknown".parse().unwrap()]));
+    }
+
+    #[test]
+    fn test_rematch_cooldown() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_rematch_cooldown(30);
+        let third: AccountId = "third".parse().unwrap();
+        make_available_near(&mut ctx, &mut ctr, &third, ONE_NEAR, None, None);
+        let (game_id, _, _) = start_near_game(&mut ctx, &mut ctr);
+        ctx.block_timestamp(sec_to_nano(100));
+        give_up(&mut ctx, &mut ctr, &opponent(), &game_id);
+
+        for account_id in [user(), opponent()] {
+            assert_eq!(ctr.get_remaining_cooldown(account_id), Some(sec_to_nano(30)));
+        }
+        assert_eq!(ctr.get_remaining_cooldown(third.clone()), None);
+        testing_env!(ctx.predecessor_account_id(user()).attached_deposit(ONE_NEAR).build());
+        assert_eq!(
+            ctr.try_start_game(third.clone(), None).unwrap(),
+            Err(StartGameError::Cooldown { account_id: user(), remaining_sec: 30 })
+        );
+
+        testing_env!(ctx.block_timestamp(sec_to_nano(130)).build());
+        assert_eq!(ctr.get_remaining_cooldown(user()), None);
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+        start_game(&mut ctx, &mut ctr, &user(), &third);
+    }
+
+    #[test]
+    #[should_panic(expected = "has just finished a game. 30 sec of cooldown left")]
+    fn test_rematch_cooldown_make_available() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_rematch_cooldown(30);
+        let (game_id, _, _) = start_near_game(&mut ctx, &mut ctr);
+        give_up(&mut ctx, &mut ctr, &opponent(), &game_id);
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+    }
+
+    #[test]
+    fn test_first_threat_bonus() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_first_threat_bonus(1000);
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        for col in 0..3 {
+            make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, col);
+            make_move(&mut ctx, &mut ctr, &player_2, &game_id, 1, col);
+        }
+        assert_eq!(ctr.internal_get_game(&game_id).first_threat_by, None);
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 3);
+        // player_2 threat is the second one
+        make_move(&mut ctx, &mut ctr, &player_2, &game_id, 1, 3);
+        assert_eq!(ctr.internal_get_game(&game_id).first_threat_by, Some(player_1.clone()));
+
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 4);
+        let (fees, reward) = split_pot(2 * ONE_NEAR, MIN_FEES, 1);
+        let bonus = fees / BASIS_P as u128 * 1000;
+        let mut transfers = near_transfers();
+        transfers.sort();
+        let mut expected = vec![(player_1.clone(), reward), (player_1, bonus)];
+        expected.sort();
+        assert_eq!(transfers, expected);
+    }
+
+    #[test]
+    fn test_give_up_out_of_turn() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (_, reward) = split_pot(2 * ONE_NEAR, MIN_FEES, 1);
+        for current_gives_up in [true, false] {
+            let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+            make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 0);
+            // player_2 moves now
+            let (loser, winner) = if current_gives_up { (player_2, player_1) } else { (player_1, player_2) };
+            give_up(&mut ctx, &mut ctr, &loser, &game_id);
+
+            let stored = ctr.get_game(&game_id);
+            assert_eq!(stored.game_result, GameResult::Win(winner.clone(), WinReason::GiveUp));
+            assert_eq!((stored.winner, stored.loser), (Some(winner.clone()), Some(loser)));
+            assert_eq!(near_transfers(), vec![(winner, reward)]);
+        }
+    }
+
+    #[test]
+    fn test_turn_info() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        let piece_2 = ctr.internal_get_game(&game_id).players[1].piece;
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 0);
+        assert_eq!(ctr.get_turn_info(game_id), Some(TurnInfo {
+            total_turns: 1,
+            current_player: player_2,
+            current_piece: piece_2,
+            next_player: player_1,
+        }));
+        assert_eq!(ctr.get_turn_info(game_id + 1), None);
+    }
+
+    #[test]
+    fn test_series() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+        make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, None, None);
+        testing_env!(ctx.predecessor_account_id(user()).build());
+        let series_id = ctr.start_series(opponent(), None, 3);
+        let first_game_id = ctr.get_series(series_id).unwrap().current_game_id;
+        let first_game = ctr.internal_get_game(&first_game_id);
+
+        // the pot is carried, nothing is paid
+        give_up(&mut ctx, &mut ctr, &opponent(), &first_game_id);
+        assert!(near_transfers().is_empty());
+        let series = ctr.get_series(series_id).unwrap();
+        assert_eq!(series.wins, [1, 0]);
+        assert_eq!(series.games_played, 1);
+        let second_game = ctr.internal_get_game(&series.current_game_id);
+        assert_eq!(second_game.reward.balance, U128(2 * ONE_NEAR));
+        assert_eq!(second_game.players[0].account_id, first_game.players[1].account_id);
+        assert_eq!(ctr.get_game_series(series.current_game_id), Some(series_id));
+
+        give_up(&mut ctx, &mut ctr, &opponent(), &series.current_game_id);
+        let (_, reward) = split_pot(2 * ONE_NEAR, MIN_FEES, 1);
+        assert_eq!(near_transfers(), vec![(user(), reward)]);
+        assert!(ctr.get_series(series_id).is_none());
+        assert!(ctr.get_active_games().is_empty());
+        assert_eq!(ctr.get_stats(&user()).victories_num, 1);
+    }
+
+    fn start_near_series(ctx: &mut VMContextBuilder, ctr: &mut Contract) -> (SeriesId, GameId) {
+        make_available_near(ctx, ctr, &user(), ONE_NEAR, None, None);
+        make_available_near(ctx, ctr, &opponent(), ONE_NEAR, None, None);
+        testing_env!(ctx.predecessor_account_id(user()).build());
+        let series_id = ctr.start_series(opponent(), None, 3);
+        (series_id, ctr.get_series(series_id).unwrap().current_game_id)
+    }
+
+    #[test]
+    fn test_series_cancelled_game_refund() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_first_move_grace_refund(true);
+        let (series_id, game_id) = start_near_series(&mut ctx, &mut ctr);
+        let (_, player_2) = ctr.internal_get_game_players(&game_id);
+        let budget_sec = nano_to_sec(ctr.per_player_budget);
+        stop_game(&mut ctx, &mut ctr, &player_2, &game_id, budget_sec + 1);
+
+        assert_eq!(ctr.get_game(&game_id).game_result, GameResult::Cancelled);
+        let mut transfers = near_transfers();
+        transfers.sort();
+        assert_eq!(transfers, vec![(opponent(), ONE_NEAR), (user(), ONE_NEAR)]);
+        assert!(ctr.get_series(series_id).is_none());
+        assert!(ctr.get_active_games().is_empty());
+    }
+
+    #[test]
+    fn test_series_forfeit_token_not_accepted() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        whitelist_token(&mut ctr);
+        let (series_id, game_id) = start_near_series(&mut ctx, &mut ctr);
+        // losing player drops the series token to end it
+        testing_env!(ctx.predecessor_account_id(opponent()).build());
+        ctr.set_accepted_tokens(Some(vec![acc_cheddar()]));
+        give_up(&mut ctx, &mut ctr, &opponent(), &game_id);
+
+        let (fees, reward) = split_pot(2 * ONE_NEAR, MIN_FEES, 1);
+        assert_eq!(near_transfers(), vec![(user(), reward)]);
+        assert_eq!(ctr.get_protocol_stats().total_fees_per_token, vec![(near_token_id(), U128(fees))]);
+        assert!(ctr.get_series(series_id).is_none());
+        assert!(ctr.get_active_games().is_empty());
+        assert_eq!(ctr.get_stats(&user()).victories_num, 1);
+    }
+
+    #[test]
+    fn test_series_cooldown_after_series() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_rematch_cooldown(30);
+        let (series_id, game_id) = start_near_series(&mut ctx, &mut ctr);
+        give_up(&mut ctx, &mut ctr, &opponent(), &game_id);
+        let series = ctr.get_series(series_id).unwrap();
+        assert_eq!(ctr.get_remaining_cooldown(user()), None);
+
+        give_up(&mut ctx, &mut ctr, &opponent(), &series.current_game_id);
+        assert!(ctr.get_series(series_id).is_none());
+        assert_eq!(ctr.get_remaining_cooldown(user()), Some(sec_to_nano(30)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Series must be best of odd number of games in range 3..7")]
+    fn test_series_even_best_of() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+        make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, None, None);
+        testing_env!(ctx.predecessor_account_id(user()).build());
+        ctr.start_series(opponent(), None, 4);
+    }
+
+    #[test]
+    fn test_try_start_game() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let try_start = |ctx: &mut VMContextBuilder, ctr: &mut Contract| {
+            testing_env!(ctx.predecessor_account_id(user()).build());
+            ctr.try_start_game(opponent(), None).unwrap()
+        };
+        assert_eq!(try_start(&mut ctx, &mut ctr), Err(StartGameError::OpponentNotReady));
+
+        make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, None, None);
+        assert_eq!(try_start(&mut ctx, &mut ctr), Err(StartGameError::NotAvailable));
+
+        make_available_near(&mut ctx, &mut ctr, &user(), 2 * ONE_NEAR, None, None);
+        assert_eq!(
+            try_start(&mut ctx, &mut ctr),
+            Err(StartGameError::MismatchedDeposits { you: U128(2 * ONE_NEAR), opponent: U128(ONE_NEAR) })
+        );
+        // invites are kept
+        assert_eq!(ctr.get_available_players().len(), 2);
+
+        make_unavailable(&mut ctx, &mut ctr, &user());
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+        let game_id = try_start(&mut ctx, &mut ctr).unwrap();
+        assert_eq!(ctr.get_active_games()[0].0, game_id);
+        assert!(ctr.get_available_players().is_empty());
+    }
+
+    #[test]
+    fn test_referrer_ineligible_token() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (fees, reward) = split_pot(2 * ONE_NEAR, MIN_FEES, 1);
+        let referrer_fee = fees / BASIS_P as u128 * ctr.referrer_ratio as u128;
+        // referrer is added only if it has stats
+        ctr.internal_update_stats(None, &referrer(), UpdateStatsAction::AddPlayedGame, None, None);
+        for referrer_eligible in [true, false] {
+            ctr.set_referrer_eligible(near_token_id(), referrer_eligible);
+            make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, Some(referrer()));
+            make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, None, Some(referrer()));
+            let game_id = start_game(&mut ctx, &mut ctr, &user(), &opponent());
+            give_up(&mut ctx, &mut ctr, &opponent(), &game_id);
+
+            let mut transfers = near_transfers();
+            transfers.sort();
+            let mut expected = vec![(user(), reward)];
+            if referrer_eligible {
+                expected.push((referrer(), referrer_fee));
+            }
+            expected.sort();
+            assert_eq!(transfers, expected);
+        }
+        // the referrer was paid only once, protocol kept the slice of the second game
+        assert_eq!(ctr.get_stats(&referrer()).total_affiliate_reward, vec![(near_token_id(), referrer_fee)]);
+    }
+
+    #[test]
+    fn test_min_referrer_games() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_min_referrer_games(5);
+        // fresh referrer with a single game
+        ctr.internal_update_stats(None, &referrer(), UpdateStatsAction::AddPlayedGame, None, None);
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, Some(referrer()));
+        make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, None, None);
+        let game_id = start_game(&mut ctx, &mut ctr, &user(), &opponent());
+        assert_eq!(ctr.get_stats(&user()).referrer_id, Some(referrer()));
+        give_up(&mut ctx, &mut ctr, &opponent(), &game_id);
+
+        let (_, reward) = split_pot(2 * ONE_NEAR, MIN_FEES, 1);
+        assert_eq!(near_transfers(), vec![(user(), reward)]);
+        assert!(ctr.get_stats(&referrer()).total_affiliate_reward.is_empty());
+    }
+
+    #[test]
+    fn test_lobby_events() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let has_event = |event: &str| near_sdk::test_utils::get_logs()
+            .iter()
+            .any(|log| log.starts_with("EVENT_JSON:") && log.contains(&format!("\"event\":\"{}\"", event)));
+        let update_seq = ctr.get_update_seq();
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, Some(opponent()), None);
+        assert!(has_event("player_available"));
+        assert!(ctr.get_update_seq() > update_seq);
+        let invite = ctr.get_invite(user(), 0).unwrap();
+        assert_eq!(invite.opponent_id, Some(opponent()));
+        assert_eq!(invite.deposit, U128(ONE_NEAR));
+
+        make_unavailable(&mut ctx, &mut ctr, &user());
+        assert!(has_event("player_unavailable"));
+        assert!(ctr.get_invite(user(), 0).is_none());
+
+        // taken invites leave the lobby too
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+        make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, None, None);
+        start_game(&mut ctx, &mut ctr, &user(), &opponent());
+        let unavailable = near_sdk::test_utils::get_logs()
+            .iter()
+            .filter(|log| log.contains("\"event\":\"player_unavailable\""))
+            .count();
+        assert_eq!(unavailable, 2);
+    }
+
+    #[test]
+    fn test_handicap() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let rules = GameRules { handicap: vec![(0, 0, Piece::X), (0, 1, Piece::X), (4, 4, Piece::O)], ..Default::default() };
+        for account_id in [user(), opponent()] {
+            let game_config = GameConfigNear { rules: Some(rules.clone()), ..Default::default() };
+            make_available_near_config(&mut ctx, &mut ctr, &account_id, ONE_NEAR, game_config);
+        }
+        let game_id = start_game(&mut ctx, &mut ctr, &user(), &opponent());
+        let game = ctr.internal_get_game(&game_id);
+        // X has more pre-placed pieces, so O moves first
+        assert_eq!(game.current_piece, Piece::O);
+        assert_eq!(game.players[0].piece, Piece::O);
+        assert_eq!(game.total_turns, 0);
+        assert_eq!(game.board.to_compact()[0], "xx...");
+        assert_eq!(game.board.to_compact()[4], "....o");
+
+        // X completes the first row with pre-placed pieces only by moves
+        let (player_o, player_x) = (game.players[0].account_id.clone(), game.players[1].account_id.clone());
+        for col in 2..5 {
+            make_move(&mut ctx, &mut ctr, &player_o, &game_id, 1, col);
+            assert!(ctr.internal_get_game(&game_id).board.winner.is_none());
+            make_move(&mut ctx, &mut ctr, &player_x, &game_id, 0, col);
+        }
+        assert_eq!(ctr.get_game_winner(&game_id), Some(GameResult::Win(player_x, WinReason::Line)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Handicap tiles overlap at (0, 0)")]
+    fn test_handicap_overlap() {
+        GameRules { handicap: vec![(0, 0, Piece::X), (0, 0, Piece::O)], ..Default::default() }.assert_valid();
+    }
+
+    #[test]
+    #[should_panic(expected = "Handicap piece at (0, 0) must lie on the bottom row or another piece")]
+    fn test_handicap_gravity() {
+        GameRules { gravity: true, handicap: vec![(0, 0, Piece::X)], ..Default::default() }.assert_valid();
+    }
+
+    #[test]
+    fn test_consolation_payout() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_consolation_ratio(1000);
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        for col in 0..4 {
+            make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, col);
+            make_move(&mut ctx, &mut ctr, &player_2, &game_id, 1, col);
+        }
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 4);
+
+        let (_, reward) = split_pot(2 * ONE_NEAR, MIN_FEES, 1);
+        let consolation = 2 * ONE_NEAR / BASIS_P as u128 * 1000;
+        assert_eq!(ctr.get_stats(&player_1).total_reward, vec![(near_token_id(), reward - consolation)]);
+        assert_eq!(ctr.get_stats(&player_2).total_reward, vec![(near_token_id(), consolation)]);
+        let mut transfers = near_transfers();
+        transfers.sort();
+        let mut expected = vec![(player_1, reward - consolation), (player_2, consolation)];
+        expected.sort();
+        assert_eq!(transfers, expected);
+    }
+
+    #[test]
+    fn test_preview_payout() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_consolation_ratio(1000);
+        let preview = ctr.preview_payout(near_token_id(), U128(ONE_NEAR), None, None, None);
+        assert_eq!(preview.pot, U128(2 * ONE_NEAR));
+        assert_eq!(
+            preview.winner_amount.0 + preview.loser_amount.0 + preview.protocol_fee.0 + preview.referrer_fee.0,
+            2 * ONE_NEAR
+        );
+        assert!(preview.referrer_fee.0 > 0);
+
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        for col in 0..4 {
+            make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, col);
+            make_move(&mut ctx, &mut ctr, &player_2, &game_id, 1, col);
+        }
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 4);
+        let mut transfers = near_transfers();
+        transfers.sort();
+        let mut expected = vec![(player_1, preview.winner_amount.0), (player_2, preview.loser_amount.0)];
+        expected.sort();
+        assert_eq!(transfers, expected);
+
+        ctr.set_referrer_eligible(near_token_id(), false);
+        let preview = ctr.preview_payout(near_token_id(), U128(ONE_NEAR), None, None, None);
+        assert_eq!(preview.referrer_fee, U128(0));
+    }
+
+    #[test]
+    fn test_preview_payout_with_rakeback_and_first_threat_bonus() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_rakeback_ratio(1000);
+        ctr.set_first_threat_bonus(1000);
+        ctr.set_fee_schedule(vec![FeeBucket { max_turns: 20, service_fee_percentage: 2 * MIN_FEES }]);
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        let preview = ctr.preview_payout(near_token_id(), U128(ONE_NEAR), Some(9), Some(player_1.clone()), Some(true));
+        assert!(preview.rakeback_each.0 > 0 && preview.first_threat_bonus.0 > 0);
+        assert_eq!(
+            preview.winner_amount.0 + preview.protocol_fee.0 + preview.referrer_fee.0
+                + 2 * preview.rakeback_each.0 + preview.first_threat_bonus.0,
+            2 * ONE_NEAR
+        );
+        // flat fee is smaller than the scheduled one
+        let flat = ctr.preview_payout(near_token_id(), U128(ONE_NEAR), None, Some(player_1.clone()), Some(true));
+        assert!(flat.winner_amount.0 > preview.winner_amount.0);
+
+        for col in 0..4 {
+            make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, col);
+            make_move(&mut ctx, &mut ctr, &player_2, &game_id, 1, col);
+        }
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 4);
+        let mut transfers = near_transfers();
+        transfers.sort();
+        let mut expected = vec![
+            (player_1.clone(), preview.winner_amount.0),
+            (player_1.clone(), preview.first_threat_bonus.0)
+        ];
+        expected.sort();
+        assert_eq!(transfers, expected);
+        for account_id in [player_1, player_2] {
+            assert_eq!(ctr.get_claimable_rakeback(account_id, near_token_id()), preview.rakeback_each);
+        }
+    }
+
+    #[test]
+    fn test_board_has_threat_at() {
+        let mut board = Board { tiles: Default::default(), current_piece: Piece::X, winner: None };
+        for index in 0..BOARD_SIZE - 1 {
+            board.tiles[index][index] = Some(Piece::X);
+        }
+        assert!(board.has_threat_at(3, 3, WinRule::Standard));
+        assert!(!board.has_threat_at(3, 3, WinRule::NoDiagonals));
+        assert!(!board.has_threat_at(4, 4, WinRule::Standard));
+        // blocked line isn't a threat
+        board.tiles[4][4] = Some(Piece::O);
+        assert!(!board.has_threat_at(3, 3, WinRule::Standard));
+    }
+
+    #[test]
+    fn test_board_find_threats() {
+        let mut board = Board { tiles: Default::default(), current_piece: Piece::X, winner: None };
+        assert!(board.find_threats(Piece::X, WinRule::Standard).is_empty());
+        for index in 0..BOARD_SIZE - 1 {
+            board.tiles[index][index] = Some(Piece::X);
+            board.tiles[index][4] = Some(Piece::O);
+        }
+        assert_eq!(board.find_threats(Piece::X, WinRule::Standard), vec![(4, 4)]);
+        assert!(board.find_threats(Piece::X, WinRule::NoDiagonals).is_empty());
+        assert_eq!(board.find_threats(Piece::O, WinRule::Standard), vec![(4, 4)]);
+        assert!(board.find_threats(Piece::O, WinRule::DiagonalsOnly).is_empty());
+        board.tiles[4][4] = Some(Piece::O);
+        assert!(board.find_threats(Piece::X, WinRule::Standard).is_empty());
+    }
+
+    #[test]
+    fn test_get_threats() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        for col in 0..BOARD_SIZE - 1 {
+            make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, col);
+            make_move(&mut ctx, &mut ctr, &player_2, &game_id, 2, col);
+        }
+        let threats = ctr.get_threats(game_id).unwrap();
+        let (player_1_threats, player_2_threats) = match ctr.internal_get_game(&game_id).players[0].piece {
+            Piece::X => (threats.x_threats, threats.o_threats),
+            Piece::O => (threats.o_threats, threats.x_threats),
+        };
+        assert_eq!(player_1_threats, vec![(0, 4)]);
+        assert_eq!(player_2_threats, vec![(2, 4)]);
+        assert!(ctr.get_threats(game_id + 1).is_none());
+    }
+
+    #[test]
+    fn test_transfer_seat() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 0);
+        testing_env!(ctx
+            .predecessor_account_id(player_1.clone())
+            .attached_deposit(ONE_YOCTO)
+            .build());
+        ctr.transfer_seat(game_id, referrer());
+        let game = ctr.internal_get_game(&game_id);
+        assert_eq!((&game.players[0].account_id, &game.players[1].account_id), (&referrer(), &player_2));
+
+        make_move(&mut ctx, &mut ctr, &player_2, &game_id, 1, 0);
+        for col in 1..5 {
+            make_move(&mut ctx, &mut ctr, &referrer(), &game_id, 0, col);
+            if col < 4 {
+                make_move(&mut ctx, &mut ctr, &player_2, &game_id, 1, col);
+            }
+        }
+        assert_eq!(ctr.get_game(&game_id).game_result, GameResult::Win(referrer(), WinReason::Line));
+        assert_eq!(ctr.get_stats(&referrer()).victories_num, 1);
+        assert_eq!(ctr.get_stats(&player_1).victories_num, 0);
+    }
+
+    #[test]
+    fn test_transfer_seat_first_threat() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        for col in 0..3 {
+            make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, col);
+            make_move(&mut ctx, &mut ctr, &player_2, &game_id, 1, col);
+        }
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 3);
+        assert_eq!(ctr.internal_get_game(&game_id).first_threat_by, Some(player_1.clone()));
+        make_move(&mut ctx, &mut ctr, &player_2, &game_id, 3, 0);
+        testing_env!(ctx
+            .predecessor_account_id(player_1)
+            .attached_deposit(ONE_YOCTO)
+            .build());
+        ctr.transfer_seat(game_id, referrer());
+        assert_eq!(ctr.internal_get_game(&game_id).first_threat_by, Some(referrer()));
+    }
+
+    #[test]
+    #[should_panic(expected = "@referrer.near doesn't accept games in near")]
+    fn test_transfer_seat_token_not_accepted() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        whitelist_token(&mut ctr);
+        testing_env!(ctx.predecessor_account_id(referrer()).build());
+        ctr.set_accepted_tokens(Some(vec![acc_cheddar()]));
+        let (game_id, player_1, _) = start_near_game(&mut ctx, &mut ctr);
+        testing_env!(ctx
+            .predecessor_account_id(player_1)
+            .attached_deposit(ONE_YOCTO)
+            .build());
+        ctr.transfer_seat(game_id, referrer());
+    }
+
+    #[test]
+    #[should_panic(expected = "@referrer.near has just finished a game. 30 sec of cooldown left")]
+    fn test_transfer_seat_on_cooldown() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_rematch_cooldown(30);
+        make_available_near(&mut ctx, &mut ctr, &referrer(), ONE_NEAR, None, None);
+        make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, None, None);
+        let first_game_id = start_game(&mut ctx, &mut ctr, &referrer(), &opponent());
+        give_up(&mut ctx, &mut ctr, &opponent(), &first_game_id);
+
+        let third: AccountId = "third".parse().unwrap();
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+        make_available_near(&mut ctx, &mut ctr, &third, ONE_NEAR, None, None);
+        let game_id = start_game(&mut ctx, &mut ctr, &user(), &third);
+        testing_env!(ctx
+            .predecessor_account_id(user())
+            .attached_deposit(ONE_YOCTO)
+            .build());
+        ctr.transfer_seat(game_id, referrer());
+    }
+
+    #[test]
+    #[should_panic(expected = "is already in this game")]
+    fn test_transfer_seat_to_opponent() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        testing_env!(ctx
+            .predecessor_account_id(player_1)
+            .attached_deposit(ONE_YOCTO)
+            .build());
+        ctr.transfer_seat(game_id, player_2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Self referral is not allowed")]
+    fn test_self_referral() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, Some(user()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Referrer can't be the opponent")]
+    fn test_referrer_is_opponent() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, Some(opponent()), Some(opponent()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Referrer can't be the opponent")]
+    fn test_referrer_is_opponent_ft() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        whitelist_token(&mut ctr);
+        let gc = GameConfigArgs { opponent_id: Some(opponent()), referrer_id: Some(opponent()), ..Default::default() };
+        let msg = near_sdk::serde_json::to_string(&gc).expect("err serialize");
+        make_available_ft(&mut ctx, &mut ctr, &user(), ONE_CHEDDAR, msg);
+    }
+
+    #[test]
+    #[should_panic(expected = "Referrer can't be the opponent")]
+    fn test_referrer_is_opponent_on_start() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        // open invite, opponent isn't known yet
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, Some(opponent()));
+        make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, None, None);
+        start_game(&mut ctx, &mut ctr, &opponent(), &user());
+    }
+
+    #[test]
+    #[should_panic(expected = "Referrer can't be the opponent")]
+    fn test_referrer_from_stats_is_opponent() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        // opponent has stats, so it becomes user's referrer in user's first game
+        for (account_id, referrer_id) in [(opponent(), None), (user(), Some(opponent()))] {
+            make_available_near(&mut ctx, &mut ctr, &account_id, ONE_NEAR, None, referrer_id);
+            make_available_near(&mut ctx, &mut ctr, &referrer(), ONE_NEAR, None, None);
+            let game_id = start_game(&mut ctx, &mut ctr, &referrer(), &account_id);
+            give_up(&mut ctx, &mut ctr, &referrer(), &game_id);
+        }
+        assert_eq!(ctr.get_stats(&user()).referrer_id, Some(opponent()));
+
+        ctr.set_block_referrer_as_opponent(true);
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+        make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, None, None);
+        start_game(&mut ctx, &mut ctr, &opponent(), &user());
+    }
+
+    fn extend_turn(ctx: &mut VMContextBuilder, ctr: &mut Contract, user: &AccountId, game_id: GameId, timestamp: u64) {
+        testing_env!(ctx
+            .predecessor_account_id(user.clone())
+            .attached_deposit(ONE_YOCTO)
+            .block_timestamp(timestamp)
+            .build());
+        ctr.extend_turn(game_id);
+    }
+
+    #[test]
+    fn test_extend_turn() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_turn_extension(60, U128(ONE_YOCTO));
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        let budget_sec = nano_to_sec(ctr.per_player_budget);
+        extend_turn(&mut ctx, &mut ctr, &player_1, game_id, sec_to_nano(budget_sec - 10));
+        assert!(!ctr.can_stop_game(game_id, player_2.clone()));
+        testing_env!(ctx.block_timestamp(sec_to_nano(budget_sec + 61)).build());
+        assert!(ctr.can_stop_game(game_id, player_2.clone()));
+        assert_eq!(ctr.get_protocol_stats().total_fees_per_token, vec![(near_token_id(), U128(ONE_YOCTO))]);
+
+        ctx.block_timestamp(sec_to_nano(budget_sec + 50));
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 0);
+        assert_eq!(ctr.internal_get_game(&game_id).time_remaining[0], sec_to_nano(10));
+    }
+
+    #[test]
+    #[should_panic(expected = "Turn extension is already used")]
+    fn test_extend_turn_twice() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_turn_extension(60, U128(ONE_YOCTO));
+        let (game_id, player_1, _) = start_near_game(&mut ctx, &mut ctr);
+        extend_turn(&mut ctx, &mut ctr, &player_1, game_id, 0);
+        extend_turn(&mut ctx, &mut ctr, &player_1, game_id, 0);
+    }
+
+    fn post_message(ctx: &mut VMContextBuilder, ctr: &mut Contract, user: &AccountId, game_id: GameId, text: &str) {
+        testing_env!(ctx.predecessor_account_id(user.clone()).build());
+        ctr.post_message(game_id, text.to_string());
+    }
+
+    #[test]
+    fn test_game_chat() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        post_message(&mut ctx, &mut ctr, &player_1, game_id, "gl hf");
+        post_message(&mut ctx, &mut ctr, &player_2, game_id, "u2");
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 0);
+        post_message(&mut ctx, &mut ctr, &player_1, game_id, "next turn");
+        assert_eq!(ctr.get_game_chat(game_id), vec![
+            ChatMessage { account_id: player_1.clone(), text: "gl hf".to_string(), turn: 0 },
+            ChatMessage { account_id: player_2.clone(), text: "u2".to_string(), turn: 0 },
+            ChatMessage { account_id: player_1.clone(), text: "next turn".to_string(), turn: 1 },
+        ]);
+
+        give_up(&mut ctx, &mut ctr, &player_2, &game_id);
+        assert!(ctr.get_game_chat(game_id).is_empty());
+    }
+
+    #[test]
+    fn test_finished_game_storage_cleanup() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_max_stored_games(1);
+        let agent: AccountId = "agent".parse().unwrap();
+        let mut storage_usage = vec![];
+        for _ in 0..3 {
+            let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+            testing_env!(ctx.predecessor_account_id(referrer()).build());
+            ctr.register_viewer(game_id);
+            post_message(&mut ctx, &mut ctr, &player_1, game_id, "gl hf");
+            testing_env!(ctx.predecessor_account_id(player_2.clone()).attached_deposit(ONE_YOCTO).build());
+            ctr.set_move_delegate(game_id, agent.clone());
+            give_up(&mut ctx, &mut ctr, &opponent(), &game_id);
+            assert!(ctr.games.is_empty());
+            storage_usage.push(env::storage_usage());
+        }
+        // the first game adds stats of players, the next ones replace the stored result only
+        assert_eq!(storage_usage[1], storage_usage[2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "One message per turn")]
+    fn test_game_chat_rate_limit() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, _) = start_near_game(&mut ctx, &mut ctr);
+        post_message(&mut ctx, &mut ctr, &player_1, game_id, "hi");
+        post_message(&mut ctx, &mut ctr, &player_1, game_id, "hi again");
+    }
+
+    #[test]
+    #[should_panic(expected = "You are not in this game")]
+    fn test_game_chat_outsider() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, _, _) = start_near_game(&mut ctx, &mut ctr);
+        post_message(&mut ctx, &mut ctr, &referrer(), game_id, "hi");
+    }
+
+    #[test]
+    fn test_validate_text() {
+        let max_len = "a".repeat(MAX_CHAT_MESSAGE_LEN);
+        assert_eq!(validate_text(&max_len, MAX_CHAT_MESSAGE_LEN), Ok(()));
+        assert_eq!(validate_text(&format!("{}a", max_len), MAX_CHAT_MESSAGE_LEN), Err(TextError::TooLong { max_len: MAX_CHAT_MESSAGE_LEN }));
+        // length is in chars, not bytes
+        assert_eq!(validate_text(&"ё".repeat(MAX_CHAT_MESSAGE_LEN), MAX_CHAT_MESSAGE_LEN), Ok(()));
+        assert_eq!(validate_text("", MAX_CHAT_MESSAGE_LEN), Ok(()));
+        for text in ["line\nbreak", "tab\t", "nul\0", "esc\u{1b}[31m", "del\u{7f}", "c1\u{85}"] {
+            assert_eq!(validate_text(text, MAX_CHAT_MESSAGE_LEN), Err(TextError::ControlChars));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Text contains control characters")]
+    fn test_game_chat_control_chars() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, _) = start_near_game(&mut ctx, &mut ctr);
+        post_message(&mut ctx, &mut ctr, &player_1, game_id, "gl\nhf");
+    }
+
+    #[test]
+    fn test_board_score_position() {
+        let mut board = Board { tiles: Default::default(), current_piece: Piece::X, winner: None };
+        assert_eq!(board.score_position(WinRule::Standard), (0, 0));
+        board.tiles[0][0] = Some(Piece::X);
+        board.tiles[0][1] = Some(Piece::O);
+        board.tiles[2][2] = Some(Piece::O);
+        // x: column 0. o: column 1, row 2, column 2 and anti-diagonal. Row 0 and main diagonal are shared
+        assert_eq!(board.score_position(WinRule::Standard), (1, 4));
+        assert_eq!(board.score_position(WinRule::NoDiagonals), (1, 3));
+        assert_eq!(board.score_position(WinRule::DiagonalsOnly), (0, 1));
+    }
+
+    #[test]
+    fn test_timeout_tie_breaker() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_tie_breaker_min_turns(Some(2));
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        // x: column 0 and diagonal, o: column 1
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 0);
+        make_move(&mut ctx, &mut ctr, &player_2, &game_id, 0, 1);
+        // player_1 stalls but has more open lines
+        let budget_sec = nano_to_sec(ctr.per_player_budget);
+        stop_game(&mut ctx, &mut ctr, &player_2, &game_id, budget_sec + 1);
+        assert_eq!(ctr.get_game(&game_id).game_result, GameResult::Win(player_1, WinReason::TieBreaker));
+    }
+
+    #[test]
+    fn test_timeout_tie_breaker_early_game() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_tie_breaker_min_turns(Some(3));
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 0);
+        make_move(&mut ctx, &mut ctr, &player_2, &game_id, 0, 1);
+        let budget_sec = nano_to_sec(ctr.per_player_budget);
+        stop_game(&mut ctx, &mut ctr, &player_2, &game_id, budget_sec + 1);
+        assert_eq!(ctr.get_game(&game_id).game_result, GameResult::Win(player_2, WinReason::Timeout));
+    }
+
+    #[test]
+    fn test_expired_turn_penalty() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+
+        ctx.block_timestamp(sec_to_nano(10));
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 0);
+        // player_2 stalls for his whole budget and reports the move himself
+        ctx.block_timestamp(sec_to_nano(10) + ctr.per_player_budget);
+        make_move(&mut ctx, &mut ctr, &player_2, &game_id, 1, 1);
+
+        assert_eq!(ctr.get_game(&game_id).game_result, GameResult::Win(player_1.clone(), WinReason::Timeout));
+        assert_eq!(ctr.get_user_penalties(&player_2).penalties_num, 1);
+        assert_eq!(ctr.get_user_penalties(&player_1).penalties_num, 0);
+    }
+
+    #[test]
+    fn test_get_game_winner() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        assert_eq!(ctr.get_game_winner(&game_id), None);
+        assert_eq!(ctr.get_game_winner(&(game_id + 1)), None);
+
+        give_up(&mut ctx, &mut ctr, &player_1, &game_id);
+        assert_eq!(ctr.get_game_winner(&game_id), Some(GameResult::Win(player_2, WinReason::GiveUp)));
+    }
+
+    fn start_rematch_game(ctx: &mut VMContextBuilder, ctr: &mut Contract, opponent_auto_rematch: bool) -> (GameId, AccountId, AccountId) {
+        let rematch_config = |auto_rematch| GameConfigNear { auto_rematch: Some(auto_rematch), ..Default::default() };
+        make_available_near_config(ctx, ctr, &user(), 2 * ONE_NEAR, rematch_config(true));
+        let opponent_deposit = if opponent_auto_rematch { 2 * ONE_NEAR } else { ONE_NEAR };
+        make_available_near_config(ctx, ctr, &opponent(), opponent_deposit, rematch_config(opponent_auto_rematch));
+        let game_id = start_game(ctx, ctr, &user(), &opponent());
+        let (player_1, player_2) = ctr.internal_get_game_players(&game_id);
+        (game_id, player_1, player_2)
+    }
+
+    #[test]
+    fn test_auto_rematch() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, player_2) = start_rematch_game(&mut ctx, &mut ctr, true);
+        assert_eq!(ctr.internal_get_game(&game_id).reward.balance.0, 2 * ONE_NEAR);
+        assert_eq!(ctr.get_rematch_escrows(game_id).len(), 2);
+
+        for col in 0..4 {
+            make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, col);
+            make_move(&mut ctx, &mut ctr, &player_2, &game_id, 1, col);
+        }
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 4);
+
+        // escrows are stakes of the new game
+        let active_games = ctr.get_active_games();
+        assert_eq!(active_games.len(), 1);
+        let (rematch_id, rematch) = &active_games[0];
+        assert_eq!(*rematch_id, game_id + 1);
+        assert_eq!(rematch.reward.balance.0, 2 * ONE_NEAR);
+        assert!(ctr.get_rematch_escrows(game_id).is_empty());
+        assert!(ctr.get_rematch_escrows(*rematch_id).is_empty());
+    }
+
+    #[test]
+    fn test_auto_rematch_refund() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, _) = start_rematch_game(&mut ctx, &mut ctr, false);
+        assert_eq!(ctr.get_rematch_escrows(game_id), vec![(user(), U128(ONE_NEAR))]);
+
+        give_up(&mut ctx, &mut ctr, &player_1, &game_id);
+        assert!(ctr.get_active_games().is_empty());
+        assert!(ctr.get_rematch_escrows(game_id).is_empty());
+    }
+
+    fn assert_rematch_refunded(ctx: &mut VMContextBuilder, ctr: &mut Contract, game_id: GameId, player_1: &AccountId, player_2: &AccountId) {
+        for col in 0..4 {
+            make_move(ctx, ctr, player_1, &game_id, 0, col);
+            make_move(ctx, ctr, player_2, &game_id, 1, col);
+        }
+        make_move(ctx, ctr, player_1, &game_id, 0, 4);
+
+        assert!(ctr.get_active_games().is_empty());
+        assert!(ctr.get_rematch_escrows(game_id).is_empty());
+        let mut refunds: Vec<(AccountId, Balance)> = near_transfers()
+            .into_iter()
+            .filter(|(_, amount)| *amount == ONE_NEAR)
+            .collect();
+        refunds.sort();
+        assert_eq!(refunds, vec![(opponent(), ONE_NEAR), (user(), ONE_NEAR)]);
+    }
+
+    #[test]
+    fn test_auto_rematch_with_cooldown() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, player_2) = start_rematch_game(&mut ctx, &mut ctr, true);
+        ctr.set_rematch_cooldown(30);
+        for col in 0..4 {
+            make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, col);
+            make_move(&mut ctx, &mut ctr, &player_2, &game_id, 1, col);
+        }
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 4);
+
+        // rematch is started, cooldown applies to other games
+        let active_games = ctr.get_active_games();
+        assert_eq!(active_games.len(), 1);
+        assert_eq!(active_games[0].1.reward.balance.0, 2 * ONE_NEAR);
+        assert!(ctr.get_rematch_escrows(game_id).is_empty());
+        assert_eq!(ctr.get_remaining_cooldown(player_1), Some(sec_to_nano(30)));
+    }
+
+    #[test]
+    fn test_auto_rematch_refund_token_not_accepted() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        whitelist_token(&mut ctr);
+        let (game_id, player_1, player_2) = start_rematch_game(&mut ctx, &mut ctr, true);
+        testing_env!(ctx.predecessor_account_id(player_2.clone()).build());
+        ctr.set_accepted_tokens(Some(vec![acc_cheddar()]));
+        assert_rematch_refunded(&mut ctx, &mut ctr, game_id, &player_1, &player_2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Deposit with rematch escrow must be even, half of it is escrow")]
+    fn test_auto_rematch_odd_deposit() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let config = GameConfigNear { auto_rematch: Some(true), ..Default::default() };
+        make_available_near_config(&mut ctx, &mut ctr, &user(), 2 * ONE_NEAR + 1, config);
+    }
+
+    #[test]
+    fn test_payout_to() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let cold: AccountId = "cold.near".parse().unwrap();
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+        let config = GameConfigNear { payout_to: Some(cold.clone()), ..Default::default() };
+        make_available_near_config(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, config);
+        let game_id = start_game(&mut ctx, &mut ctr, &user(), &opponent());
+
+        give_up(&mut ctx, &mut ctr, &user(), &game_id);
+        let reward = ctr.get_game(&game_id).reward_or_tie_refund.balance.0;
+        let transfers = near_transfers();
+        assert!(transfers.contains(&(cold, reward)));
+        assert!(!transfers.iter().any(|(receiver_id, _)| *receiver_id == opponent()));
+        // stats are credited to the player
+        assert_eq!(ctr.get_stats(&opponent()).victories_num, 1);
+    }
+
+    /// fill the board without lines
+    fn play_tie(
+        ctx: &mut VMContextBuilder,
+        ctr: &mut Contract,
+        game_id: &GameId,
+        player_1: &AccountId,
+        player_2: &AccountId,
+    ) {
+        // X X O O X
+        // O O X X O
+        // X X O O X
+        // O O X X O
+        // X O X O X
+        let player_1_tiles = [(0, 0), (0, 1), (0, 4), (1, 2), (1, 3), (2, 0), (2, 1), (2, 4), (3, 2), (3, 3), (4, 0), (4, 2), (4, 4)];
+        let player_2_tiles = [(0, 2), (0, 3), (1, 0), (1, 1), (1, 4), (2, 2), (2, 3), (3, 0), (3, 1), (3, 4), (4, 1), (4, 3)];
+        for turn in 0..MAX_NUM_TURNS as usize {
+            let (player, (row, col)) = if turn % 2 == 0 {
+                (player_1, player_1_tiles[turn / 2])
+            } else {
+                (player_2, player_2_tiles[turn / 2])
+            };
+            make_move(ctx, ctr, player, game_id, row, col);
+        }
+    }
+
+    fn near_transfers() -> Vec<(AccountId, Balance)> {
+        near_sdk::test_utils::get_created_receipts()
+            .into_iter()
+            .flat_map(|receipt| {
+                let receiver_id = receipt.receiver_id;
+                receipt.actions.into_iter().filter_map(move |action| match action {
+                    near_sdk::mock::VmAction::Transfer { deposit } => Some((receiver_id.clone(), deposit)),
+                    _ => None,
+                })
+            })
+            .collect()
+    }
+
+    fn start_delayed_payout_game(ctx: &mut VMContextBuilder, ctr: &mut Contract) -> GameId {
+        ctr.set_payout_delay(600);
+        ctr.set_payout_delay_threshold(near_token_id(), U128(ONE_NEAR));
+        let (game_id, _, _) = start_near_game(ctx, ctr);
+        give_up(ctx, ctr, &user(), &game_id);
+        game_id
+    }
+
+    #[test]
+    fn test_payout_delay() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let game_id = start_delayed_payout_game(&mut ctx, &mut ctr);
+        let reward = ctr.get_game(&game_id).reward_or_tie_refund.balance.0;
+        assert!(near_transfers().is_empty());
+        assert_eq!(ctr.get_delayed_payout(game_id).unwrap().release_at_sec, 600);
+
+        testing_env!(ctx.predecessor_account_id(user()).block_timestamp(sec_to_nano(599)).build());
+        ctr.dispute_payout(game_id);
+        assert!(near_sdk::test_utils::get_logs().iter().any(|log| log.contains("\"event\":\"payout_disputed\"")));
+
+        testing_env!(ctx.predecessor_account_id("third".parse().unwrap()).block_timestamp(sec_to_nano(600)).build());
+        ctr.finalize_payout(game_id);
+        assert!(ctr.get_delayed_payout(game_id).is_none());
+        assert!(near_transfers().contains(&(opponent(), reward)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Too early to finalize payout. Release at 600 sec")]
+    fn test_payout_delay_too_early() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let game_id = start_delayed_payout_game(&mut ctx, &mut ctr);
+        ctr.finalize_payout(game_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only loser can dispute the payout")]
+    fn test_payout_dispute_by_winner() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let game_id = start_delayed_payout_game(&mut ctx, &mut ctr);
+        testing_env!(ctx.predecessor_account_id(opponent()).build());
+        ctr.dispute_payout(game_id);
+    }
+
+    #[test]
+    fn test_debug_logs() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let budget_sec = nano_to_sec(ctr.per_player_budget);
+        let has_debug_logs = || near_sdk::test_utils::get_logs().iter().any(|log| log.starts_with("game.current_duration"));
+
+        let (game_id, _, player_2) = start_near_game(&mut ctx, &mut ctr);
+        stop_game(&mut ctx, &mut ctr, &player_2, &game_id, budget_sec + 1);
+        assert!(!has_debug_logs());
+
+        ctr.set_debug_logs(true);
+        assert_eq!(ctr.get_config().debug_logs, Some(true));
+        let (game_id, _, player_2) = start_near_game(&mut ctx, &mut ctr);
+        stop_game(&mut ctx, &mut ctr, &player_2, &game_id, 2 * budget_sec + 2);
+        assert!(has_debug_logs());
+    }
+
+    #[test]
+    fn test_forfeit_all_my_games() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 0);
+
+        // conceding out of turn as well
+        testing_env!(ctx
+            .predecessor_account_id(player_1.clone())
+            .attached_deposit(ONE_YOCTO)
+            .build());
+        assert_eq!(ctr.forfeit_all_my_games(), 0);
+
+        assert!(ctr.get_active_games().is_empty());
+        assert_eq!(ctr.get_game(&game_id).winner, Some(player_2.clone()));
+        assert_eq!(ctr.get_stats(&player_2).victories_num, 1);
+        // nothing left to forfeit
+        assert_eq!(ctr.forfeit_all_my_games(), 0);
+    }
+
+    #[test]
+    fn test_no_diagonals_win_rule() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let game_config = GameConfigNear {
+            rules: Some(GameRules { win_rule: WinRule::NoDiagonals, ..Default::default() }),
+            ..Default::default()
+        };
+        make_available_near_config(&mut ctx, &mut ctr, &user(), ONE_NEAR, game_config);
+        let game_config = GameConfigNear {
+            rules: Some(GameRules { win_rule: WinRule::NoDiagonals, ..Default::default() }),
+            ..Default::default()
+        };
+        make_available_near_config(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, game_config);
+        let game_id = start_game(&mut ctx, &mut ctr, &user(), &opponent());
+        let (player_1, player_2) = ctr.internal_get_game_players(&game_id);
+
+        // full diagonal doesn't win
+        for i in 0..BOARD_SIZE - 1 {
+            make_move(&mut ctx, &mut ctr, &player_1, &game_id, i, i);
+            make_move(&mut ctx, &mut ctr, &player_2, &game_id, i + 1, 0);
+        }
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 4, 4);
+        assert_eq!(ctr.internal_get_game(&game_id).game_state, GameState::Active);
+
+        // full column still wins
+        for (row, &(opponent_row, opponent_col)) in [(0, 1), (0, 2), (0, 3), (1, 2)].iter().enumerate() {
+            make_move(&mut ctx, &mut ctr, &player_2, &game_id, opponent_row, opponent_col);
+            make_move(&mut ctx, &mut ctr, &player_1, &game_id, row, 4);
+        }
+        assert!(ctr.get_active_games().is_empty());
+        assert_eq!(ctr.get_game(&game_id).winner, Some(player_1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Mismatched game rules! Choosen rules for opponent and you must be the same")]
+    fn test_mismatched_rules() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let game_config = GameConfigNear {
+            rules: Some(GameRules { win_rule: WinRule::DiagonalsOnly, ..Default::default() }),
+            ..Default::default()
+        };
+        make_available_near_config(&mut ctx, &mut ctr, &user(), ONE_NEAR, game_config);
+        make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, None, None);
+        start_game(&mut ctx, &mut ctr, &user(), &opponent());
+    }
+
+    #[test]
+    #[should_panic(expected = "Provided position is invalid: row: 5 col: 0")]
+    fn test_make_move_row_out_of_bounds() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, _) = start_near_game(&mut ctx, &mut ctr);
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, BOARD_SIZE, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Provided position is invalid: row: 0 col: 5")]
+    fn test_make_move_col_out_of_bounds() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, _) = start_near_game(&mut ctx, &mut ctr);
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, BOARD_SIZE);
+    }
+
+    #[test]
+    #[should_panic(expected = "The column 0 is already filled")]
+    fn test_gravity_mode() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        for account_id in [user(), opponent()].iter() {
+            let game_config = GameConfigNear {
+                rules: Some(GameRules { gravity: true, ..Default::default() }),
+                ..Default::default()
+            };
+            make_available_near_config(&mut ctx, &mut ctr, account_id, ONE_NEAR, game_config);
+        }
+        let game_id = start_game(&mut ctx, &mut ctr, &user(), &opponent());
+        let (player_1, player_2) = ctr.internal_get_game_players(&game_id);
+        let piece_1 = ctr.internal_get_game(&game_id).current_piece;
+
+        // row is ignored, piece drops to the bottom
+        let tiles = make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 0);
+        assert_eq!(tiles[BOARD_SIZE - 1][0], Some(piece_1));
+        assert_eq!(tiles[0][0], None);
+        let tiles = make_move(&mut ctx, &mut ctr, &player_2, &game_id, 0, 0);
+        assert_eq!(tiles[BOARD_SIZE - 2][0], Some(piece_1.other()));
+
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 0);
+        make_move(&mut ctx, &mut ctr, &player_2, &game_id, 0, 0);
+        let tiles = make_move(&mut ctx, &mut ctr, &player_1, &game_id, 4, 0);
+        assert_eq!(tiles[0][0], Some(piece_1));
+        // column is full
+        make_move(&mut ctx, &mut ctr, &player_2, &game_id, 0, 0);
+    }
+
+    #[test]
+    fn test_viewers() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+
+        testing_env!(ctx.predecessor_account_id(referrer()).build());
+        ctr.register_viewer(game_id);
+        // registering twice doesn't count twice
+        ctr.register_viewer(game_id);
+        testing_env!(ctx.predecessor_account_id(acc_cheddar()).build());
+        ctr.register_viewer(game_id);
+        assert_eq!(ctr.get_viewer_count(game_id), 2);
+
+        ctr.unregister_viewer(game_id);
+        assert_eq!(ctr.get_viewer_count(game_id), 1);
+
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 0);
+        give_up(&mut ctx, &mut ctr, &player_2, &game_id);
+        assert_eq!(ctr.get_viewer_count(game_id), 0);
+        assert!(ctr.viewers.get(&game_id).is_none());
+    }
+
+    #[test]
+    fn test_payout_in_other_token() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        whitelist_token(&mut ctr);
+        ctr.set_payout_rate(near_token_id(), acc_cheddar(), U128(2 * RATE_DENOMINATOR));
+
+        // fund reserve for one payout only
+        let contract_id = ctx.context.current_account_id.clone();
+        testing_env!(ctx.predecessor_account_id(acc_cheddar()).build());
+        ctr.ft_on_transfer(contract_id, U128(5 * ONE_CHEDDAR), PAYOUT_RESERVE_MSG.to_string());
+        assert_eq!(ctr.get_payout_reserve(acc_cheddar()).0, 5 * ONE_CHEDDAR);
+        assert!(ctr.get_available_players().is_empty());
+
+        let winner_reward = 2 * ONE_NEAR - 2 * ONE_NEAR / BASIS_P as u128 * MIN_FEES as u128;
+
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        give_up(&mut ctx, &mut ctr, &player_1, &game_id);
+        let reward = ctr.get_game(&game_id).reward_or_tie_refund;
+        assert_eq!(reward, GameDeposit { token_id: acc_cheddar(), balance: (2 * winner_reward).into() });
+        assert_eq!(ctr.get_payout_reserve(acc_cheddar()).0, 5 * ONE_CHEDDAR - 2 * winner_reward);
+        assert_eq!(ctr.get_stats(&player_2).total_reward, vec![(acc_cheddar(), 2 * winner_reward)]);
+
+        // reserve is not enough, reward is paid in NEAR
+        let (game_id, player_1, _) = start_near_game(&mut ctx, &mut ctr);
+        give_up(&mut ctx, &mut ctr, &player_1, &game_id);
+        let reward = ctr.get_game(&game_id).reward_or_tie_refund;
+        assert_eq!(reward, GameDeposit { token_id: near_token_id(), balance: winner_reward.into() });
+    }
+
+    #[test]
+    fn test_pending_refunds() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        whitelist_token(&mut ctr);
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+
+        // failed payouts
+        testing_env!(
+            ctx.predecessor_account_id(ctx.context.current_account_id.clone()).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        ctr.transfers_callback(vec![
+            Transfer { receiver_id: user(), token_id: near_token_id(), amount: U128(ONE_NEAR / 2) },
+        ]);
+        ctr.transfers_callback(vec![
+            Transfer { receiver_id: user(), token_id: acc_cheddar(), amount: U128(ONE_CHEDDAR) },
+        ]);
+        assert_eq!(ctr.get_my_pending_refunds(user()), vec![
+            (near_token_id(), U128(ONE_NEAR + ONE_NEAR / 2)),
+            (acc_cheddar(), U128(ONE_CHEDDAR)),
+        ]);
+
+        testing_env!(ctx
+            .predecessor_account_id(user())
+            .attached_deposit(ONE_YOCTO)
+            .build());
+        ctr.claim_all_refunds();
+        assert!(ctr.get_my_pending_refunds(user()).is_empty());
+        assert!(ctr.get_available_players().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Too many players are waiting with near. Try later")]
+    fn test_max_waiting_per_token() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        whitelist_token(&mut ctr);
+        ctr.set_max_waiting_per_token(1);
+
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+        // other tokens have own limit
+        make_available_ft(&mut ctx, &mut ctr, &opponent(), ONE_CHEDDAR, "".to_string());
+        assert_eq!(ctr.get_waiting_counts(), vec![(near_token_id(), 1), (acc_cheddar(), 1)]);
+
+        make_unavailable(&mut ctx, &mut ctr, &opponent());
+        assert_eq!(ctr.get_waiting_counts(), vec![(near_token_id(), 1)]);
+
+        make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, None, None);
+    }
+
+    #[test]
+    fn test_migrate_game_v1() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, _) = start_near_game(&mut ctx, &mut ctr);
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 2, 2);
+        let game = ctr.internal_get_game(&game_id);
+
+        let game_v1 = GameV1 {
+            game_state: game.game_state,
+            players: game.players.clone(),
+            current_piece: game.current_piece,
+            current_player_index: game.current_player_index,
+            reward: game.reward.clone(),
+            board: game.board.clone(),
+            total_turns: game.total_turns,
+            initiated_at: game.initiated_at,
+            last_turn_timestamp: game.last_turn_timestamp,
+            current_duration: game.current_duration,
+        };
+        let game_v1_bytes = game_v1.try_to_vec().unwrap();
+        // seed of legacy game is unknown
+        let legacy_game = Game { seed: vec![], ..game.clone() };
+        let migrated = Game::migrate(&game_v1_bytes, ctr.per_player_budget);
+        assert_eq!(migrated, legacy_game);
+        assert_eq!(migrated.version, GAME_VERSION);
+
+        // current layout is kept as is
+        assert_eq!(Game::migrate(&game.try_to_vec().unwrap(), ctr.per_player_budget), game);
+    }
+
+    #[test]
+    fn test_migrate_contract() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, _) = start_near_game(&mut ctx, &mut ctr);
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 2, 2);
+        let game = ctr.internal_get_game(&game_id);
+        let game_v1 = GameV1 {
+            game_state: game.game_state,
+            players: game.players.clone(),
+            current_piece: game.current_piece,
+            current_player_index: game.current_player_index,
+            reward: game.reward.clone(),
+            board: game.board.clone(),
+            total_turns: game.total_turns,
+            initiated_at: game.initiated_at,
+            last_turn_timestamp: game.last_turn_timestamp,
+            current_duration: game.current_duration,
+        };
+
+        // fresh storage with the first release state
+        near_sdk::mock::with_mocked_blockchain(|blockchain| blockchain.take_storage());
+        let mut old = OldContract {
+            whitelisted_tokens: UnorderedMap::new(StorageKey::WhitelistedTokens),
+            games: UnorderedMap::new(StorageKey::Games),
+            available_players: UnorderedMap::new(StorageKey::Players),
+            stats: UnorderedMap::new(StorageKey::Stats),
+            next_game_id: game_id + 2,
+            service_fee_percentage: MIN_FEES,
+            max_game_duration: sec_to_nano(60 * 25),
+            referrer_ratio: BASIS_P / 2,
+            last_update_timestamp: 0,
+            max_turn_duration: sec_to_nano(60),
+            max_stored_games: 50,
+            stored_games: UnorderedMap::new(StorageKey::StoredGames),
+        };
+        old.games.insert(&game_id, &RawBytes(game_v1.try_to_vec().unwrap()));
+        old.available_players.insert(&referrer(), &GameConfigV1 {
+            token_id: near_token_id(),
+            deposit: MIN_DEPOSIT_NEAR,
+            opponent_id: None,
+            referrer_id: None,
+            created_at: 0,
+        });
+        old.stored_games.insert(&(game_id + 1), &GameLimitedViewV1 {
+            game_result: GameResultV1::Win(user()),
+            player1: opponent(),
+            player2: user(),
+            reward_or_tie_refund: game.reward.clone(),
+            board: game.board.tiles,
+        });
+        let mut old_stats = StatsV1 {
+            referrer_id: None,
+            affiliates: UnorderedSet::new(StorageKey::Affiliates { account_id: user() }),
+            games_num: 3,
+            victories_num: 2,
+            penalties_num: 0,
+            total_reward: UnorderedMap::new(StorageKey::TotalRewards { account_id: user() }),
+            total_affiliate_reward: UnorderedMap::new(StorageKey::TotalAffiliateRewards { account_id: user() }),
+        };
+        old_stats.total_reward.insert(&near_token_id(), &MIN_DEPOSIT_NEAR);
+        old.stats.insert(&user(), &RawBytes(old_stats.try_to_vec().unwrap()));
+        env::state_write(&old);
+
+        testing_env!(ctx.block_timestamp(sec_to_nano(1000)).build());
+        let mut ctr = Contract::migrate();
+        assert_eq!(ctr.next_game_id, game_id + 2);
+        assert_eq!(ctr.per_player_budget, sec_to_nano(60 * 25) / 2);
+        assert_eq!(ctr.get_build_info().upgraded_at_sec, 1000);
+        assert_eq!(ctr.get_version(), env!("CARGO_PKG_VERSION"));
+        assert_eq!(ctr.internal_get_game(&game_id), Game { seed: vec![], ..game });
+
+        let invites = ctr.get_available_players();
+        assert_eq!(invites.len(), 1);
+        assert_eq!((&invites[0].0, invites[0].1), (&referrer(), 0));
+        assert_eq!(invites[0].2.deposit, U128(MIN_DEPOSIT_NEAR));
+        assert_eq!(invites[0].2.rules, GameRules::default());
+
+        let stored = ctr.get_game(&(game_id + 1));
+        assert_eq!(stored.game_result, GameResult::Win(user(), WinReason::Line));
+        assert_eq!((stored.winner, stored.loser), (Some(user()), Some(opponent())));
+        assert!(stored.seed.is_empty());
+
+        assert_eq!(ctr.migrate_stats(0, 10), 1);
+        // already converted
+        assert_eq!(ctr.migrate_stats(0, 10), 0);
+        let stats = ctr.get_stats(&user());
+        assert_eq!((stats.games_played, stats.victories_num), (3, 2));
+        assert_eq!(stats.total_reward, vec![(near_token_id(), MIN_DEPOSIT_NEAR)]);
+        assert!(stats.total_wagered.is_empty());
+    }
+
+    #[test]
+    fn test_chess_clock() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        let budget = ctr.per_player_budget;
+
+        ctx.block_timestamp(sec_to_nano(100));
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 0);
+        ctx.block_timestamp(sec_to_nano(110));
+        make_move(&mut ctx, &mut ctr, &player_2, &game_id, 1, 1);
+
+        let game = ctr.internal_get_game(&game_id);
+        assert_eq!(game.time_remaining, [budget - sec_to_nano(100), budget - sec_to_nano(10)]);
+        // player_1 turn, view order is [current, next]
+        let view = ctr.get_active_games()[0].1.time_remaining_sec;
+        assert_eq!(view, [nano_to_sec(budget) - 100, nano_to_sec(budget) - 10]);
+
+        // player_1 spent all his budget for the next move
+        ctx.block_timestamp(sec_to_nano(110) + budget - sec_to_nano(100));
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 1);
+        assert!(ctr.get_active_games().is_empty());
+        assert_eq!(ctr.get_game(&game_id).game_result, GameResult::Win(player_2, WinReason::Timeout));
+    }
+
+    #[test]
+    #[should_panic(expected = "Method is private")]
+    fn test_transfer_deposit_callback_is_private() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let config = GameConfig::with_only_token_params(&near_token_id(), ONE_NEAR);
+        testing_env!(
+            ctx.predecessor_account_id(user()).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        ctr.transfer_deposit_callback(user(), 0, &config);
+    }
+
+    #[test]
+    #[should_panic(expected = "Method is private")]
+    fn test_transfers_callback_is_private() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        testing_env!(
+            ctx.predecessor_account_id(user()).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        ctr.transfers_callback(vec![
+            Transfer { receiver_id: user(), token_id: near_token_id(), amount: U128(ONE_NEAR) },
+        ]);
+    }
+
+    #[test]
+    fn test_make_unavailable_full_refund() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+        make_available_near(&mut ctx, &mut ctr, &user(), 2 * ONE_NEAR, None, None);
+        make_unavailable(&mut ctx, &mut ctr, &user());
+        // deposits and attached 1 yocto are returned, nothing is kept for storage
+        let transfers = near_transfers();
+        assert_eq!(transfers.len(), 2);
+        assert!(transfers.iter().all(|(receiver_id, _)| receiver_id == &user()));
+        let refunded: Balance = transfers.iter().map(|(_, amount)| amount).sum();
+        assert_eq!(refunded, 3 * ONE_NEAR + ONE_YOCTO);
+    }
+
+    #[test]
+    fn test_make_unavailable_failed_refund() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+        let (_, config) = ctr.internal_get_player_invites(&user()).remove(0);
+        make_unavailable(&mut ctx, &mut ctr, &user());
+        // deposit is zeroed before refund
+        assert!(ctr.get_available_players().is_empty());
+        assert!(ctr.get_my_pending_refunds(user()).is_empty());
+
+        // refund failed, player is back to the list and can claim again
+        testing_env!(
+            ctx.predecessor_account_id(ctx.context.current_account_id.clone()).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        ctr.transfer_deposit_callback(user(), 0, &config);
+        assert_eq!(ctr.get_my_pending_refunds(user()), vec![(near_token_id(), U128(ONE_NEAR))]);
+        make_unavailable(&mut ctx, &mut ctr, &user());
+
+        // player made available again before failed refund callback. Both invites are open
+        make_available_near(&mut ctx, &mut ctr, &user(), 2 * ONE_NEAR, None, None);
+        testing_env!(
+            ctx.predecessor_account_id(ctx.context.current_account_id.clone()).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        ctr.transfer_deposit_callback(user(), 0, &config);
+        let deposits: Vec<(InviteId, Balance)> = ctr.internal_get_player_invites(&user())
+            .into_iter()
+            .map(|(invite_id, config)| (invite_id, config.deposit))
+            .collect();
+        assert_eq!(deposits, vec![(0, ONE_NEAR), (1, 2 * ONE_NEAR)]);
+        assert_eq!(ctr.get_my_pending_refunds(user()), vec![(near_token_id(), U128(3 * ONE_NEAR))]);
+        assert_eq!(ctr.get_waiting_counts(), vec![(near_token_id(), 2)]);
+        make_unavailable(&mut ctx, &mut ctr, &user());
+
+        // invites limit is reached, failed refund goes to pending refunds
+        for _ in 0..MAX_INVITES_PER_PLAYER {
+            make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+        }
+        testing_env!(
+            ctx.predecessor_account_id(ctx.context.current_account_id.clone()).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        ctr.transfer_deposit_callback(user(), 0, &config);
+        assert_eq!(ctr.get_player_invites(user()).len(), MAX_INVITES_PER_PLAYER);
+        assert_eq!(ctr.pending_refunds.get(&user()), Some(vec![(near_token_id(), ONE_NEAR)]));
+    }
+
+    #[test]
+    fn test_multiple_invites() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let rules = GameRules { gravity: true, ..Default::default() };
+        make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, None, None);
+        make_available_near_config(&mut ctx, &mut ctr, &opponent(), 2 * ONE_NEAR, GameConfigNear {
+            rules: Some(rules.clone()),
+            ..Default::default()
+        });
+        let gravity_invite_id = 1;
+        assert_eq!(ctr.get_player_invites(opponent()).len(), 2);
+        assert_eq!(ctr.get_available_players().len(), 2);
+        assert_eq!(ctr.get_waiting_counts(), vec![(near_token_id(), 2)]);
+
+        // user's invite matching opponent's one is taken
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+        make_available_near_config(&mut ctx, &mut ctr, &user(), 2 * ONE_NEAR, GameConfigNear {
+            rules: Some(rules.clone()),
+            ..Default::default()
+        });
+        testing_env!(ctx.predecessor_account_id(user()).attached_deposit(0).build());
+        let game_id = ctr.start_game(opponent(), Some(gravity_invite_id));
+        let game = ctr.internal_get_game(&game_id);
+        assert!(game.rules == rules);
+        assert_eq!(game.reward.balance, U128(4 * ONE_NEAR));
+
+        // the rest invites are refunded
+        assert!(ctr.get_player_invites(opponent()).is_empty());
+        assert!(ctr.get_player_invites(user()).is_empty());
+        assert!(ctr.get_waiting_counts().is_empty());
+        let mut transfers = near_transfers();
+        transfers.sort();
+        assert_eq!(transfers, vec![(opponent(), ONE_NEAR), (user(), ONE_NEAR)]);
+    }
+
+    #[test]
+    fn test_invites_matched_twice() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let third: AccountId = "third".parse().unwrap();
+        make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, None, None);
+        make_available_near(&mut ctx, &mut ctr, &opponent(), 2 * ONE_NEAR, None, None);
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+        make_available_near(&mut ctx, &mut ctr, &third, 2 * ONE_NEAR, None, None);
+        testing_env!(ctx.predecessor_account_id(user()).build());
+        ctr.start_game(opponent(), Some(0));
+        assert_eq!(near_transfers(), vec![(opponent(), 2 * ONE_NEAR)]);
+
+        // the second invite is refunded on game start, so opponent can't be matched again
+        testing_env!(ctx.predecessor_account_id(third.clone()).build());
+        assert_eq!(ctr.try_start_game(opponent(), Some(1)).unwrap(), Err(StartGameError::OpponentNotReady));
+        assert_eq!(ctr.try_start_game(opponent(), None).unwrap(), Err(StartGameError::OpponentNotReady));
+        assert_eq!(ctr.get_active_games().len(), 1);
+        assert_eq!(ctr.get_player_invites(third).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Opponent has several invites. Choose one with invite_id")]
+    fn test_start_game_ambiguous_invite() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, None, None);
+        make_available_near(&mut ctx, &mut ctr, &opponent(), 2 * ONE_NEAR, None, None);
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+        start_game(&mut ctx, &mut ctr, &user(), &opponent());
+    }
+
+    #[test]
+    #[should_panic(expected = "Too many open invites. Max 5")]
+    fn test_invites_limit() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        for _ in 0..=MAX_INVITES_PER_PLAYER {
+            make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+        }
+    }
+
+    #[test]
+    fn test_propose_and_accept_game() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+        make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, None, None);
+
+        testing_env!(ctx.predecessor_account_id(user()).build());
+        let game_id = ctr.propose_game(opponent(), None);
+        assert!(near_sdk::test_utils::get_logs().iter().any(|log| log.starts_with("EVENT_JSON:") && log.contains("\"event\":\"game_proposed\"")));
+        assert_eq!(ctr.get_game_proposals(), vec![GameProposalView {
+            game_id,
+            proposer_id: user(),
+            opponent_id: opponent(),
+            expires_at_sec: DEFAULT_READY_CHECK_SEC,
+        }]);
+        // deposits are locked
+        assert!(ctr.get_available_players().is_empty());
+        assert_eq!(ctr.internal_get_game(&game_id).game_state, GameState::NotStarted);
+
+        testing_env!(ctx
+            .predecessor_account_id(opponent())
+            .block_timestamp(sec_to_nano(60))
+            .build());
+        ctr.accept_game(game_id);
+        let game = ctr.internal_get_game(&game_id);
+        assert_eq!(game.game_state, GameState::Active);
+        assert_eq!(game.initiated_at, sec_to_nano(60));
+        assert!(ctr.get_game_proposals().is_empty());
+        assert_eq!(ctr.get_stats(&user()).games_played, 1);
+    }
+
+    #[test]
+    fn test_game_proposal_expired() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+        make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, None, None);
+
+        testing_env!(ctx.predecessor_account_id(user()).build());
+        let game_id = ctr.propose_game(opponent(), None);
+
+        // late accept cancels proposal and players are available again
+        testing_env!(ctx
+            .predecessor_account_id(opponent())
+            .block_timestamp(sec_to_nano(DEFAULT_READY_CHECK_SEC + 1))
+            .build());
+        ctr.accept_game(game_id);
+        assert!(ctr.get_game_proposals().is_empty());
+        assert!(ctr.games.get(&game_id).is_none());
+        assert_eq!(ctr.get_available_players().len(), 2);
+
+        testing_env!(ctx.predecessor_account_id(user()).build());
+        let new_game_id = ctr.start_game(opponent(), None);
+        assert_eq!(ctr.internal_get_game(&new_game_id).game_state, GameState::Active);
+    }
+
+    #[test]
+    #[should_panic(expected = "Ready check isn't over yet")]
+    fn test_cancel_proposal_in_ready_check() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+        make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, None, None);
+        testing_env!(ctx.predecessor_account_id(user()).build());
+        let game_id = ctr.propose_game(opponent(), None);
+        testing_env!(ctx.block_timestamp(sec_to_nano(DEFAULT_READY_CHECK_SEC)).build());
+        ctr.cancel_proposal(game_id);
+    }
+
+    #[test]
+    fn test_cancel_proposal_without_room() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+        make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, None, None);
+        testing_env!(ctx.predecessor_account_id(user()).build());
+        let game_id = ctr.propose_game(opponent(), None);
+        // lobby is filled while proposal is pending
+        ctr.set_max_waiting_per_token(1);
+        let third: AccountId = "third".parse().unwrap();
+        make_available_near(&mut ctx, &mut ctr, &third, ONE_NEAR, None, None);
+
+        testing_env!(ctx
+            .predecessor_account_id(user())
+            .block_timestamp(sec_to_nano(DEFAULT_READY_CHECK_SEC + 1))
+            .build());
+        ctr.cancel_proposal(game_id);
+        assert!(ctr.get_game_proposals().is_empty());
+        assert!(ctr.games.get(&game_id).is_none());
+        assert_eq!(ctr.get_waiting_counts(), vec![(near_token_id(), 1)]);
+        for account_id in [user(), opponent()] {
+            assert!(ctr.get_player_invites(account_id.clone()).is_empty());
+            assert_eq!(ctr.get_my_pending_refunds(account_id), vec![(near_token_id(), U128(ONE_NEAR))]);
+        }
+    }
+
+    #[test]
+    fn test_ft_on_transfer_malformed_msg() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        whitelist_token(&mut ctr);
+        testing_env!(ctx
+            .attached_deposit(ONE_YOCTO)
+            .predecessor_account_id(acc_cheddar())
+            .build());
+        // too long valid config is rejected before parsing too
+        let too_long = format!("{{{}}}", " ".repeat(MAX_TRANSFER_MSG_LEN));
+        for msg in ["garbage", "{\"rules\": 1}", &too_long] {
+            let unused = ctr.ft_on_transfer(user(), U128(ONE_CHEDDAR), msg.to_string());
+            assert!(matches!(unused, PromiseOrValue::Value(U128(amount)) if amount == ONE_CHEDDAR));
+        }
+        assert!(ctr.get_available_players().is_empty());
+        assert!(ctr.get_waiting_counts().is_empty());
+    }
+
+    #[test]
+    fn test_ft_on_transfer_pretty_printed_msg() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        whitelist_token(&mut ctr);
+        testing_env!(ctx
+            .attached_deposit(ONE_YOCTO)
+            .predecessor_account_id(acc_cheddar())
+            .build());
+        let msg = "{\n\t\"opponent_id\": \"opponent.near\"\r\n}";
+        let unused = ctr.ft_on_transfer(user(), U128(ONE_CHEDDAR), msg.to_string());
+        assert!(matches!(unused, PromiseOrValue::Value(U128(0))));
+        let invites = ctr.get_available_players();
+        assert_eq!(invites.len(), 1);
+        assert_eq!(invites[0].2.opponent_id, Some(opponent()));
+    }
+
+    #[test]
+    fn test_ft_on_transfer_rejected_deposit() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        testing_env!(ctx
+            .attached_deposit(ONE_YOCTO)
+            .predecessor_account_id(acc_cheddar())
+            .build());
+        // not whitelisted token
+        let unused = ctr.ft_on_transfer(user(), U128(ONE_CHEDDAR), String::new());
+        assert!(matches!(unused, PromiseOrValue::Value(U128(amount)) if amount == ONE_CHEDDAR));
+
+        // below min deposit
+        whitelist_token(&mut ctr);
+        let unused = ctr.ft_on_transfer(user(), U128(ONE_CHEDDAR / 100), String::new());
+        assert!(matches!(unused, PromiseOrValue::Value(U128(amount)) if amount == ONE_CHEDDAR / 100));
+        assert!(ctr.get_available_players().is_empty());
+
+        let unused = ctr.ft_on_transfer(user(), U128(ONE_CHEDDAR / 10), String::new());
+        assert!(matches!(unused, PromiseOrValue::Value(U128(0))));
+        assert_eq!(ctr.get_available_players().len(), 1);
+    }
+
+    #[test]
+    fn test_max_deposit_ft() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        whitelist_token(&mut ctr);
+        ctr.set_max_deposit(acc_cheddar(), Some(U128(ONE_CHEDDAR)));
+        assert_eq!(ctr.get_whitelisted_tokens(), vec![
+            (acc_cheddar(), DepositRangeView { min_deposit: U128(ONE_CHEDDAR / 10), max_deposit: Some(U128(ONE_CHEDDAR)), referrer_eligible: true })
+        ]);
+        testing_env!(ctx
+            .attached_deposit(ONE_YOCTO)
+            .predecessor_account_id(acc_cheddar())
+            .build());
+        let unused = ctr.ft_on_transfer(user(), U128(ONE_CHEDDAR + 1), String::new());
+        assert!(matches!(unused, PromiseOrValue::Value(U128(amount)) if amount == ONE_CHEDDAR + 1));
+        assert!(ctr.get_available_players().is_empty());
+
+        let unused = ctr.ft_on_transfer(user(), U128(ONE_CHEDDAR), String::new());
+        assert!(matches!(unused, PromiseOrValue::Value(U128(0))));
+        assert_eq!(ctr.get_available_players().len(), 1);
+
+        // cap is removed
+        ctr.set_max_deposit(acc_cheddar(), None);
+        let unused = ctr.ft_on_transfer(opponent(), U128(ONE_CHEDDAR + 1), String::new());
+        assert!(matches!(unused, PromiseOrValue::Value(U128(0))));
+    }
+
+    #[test]
+    fn test_ft_top_up() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        whitelist_token(&mut ctr);
+        ctr.set_max_deposit(acc_cheddar(), Some(U128(2 * ONE_CHEDDAR)));
+        let top_up = r#"{"top_up": true}"#.to_string();
+        testing_env!(ctx
+            .attached_deposit(ONE_YOCTO)
+            .predecessor_account_id(acc_cheddar())
+            .build());
+        // nothing to top up
+        let unused = ctr.ft_on_transfer(user(), U128(ONE_CHEDDAR), top_up.clone());
+        assert!(matches!(unused, PromiseOrValue::Value(U128(amount)) if amount == ONE_CHEDDAR));
+        assert!(ctr.get_available_players().is_empty());
+
+        ctr.ft_on_transfer(user(), U128(ONE_CHEDDAR), String::new());
+        // below min deposit is fine for top up
+        let unused = ctr.ft_on_transfer(user(), U128(ONE_CHEDDAR / 100), top_up.clone());
+        assert!(matches!(unused, PromiseOrValue::Value(U128(0))));
+        let invites = ctr.internal_get_player_invites(&user());
+        assert_eq!(invites.len(), 1);
+        assert_eq!(invites[0].1.deposit, ONE_CHEDDAR + ONE_CHEDDAR / 100);
+
+        // over max deposit
+        let unused = ctr.ft_on_transfer(user(), U128(ONE_CHEDDAR), top_up.clone());
+        assert!(matches!(unused, PromiseOrValue::Value(U128(amount)) if amount == ONE_CHEDDAR));
+        assert_eq!(ctr.internal_get_player_invites(&user())[0].1.deposit, ONE_CHEDDAR + ONE_CHEDDAR / 100);
+
+        // several invites need `invite_id`, invite with other token is rejected
+        let (ft_invite_id, _) = invites[0];
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+        testing_env!(ctx
+            .attached_deposit(ONE_YOCTO)
+            .predecessor_account_id(acc_cheddar())
+            .build());
+        let unused = ctr.ft_on_transfer(user(), U128(ONE_CHEDDAR / 2), top_up);
+        assert!(matches!(unused, PromiseOrValue::Value(U128(amount)) if amount == ONE_CHEDDAR / 2));
+        let near_invite_id = ft_invite_id + 1;
+        let unused = ctr.ft_on_transfer(
+            user(), U128(ONE_CHEDDAR / 2), format!(r#"{{"top_up": true, "invite_id": {}}}"#, near_invite_id)
+        );
+        assert!(matches!(unused, PromiseOrValue::Value(U128(amount)) if amount == ONE_CHEDDAR / 2));
+        let unused = ctr.ft_on_transfer(
+            user(), U128(ONE_CHEDDAR / 2), format!(r#"{{"top_up": true, "invite_id": {}}}"#, ft_invite_id)
+        );
+        assert!(matches!(unused, PromiseOrValue::Value(U128(0))));
+        let invites = ctr.internal_get_player_invites(&user());
+        assert_eq!(invites[0].1.deposit, ONE_CHEDDAR + ONE_CHEDDAR / 2 + ONE_CHEDDAR / 100);
+        assert_eq!(invites[1].1.deposit, ONE_NEAR);
+    }
+
+    #[test]
+    fn test_time_to_be_available() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        assert_eq!(ctr.get_contract_params().time_to_be_available, 24 * 60 * 60);
+        ctr.set_time_to_be_available(60);
+        assert_eq!(ctr.get_config().time_to_be_available_sec, Some(60));
+        assert_eq!(ctr.get_contract_params().time_to_be_available, 60);
+
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+        testing_env!(ctx.block_timestamp(sec_to_nano(60)).build());
+        make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, None, None);
+        assert_eq!(ctr.get_available_players().len(), 2);
+
+        // expired long before the default day
+        testing_env!(ctx.block_timestamp(sec_to_nano(60) + 1).build());
+        make_available_near(&mut ctx, &mut ctr, &"third".parse().unwrap(), ONE_NEAR, None, None);
+        let available: Vec<AccountId> = ctr.get_available_players().into_iter().map(|(account_id, _, _)| account_id).collect();
+        assert_eq!(available.len(), 2);
+        assert!(!available.contains(&user()));
+        assert_eq!(near_transfers(), vec![(user(), ONE_NEAR)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "time to be available need to be in range 60..604800 seconds")]
+    fn test_time_to_be_available_too_short() {
+        let (_, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_time_to_be_available(59);
+    }
+
+    #[test]
+    fn test_max_deposit_near() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_max_deposit(near_token_id(), Some(U128(2 * ONE_NEAR)));
+        make_available_near(&mut ctx, &mut ctr, &user(), 2 * ONE_NEAR, None, None);
+        assert_eq!(ctr.get_available_players().len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Deposit is too big. Max deposit for near is 2000000000000000000000000")]
+    fn test_max_deposit_near_exceeded() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_max_deposit(near_token_id(), Some(U128(2 * ONE_NEAR)));
+        make_available_near(&mut ctx, &mut ctr, &user(), 2 * ONE_NEAR + 1, None, None);
+    }
+
+    #[test]
+    fn test_split_pot_is_exact() {
+        for pot in [2u128, 10_007, 1_000_003, 2 * ONE_NEAR + 10_006, 999_999_999_999_999_989] {
+            for fee in [MIN_FEES, 15, 333, MAX_FEES] {
+                for receivers_num in [1, PLAYERS_NUM as u128] {
+                    let (fees, amount) = split_pot(pot, fee, receivers_num);
+                    assert_eq!(fees + amount * receivers_num, pot);
+                    // remainder is less than one yocto per receiver
+                    assert!(fees - pot / BASIS_P as u128 * (fee as u128) < receivers_num);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_tie_refund_remainder() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(15), None,  Some(60 * 25));
+        // odd pot / BASIS_P with odd fee - pot without fee can't be split equally
+        let deposit = ONE_NEAR + 5003;
+        let pot = 2 * deposit;
+        make_available_near(&mut ctx, &mut ctr, &user(), deposit, None, None);
+        make_available_near(&mut ctx, &mut ctr, &opponent(), deposit, None, None);
+        let game_id = start_game(&mut ctx, &mut ctr, &user(), &opponent());
+        let (player_1, player_2) = ctr.internal_get_game_players(&game_id);
+        play_tie(&mut ctx, &mut ctr, &game_id, &player_1, &player_2);
+
+        let transfers = near_transfers();
+        assert_eq!(transfers.len(), 2);
+        assert_eq!(transfers[0].1, transfers[1].1);
+        // extra yocto is kept as service fee
+        let fee = pot / BASIS_P as u128 * 15;
+        assert_eq!(transfers[0].1 + transfers[1].1 + fee + 1, pot);
+    }
+
+    #[test]
+    fn test_batched_reward_transfers() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        whitelist_token(&mut ctr);
+        let msg = near_sdk::serde_json::to_string(&GameConfigArgs::default()).unwrap();
+        make_available_ft(&mut ctx, &mut ctr, &user(), ONE_CHEDDAR, msg.clone());
+        make_available_ft(&mut ctx, &mut ctr, &opponent(), ONE_CHEDDAR, msg);
+        let game_id = start_game(&mut ctx, &mut ctr, &user(), &opponent());
+        let (player_1, player_2) = ctr.internal_get_game_players(&game_id);
+
+        // tie: both refunds go in one batch with one callback. Previously each
+        // `ft_transfer` had own callback, so callbacks cost 2 * CALLBACK_GAS (10 Tgas),
+        // now it's 1 * CALLBACK_GAS (5 Tgas). Winner + referrer payout saves the same 5 Tgas
+        play_tie(&mut ctx, &mut ctr, &game_id, &player_1, &player_2);
+        assert_eq!(ctr.get_game(&game_id).game_result, GameResult::Tie);
+
+        let callbacks: Vec<Gas> = near_sdk::test_utils::get_created_receipts()
+            .into_iter()
+            .flat_map(|receipt| receipt.actions)
+            .filter_map(|action| match action {
+                near_sdk::mock::VmAction::FunctionCall { function_name, gas, .. } if function_name == "transfers_callback" => Some(gas),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(callbacks, vec![CALLBACK_GAS]);
+    }
+
+    #[test]
+    fn test_board_compact() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        let piece_1 = ctr.internal_get_game(&game_id).current_piece;
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 0);
+        make_move(&mut ctx, &mut ctr, &player_2, &game_id, 2, 4);
+
+        let (p1, p2) = (piece_1.to_string(), piece_1.other().to_string());
+        assert_eq!(ctr.get_board_compact(&game_id), vec![
+            format!("{}....", p1),
+            ".....".to_string(),
+            format!("....{}", p2),
+            ".....".to_string(),
+            ".....".to_string(),
+        ]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Too fast move. Min turn interval is 10 sec")]
+    fn test_min_turn_interval() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_min_turn_interval(10);
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        // first move is exempt
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 0);
+
+        testing_env!(ctx.block_timestamp(sec_to_nano(10)).build());
+        make_move(&mut ctx, &mut ctr, &player_2, &game_id, 1, 1);
+        testing_env!(ctx.block_timestamp(sec_to_nano(19)).build());
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 2, 2);
+    }
+
+    #[test]
+    fn test_get_stats_batch() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        give_up(&mut ctx, &mut ctr, &player_1, &game_id);
+
+        let stats = ctr.get_stats_batch(vec![player_2.clone(), referrer(), player_1.clone()]);
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats[0].0, player_2);
+        assert_eq!(stats[0].1.as_ref().unwrap().victories_num, 1);
+        assert_eq!(stats[1].0, referrer());
+        assert!(stats[1].1.is_none());
+        assert_eq!(stats[2].0, player_1);
+        assert_eq!(stats[2].1.as_ref().unwrap().victories_num, 0);
+
+        testing_env!(ctx.predecessor_account_id(player_2.clone()).build());
+        let my_stats = ctr.get_my_stats();
+        assert_eq!((my_stats.games_played, my_stats.victories_num), (1, 1));
+        testing_env!(ctx.predecessor_account_id(referrer()).build());
+        let my_stats = ctr.get_my_stats();
+        assert_eq!((my_stats.games_played, my_stats.victories_num, my_stats.penalties_num), (0, 0, 0));
+        assert!(my_stats.total_reward.is_empty());
+    }
+
+    #[test]
+    fn test_total_wagered() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        whitelist_token(&mut ctr);
+        for _ in 0..2 {
+            make_available_ft(&mut ctx, &mut ctr, &user(), ONE_CHEDDAR, "".to_string());
+            make_available_ft(&mut ctx, &mut ctr, &opponent(), ONE_CHEDDAR, "".to_string());
+            let game_id = start_game(&mut ctx, &mut ctr, &user(), &opponent());
+            give_up(&mut ctx, &mut ctr, &user(), &game_id);
+        }
+        for account_id in [user(), opponent()].iter() {
+            assert_eq!(ctr.get_stats(account_id).total_wagered, vec![(acc_cheddar(), 2 * ONE_CHEDDAR)]);
+        }
+    }
+
+    #[test]
+    fn test_rakeback() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_rakeback_ratio(BASIS_P / 10);
+        let (game_id, player_1, player_2) = start_near_game(&mut ctx, &mut ctr);
+        give_up(&mut ctx, &mut ctr, &player_1, &game_id);
+
+        // 10% from each player's half of service fee
+        let fees = 2 * ONE_NEAR / BASIS_P as u128 * MIN_FEES as u128;
+        let rakeback = fees / 2 / BASIS_P as u128 * (BASIS_P / 10) as u128;
+        assert_eq!(ctr.get_claimable_rakeback(player_1.clone(), near_token_id()).0, rakeback);
+        assert_eq!(ctr.get_claimable_rakeback(player_2, near_token_id()).0, rakeback);
+
+        testing_env!(ctx
+            .predecessor_account_id(player_1.clone())
+            .attached_deposit(ONE_YOCTO)
+            .build());
+        assert_eq!(ctr.claim_rakeback(near_token_id()).0, rakeback);
+        assert_eq!(ctr.get_claimable_rakeback(player_1, near_token_id()).0, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Find a friend to play")]
+    fn test_self_opponent_near() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, Some(user()), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Find a friend to play")]
+    fn test_self_opponent_ft() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        whitelist_token(&mut ctr);
+        let msg = near_sdk::serde_json::to_string(&GameConfigArgs {
+            opponent_id: Some(user()),
+            ..Default::default()
+        }).unwrap();
+        make_available_ft(&mut ctx, &mut ctr, &user(), ONE_CHEDDAR, msg);
+    }
+
+    #[test]
+    fn test_get_config() {
+        let (_, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_max_waiting_per_token(7);
+        ctr.set_fee_schedule(vec![FeeBucket { max_turns: 10, service_fee_percentage: MAX_FEES }]);
+        let config = ctr.get_config();
+        assert_eq!(config.max_game_duration_sec, 60 * 25);
+        assert_eq!(config.max_waiting_per_token, Some(7));
+
+        let cloned = Contract::new(Some(ctr.get_config()));
+        assert_eq!(cloned.get_config(), config);
+    }
+
+    #[test]
+    fn test_has_winner_at_random_boards() {
+        let mut rng = TestRng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..5000 {
+            let mut board = random_board(&mut rng);
+            let (row, col) = (rng.next(BOARD_SIZE), rng.next(BOARD_SIZE));
+            board.tiles[row][col] = Some(if rng.next(2) == 0 { Piece::X } else { Piece::O });
+            let win_rule = WIN_RULES[rng.next(WIN_RULES.len())];
+            assert_eq!(
+                board.has_winner_at(row, col, win_rule),
+                reference_winner_at(&board, row, col, win_rule),
+                "board: {:?}, move: ({}, {}), rule: {:?}", board.to_compact(), row, col, win_rule
+            );
+            assert!(board.winner.is_none());
+        }
+    }
+
+    #[test]
+    fn test_has_winner_at_random_lines() {
+        let mut rng = TestRng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..2000 {
+            let mut board = random_board(&mut rng);
+            let piece = if rng.next(2) == 0 { Piece::X } else { Piece::O };
+            let i = rng.next(BOARD_SIZE);
+            let (line, rule): (Vec<(usize, usize)>, WinRule) = match rng.next(4) {
+                0 => ((0..BOARD_SIZE).map(|j| (i, j)).collect(), WinRule::NoDiagonals),
+                1 => ((0..BOARD_SIZE).map(|j| (j, i)).collect(), WinRule::NoDiagonals),
+                2 => ((0..BOARD_SIZE).map(|j| (j, j)).collect(), WinRule::DiagonalsOnly),
+                _ => ((0..BOARD_SIZE).map(|j| (j, BOARD_SIZE - 1 - j)).collect(), WinRule::DiagonalsOnly),
+            };
+            for &(r, c) in line.iter() {
+                board.tiles[r][c] = Some(piece);
+            }
+            let (row, col) = line[rng.next(BOARD_SIZE)];
+            let expected = Some(if piece == Piece::X { Winner::X } else { Winner::O });
+            assert_eq!(board.has_winner_at(row, col, WinRule::Standard), expected);
+            assert_eq!(board.has_winner_at(row, col, rule), expected);
+            assert_eq!(reference_winner_at(&board, row, col, rule), expected);
+
+            board.update_winner(row, col, rule);
+            assert_eq!(board.winner, expected);
+        }
+    }
+
+    #[test]
+    fn test_available_players_filtered() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        whitelist_token(&mut ctr);
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+        make_available_near(&mut ctx, &mut ctr, &user(), 2 * ONE_NEAR, None, None);
+        make_available_ft(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, String::new());
+
+        let invite_ids = |players: Vec<(AccountId, InviteId, GameConfigView)>| {
+            let mut ids: Vec<InviteId> = players.iter().map(|(_, invite_id, _)| *invite_id).collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(invite_ids(ctr.get_available_players_filtered(None, None)), vec![0, 1, 2]);
+        assert_eq!(invite_ids(ctr.get_available_players_filtered(Some(near_token_id()), None)), vec![0, 1]);
+        assert_eq!(invite_ids(ctr.get_available_players_filtered(None, Some(U128(ONE_NEAR)))), vec![0, 2]);
+        assert_eq!(invite_ids(ctr.get_available_players_filtered(Some(acc_cheddar()), Some(U128(ONE_NEAR)))), vec![2]);
+        assert!(ctr.get_available_players_filtered(Some(acc_cheddar()), Some(U128(2 * ONE_NEAR))).is_empty());
+    }
+
+    #[test]
+    fn test_available_players_humanized() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        whitelist_token(&mut ctr);
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+        let msg = near_sdk::serde_json::to_string(&GameConfigArgs::default()).expect("err serialize");
+        make_available_ft(&mut ctx, &mut ctr, &opponent(), ONE_CHEDDAR, msg);
+
+        let decimals = |ctr: &Contract, account_id: AccountId| ctr.get_available_players_humanized()
+            .into_iter()
+            .find(|(acc, _, _)| *acc == account_id)
+            .map(|(_, _, view)| view.decimals)
+            .expect("player not found");
+        assert_eq!(decimals(&ctr, user()), Some(NEAR_DECIMALS));
+        assert_eq!(decimals(&ctr, opponent()), None);
+
+        ctr.set_token_decimals(acc_cheddar(), 24);
+        assert_eq!(decimals(&ctr, opponent()), Some(24));
+    }
+
+    #[test]
+    fn test_games_counts() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, _) = start_near_game(&mut ctx, &mut ctr);
+        give_up(&mut ctx, &mut ctr, &player_1, &game_id);
+        start_near_game(&mut ctx, &mut ctr);
+
+        assert_eq!(ctr.get_next_game_id(), 2);
+        assert_eq!(ctr.get_total_games_created(), 2);
+        assert_eq!(ctr.get_games_counts(), GamesCountsView {
+            total_created: 2,
+            active: 1,
+            not_started: 0,
+            finished: 1,
+            stored: 1,
+        });
+        let (fees, _) = split_pot(2 * ONE_NEAR, MIN_FEES, 1);
+        assert_eq!(ctr.get_protocol_stats(), ProtocolStats {
+            total_games: 2,
+            active_games: 1,
+            total_players: 2,
+            total_volume_per_token: vec![(near_token_id(), U128(4 * ONE_NEAR))],
+            total_fees_per_token: vec![(near_token_id(), U128(fees))],
+        });
+    }
+
+    #[test]
+    fn test_board_bitboards() {
+        let mut rng = TestRng(0x0fed_cba9_8765_4321);
+        for _ in 0..200 {
+            let board = random_board(&mut rng);
+            let (x, o) = board.to_bitboards();
+            assert_eq!(x & o, 0);
+            assert_eq!(Board::from_bitboards(x, o).tiles, board.tiles);
+        }
+        let mut board = Board { tiles: Default::default(), current_piece: Piece::X, winner: None };
+        board.tiles[0][1] = Some(Piece::X);
+        board.tiles[4][4] = Some(Piece::O);
+        assert_eq!(board.to_bitboards(), (1 << 1, 1 << 24));
+        assert_eq!(Board::from_bitboards(1 << 1, 0).current_piece, Piece::O);
+    }
+
+    #[test]
+    fn test_board_canonical_hash() {
+        let mut rng = TestRng(0x1234_5678_9abc_def1);
+        for _ in 0..200 {
+            let board = random_board(&mut rng);
+            let hash = board.canonical_hash();
+            let mut rotated = board.clone();
+            let mut reflected = board.clone();
+            for row in 0..BOARD_SIZE {
+                for col in 0..BOARD_SIZE {
+                    rotated.tiles[col][BOARD_SIZE - 1 - row] = board.tiles[row][col];
+                    reflected.tiles[row][BOARD_SIZE - 1 - col] = board.tiles[row][col];
+                }
+            }
+            assert_eq!(rotated.canonical_hash(), hash);
+            assert_eq!(reflected.canonical_hash(), hash);
+        }
+
+        let mut corner = Board { tiles: Default::default(), current_piece: Piece::X, winner: None };
+        let mut center = corner.clone();
+        corner.tiles[0][0] = Some(Piece::X);
+        center.tiles[2][2] = Some(Piece::X);
+        assert_ne!(corner.canonical_hash(), center.canonical_hash());
+        let mut other_corner = corner.clone();
+        other_corner.tiles[0][0] = None;
+        other_corner.tiles[4][0] = Some(Piece::X);
+        assert_eq!(corner.canonical_hash(), other_corner.canonical_hash());
+    }
+
+    #[test]
+    fn test_update_seq() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        assert_eq!(ctr.get_update_seq(), 0);
+        let (game_id, player_1, _) = start_near_game(&mut ctx, &mut ctr);
+        // two `make_available` and `start_game`
+        assert_eq!(ctr.get_update_seq(), 3);
+        assert_eq!(ctr.get_active_games()[0].1.update_seq, 3);
+
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 0);
+        assert_eq!(ctr.get_update_seq(), 4);
+        assert_eq!(ctr.get_contract_params().update_seq, 4);
+        ctr.get_game_proposals();
+        assert_eq!(ctr.get_update_seq(), 4);
+    }
+
+    #[test]
+    fn test_set_max_stored_games() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let mut game_ids = vec![];
+        for _ in 0..3 {
+            let (game_id, player_1, _) = start_near_game(&mut ctx, &mut ctr);
+            give_up(&mut ctx, &mut ctr, &player_1, &game_id);
+            game_ids.push(game_id);
+        }
+        assert_eq!(ctr.get_last_games().len(), 3);
+
+        ctr.set_max_stored_games(1);
+        let stored: Vec<GameId> = ctr.get_last_games().iter().map(|(game_id, _)| *game_id).collect();
+        assert_eq!(stored, vec![game_ids[2]]);
+        assert_eq!(ctr.get_config().max_stored_games, 1);
+    }
+
+    #[test]
+    fn test_stored_games_ring_buffer() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let max_stored_games = 5;
+        ctr.set_max_stored_games(max_stored_games);
+        let mut game_ids = vec![];
+        for _ in 0..max_stored_games + 10 {
+            let (game_id, player_1, _) = start_near_game(&mut ctx, &mut ctr);
+            give_up(&mut ctx, &mut ctr, &player_1, &game_id);
+            game_ids.push(game_id);
+        }
+
+        let stored: Vec<GameId> = ctr.get_last_games().iter().map(|(game_id, _)| *game_id).collect();
+        assert_eq!(stored, game_ids[10..].to_vec());
+        assert_eq!(ctr.get_stored_games_num(), max_stored_games);
+        assert!(ctr.stored_games_order.get(&(ctr.stored_games_head - 1)).is_none());
+    }
+
+    #[test]
+    fn test_stored_game_max_age() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_stored_game_max_age(60 * 60);
+        let mut game_ids = vec![];
+        for finished_at_sec in [100, 1000] {
+            let (game_id, player_1, _) = start_near_game(&mut ctx, &mut ctr);
+            ctx.block_timestamp(sec_to_nano(finished_at_sec));
+            give_up(&mut ctx, &mut ctr, &player_1, &game_id);
+            assert_eq!(ctr.get_game(&game_id).finished_at, sec_to_nano(finished_at_sec));
+            game_ids.push(game_id);
+        }
+        assert_eq!(ctr.get_stored_games_num(), 2);
+
+        // the first game is expired, the second one is not yet
+        ctx.block_timestamp(sec_to_nano(100 + 60 * 60));
+        let (game_id, player_1, _) = start_near_game(&mut ctx, &mut ctr);
+        give_up(&mut ctx, &mut ctr, &player_1, &game_id);
+        game_ids.push(game_id);
+        let stored: Vec<GameId> = ctr.get_last_games().iter().map(|(game_id, _)| *game_id).collect();
+        assert_eq!(stored, game_ids[1..].to_vec());
+
+        // disabled age keeps stored games
+        ctx.block_timestamp(sec_to_nano(100 * 60 * 60));
+        testing_env!(ctx.build());
+        ctr.set_stored_game_max_age(0);
+        assert_eq!(ctr.get_stored_games_num(), 2);
+        ctr.set_stored_game_max_age(60 * 60);
+        assert_eq!(ctr.get_stored_games_num(), 0);
+        assert_eq!(ctr.get_config().stored_game_max_age_sec, Some(60 * 60));
+    }
+
+    #[test]
+    #[should_panic(expected = "stored game max age need to be 0 or at least 3600 seconds")]
+    fn test_stored_game_max_age_too_short() {
+        let (_, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        ctr.set_stored_game_max_age(60);
+    }
+
+    #[test]
+    fn test_account_games() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let third: AccountId = "third".parse().unwrap();
+        let mut finished = vec![];
+        for opponent_id in [opponent(), third.clone()] {
+            make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+            make_available_near(&mut ctx, &mut ctr, &opponent_id, ONE_NEAR, None, None);
+            let game_id = start_game(&mut ctx, &mut ctr, &user(), &opponent_id);
+            give_up(&mut ctx, &mut ctr, &opponent_id, &game_id);
+            finished.push(game_id);
+        }
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+        make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, None, None);
+        let active_id = start_game(&mut ctx, &mut ctr, &user(), &opponent());
+
+        let games = ctr.get_account_games(user());
+        let active: Vec<GameId> = games.active.iter().map(|(game_id, _)| *game_id).collect();
+        let recent: Vec<GameId> = games.recent.iter().map(|(game_id, _)| *game_id).collect();
+        assert_eq!(active, vec![active_id]);
+        assert_eq!(recent, vec![finished[1], finished[0]]);
+
+        let games = ctr.get_account_games(third);
+        assert!(games.active.is_empty());
+        assert_eq!(games.recent.len(), 1);
+        assert_eq!(games.recent[0].0, finished[1]);
+    }
+
+    #[test]
+    fn test_stored_games_by_result() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let mut wins = vec![];
+        for _ in 0..2 {
+            make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+            make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, None, None);
+            let game_id = start_game(&mut ctx, &mut ctr, &user(), &opponent());
+            give_up(&mut ctx, &mut ctr, &opponent(), &game_id);
+            wins.push(game_id);
+        }
+        make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+        make_available_near(&mut ctx, &mut ctr, &opponent(), ONE_NEAR, None, None);
+        let tie_id = start_game(&mut ctx, &mut ctr, &user(), &opponent());
+        let (player_1, player_2) = ctr.internal_get_game_players(&tie_id);
+        play_tie(&mut ctx, &mut ctr, &tie_id, &player_1, &player_2);
+
+        let ids = |games: Vec<(GameId, GameLimitedView)>| games.into_iter().map(|(game_id, _)| game_id).collect::<Vec<_>>();
+        assert_eq!(ids(ctr.get_stored_games_by_result(GameResultKind::Tie, 0, 10)), vec![tie_id]);
+        assert_eq!(ids(ctr.get_stored_games_by_result(GameResultKind::Win, 0, 10)), vec![wins[1], wins[0]]);
+        assert_eq!(ids(ctr.get_stored_games_by_result(GameResultKind::Win, 1, 10)), vec![wins[0]]);
+        assert_eq!(ids(ctr.get_stored_games_by_result(GameResultKind::Win, 0, 1)), vec![wins[1]]);
+        assert!(ctr.get_stored_games_by_result(GameResultKind::Cancelled, 0, 10).is_empty());
+    }
+
+    #[test]
+    fn test_recent_opponents() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let third: AccountId = "third".parse().unwrap();
+        for opponent_id in [opponent(), third.clone(), opponent()] {
+            make_available_near(&mut ctx, &mut ctr, &user(), ONE_NEAR, None, None);
+            make_available_near(&mut ctx, &mut ctr, &opponent_id, ONE_NEAR, None, None);
+            let game_id = start_game(&mut ctx, &mut ctr, &user(), &opponent_id);
+            give_up(&mut ctx, &mut ctr, &opponent_id, &game_id);
+        }
+        assert_eq!(ctr.get_recent_opponents(user(), 10), vec![opponent(), third.clone()]);
+        assert_eq!(ctr.get_recent_opponents(user(), 1), vec![opponent()]);
+        assert_eq!(ctr.get_recent_opponents(third, 10), vec![user()]);
+        assert!(ctr.get_recent_opponents(referrer(), 10).is_empty());
+    }
+
+    #[test]
+    fn test_game_seed() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let (game_id, player_1, _) = start_near_game(&mut ctx, &mut ctr);
+        assert_eq!(player_1, opponent());
+        give_up(&mut ctx, &mut ctr, &player_1, &game_id);
+        assert_eq!(ctr.get_game(&game_id).seed, vec![0; 32]);
+
+        // odd seed byte - player who started the game moves first
+        let mut seed = [0; 32];
+        seed[0] = 3;
+        ctx.random_seed(seed);
+        let (game_id, player_1, _) = start_near_game(&mut ctx, &mut ctr);
+        assert_eq!(player_1, user());
+        give_up(&mut ctx, &mut ctr, &player_1, &game_id);
+        assert_eq!(ctr.get_game(&game_id).seed, seed.to_vec());
+    }
+
+    #[test]
+    fn test_misere() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let rules = GameRules { misere: true, ..Default::default() };
+        for account_id in [user(), opponent()] {
+            make_available_near_config(&mut ctx, &mut ctr, &account_id, ONE_NEAR, GameConfigNear {
+                rules: Some(rules.clone()),
+                ..Default::default()
+            });
+        }
+        let game_id = start_game(&mut ctx, &mut ctr, &user(), &opponent());
+        let (player_1, player_2) = ctr.internal_get_game_players(&game_id);
+        assert_eq!(ctr.internal_get_game(&game_id).current_piece, Piece::X);
+        for col in 0..4 {
+            make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, col);
+            make_move(&mut ctx, &mut ctr, &player_2, &game_id, 1, col);
+        }
+        // X completes the line and loses
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 4);
+        assert_eq!(ctr.get_game(&game_id).game_result, GameResult::Win(player_2, WinReason::Line));
+    }
+
+    #[test]
+    fn test_lines_to_win() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let rules = GameRules { lines_to_win: 2, ..Default::default() };
+        for account_id in [user(), opponent()] {
+            make_available_near_config(&mut ctx, &mut ctr, &account_id, ONE_NEAR, GameConfigNear {
+                rules: Some(rules.clone()),
+                ..Default::default()
+            });
+        }
+        let game_id = start_game(&mut ctx, &mut ctr, &user(), &opponent());
+        let (player_1, player_2) = ctr.internal_get_game_players(&game_id);
+        for col in 0..4 {
+            make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, col);
+            make_move(&mut ctx, &mut ctr, &player_2, &game_id, 1, col);
+        }
+        // first line doesn't finish the game
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 4);
+        // view order is [current, next]
+        assert_eq!(ctr.get_active_games()[0].1.lines_completed, [0, 1]);
+        for row in 1..4 {
+            make_move(&mut ctx, &mut ctr, &player_2, &game_id, 2, row - 1);
+            make_move(&mut ctx, &mut ctr, &player_1, &game_id, row, 4);
+        }
+        make_move(&mut ctx, &mut ctr, &player_2, &game_id, 2, 3);
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 4, 4);
+        assert_eq!(ctr.get_game(&game_id).game_result, GameResult::Win(player_1, WinReason::Line));
+
+        // one move completes row and column at once
+        let mut board = Board { tiles: Default::default(), current_piece: Piece::X, winner: None };
+        for index in 0..BOARD_SIZE {
+            board.tiles[4][index] = Some(Piece::X);
+            board.tiles[index][4] = Some(Piece::X);
+        }
+        assert_eq!(board.lines_completed_at(4, 4, WinRule::Standard), 2);
+        assert_eq!(board.lines_completed_at(4, 4, WinRule::DiagonalsOnly), 0);
+    }
+
+    #[test]
+    fn test_min_moves_before_win() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        let rules = GameRules { min_moves_before_win: 10, ..Default::default() };
+        for account_id in [user(), opponent()] {
+            make_available_near_config(&mut ctx, &mut ctr, &account_id, ONE_NEAR, GameConfigNear {
+                rules: Some(rules.clone()),
+                ..Default::default()
+            });
+        }
+        let game_id = start_game(&mut ctx, &mut ctr, &user(), &opponent());
+        let (player_1, player_2) = ctr.internal_get_game_players(&game_id);
+        for col in 0..4 {
+            make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, col);
+            make_move(&mut ctx, &mut ctr, &player_2, &game_id, 1, col);
+        }
+        // line on the 9th move is ignored
+        make_move(&mut ctx, &mut ctr, &player_1, &game_id, 0, 4);
+        assert_eq!(ctr.get_active_games().len(), 1);
+        // line exactly on the 10th move wins
+        make_move(&mut ctx, &mut ctr, &player_2, &game_id, 1, 4);
+        assert_eq!(ctr.get_game(&game_id).game_result, GameResult::Win(player_2, WinReason::Line));
+    }
+
+    #[test]
+    #[should_panic(expected = "Too many lines to win. Max for DiagonalsOnly is 2")]
+    fn test_lines_to_win_invalid() {
+        let (mut ctx, mut ctr) = setup_contract(user(), Some(MIN_FEES), None,  Some(60 * 25));
+        make_available_near_config(&mut ctx, &mut ctr, &user(), ONE_NEAR, GameConfigNear {
+            rules: Some(GameRules { win_rule: WinRule::DiagonalsOnly, lines_to_win: 3, ..Default::default() }),
+            ..Default::default()
+        });
+    }
+}
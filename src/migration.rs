@@ -0,0 +1,72 @@
+use crate::*;
+
+/// Same collection handle with another element type. Handle layout doesn't depend on it
+fn retype<T: BorshSerialize, U: BorshDeserialize>(collection: &T) -> U {
+    U::try_from_slice(&collection.try_to_vec().expect("Failed to serialize collection"))
+        .expect("Failed to deserialize collection")
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Upgrade from the first release layout (see `OldContract`). New fields get `new` defaults.
+    /// Games, invites and stored games are converted at once. Invites get new ids.
+    /// Stats are converted with `migrate_stats` afterwards
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let mut old: OldContract = env::state_read().expect("Old state doesn't exist");
+        let games = old.games.to_vec();
+        let available_players = old.available_players.to_vec();
+        let stored_games = old.stored_games.to_vec();
+        old.games.clear();
+        old.available_players.clear();
+        old.stored_games.clear();
+
+        let mut contract = Self::new(None);
+        contract.whitelisted_tokens = old.whitelisted_tokens;
+        contract.stats = retype(&old.stats);
+        contract.next_game_id = old.next_game_id;
+        contract.service_fee_percentage = old.service_fee_percentage;
+        contract.max_game_duration = old.max_game_duration;
+        contract.referrer_ratio = old.referrer_ratio;
+        contract.last_update_timestamp = old.last_update_timestamp;
+        contract.per_player_budget = old.max_game_duration / PLAYERS_NUM as u64;
+        contract.max_stored_games = old.max_stored_games;
+
+        for (game_id, game) in games {
+            contract.games.insert(&game_id, &Game::migrate(&game.0, contract.per_player_budget));
+        }
+        for (account_id, config) in available_players {
+            contract.internal_add_invite(&account_id, &config.into());
+        }
+        for (game_id, game) in stored_games {
+            contract.internal_store_game(&game_id, game.into());
+        }
+        contract
+    }
+
+    /// Converts stats of accounts in `from_index..from_index + limit` range
+    /// stored before `total_wagered` was added. Returns number of converted ones
+    #[private]
+    pub fn migrate_stats(&mut self, from_index: u64, limit: u64) -> u64 {
+        // replacing existing keys keeps the map length, so `self.stats` handle stays valid
+        let mut raw_stats: UnorderedMap<AccountId, RawBytes> = retype(&self.stats);
+        let entries: Vec<(AccountId, RawBytes)> = {
+            let keys = raw_stats.keys_as_vector();
+            let values = raw_stats.values_as_vector();
+            (from_index..std::cmp::min(from_index + limit, keys.len()))
+                .map(|index| (keys.get(index).expect("Account not found"), values.get(index).expect("Stats not found")))
+                .collect()
+        };
+        let mut converted = 0;
+        for (account_id, raw) in entries {
+            if Stats::try_from_slice(&raw.0).is_ok() {
+                continue;
+            }
+            let stats = StatsV1::try_from_slice(&raw.0).expect("Unknown stats layout").into_stats(&account_id);
+            raw_stats.insert(&account_id, &RawBytes(stats.try_to_vec().expect("Failed to serialize stats")));
+            converted += 1;
+        }
+        converted
+    }
+}
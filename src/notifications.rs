@@ -0,0 +1,29 @@
+//! Notification preferences read by off-chain notifiers. Pure metadata, not used by the contract
+use crate::*;
+
+pub const NOTIFY_TURN_REMINDER: u8 = 1;
+pub const NOTIFY_GAME_START: u8 = 1 << 1;
+pub const NOTIFY_GAME_END: u8 = 1 << 2;
+/// all known categories, default for accounts without preferences
+pub const NOTIFY_ALL: u8 = NOTIFY_TURN_REMINDER | NOTIFY_GAME_START | NOTIFY_GAME_END;
+
+#[near_bindgen]
+impl Contract {
+    /// Set caller's notification categories as bitflags:
+    /// 1 - turn reminder, 2 - game start, 4 - game end. 0 turns notifications off
+    pub fn set_notification_prefs(&mut self, flags: u8) {
+        self.internal_bump_update_seq();
+        assert_eq!(flags & !NOTIFY_ALL, 0, "Unknown notification flags. Max is {}", NOTIFY_ALL);
+        let account_id = env::predecessor_account_id();
+        // default value isn't stored
+        if flags == NOTIFY_ALL {
+            self.notification_prefs.remove(&account_id);
+        } else {
+            self.notification_prefs.insert(&account_id, &flags);
+        }
+    }
+
+    pub fn get_notification_prefs(&self, account_id: AccountId) -> u8 {
+        self.notification_prefs.get(&account_id).unwrap_or(NOTIFY_ALL)
+    }
+}
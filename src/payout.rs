@@ -0,0 +1,96 @@
+use crate::*;
+
+/// Winner reward for games staked in some token is paid in `payout_token_id`
+/// converted by fixed rate: `payout = reward * rate / RATE_DENOMINATOR`
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct PayoutRate {
+    pub payout_token_id: TokenContractId,
+    pub rate: U128,
+}
+
+impl PayoutRate {
+    /// `None` on overflow
+    pub(crate) fn convert(&self, amount: Balance) -> Option<Balance> {
+        amount
+            .checked_mul(self.rate.0)
+            .map(|payout| payout / RATE_DENOMINATOR)
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// pay winners of games staked in `token_id` with `payout_token_id` by fixed `rate`
+    /// (see `RATE_DENOMINATOR`). Payout token must be whitelisted and funded into payout reserve
+    #[private]
+    pub fn set_payout_rate(&mut self, token_id: TokenContractId, payout_token_id: TokenContractId, rate: U128) {
+        self.internal_bump_update_seq();
+        self.assert_payout_token(&token_id);
+        self.assert_payout_token(&payout_token_id);
+        assert_ne!(token_id, payout_token_id, "Payout token must differ from stake token");
+        assert!(rate.0 > 0, "Rate must be positive");
+        self.payout_rates.insert(&token_id, &PayoutRate { payout_token_id, rate });
+    }
+
+    #[private]
+    pub fn remove_payout_rate(&mut self, token_id: TokenContractId) {
+        self.internal_bump_update_seq();
+        self.payout_rates.remove(&token_id);
+    }
+
+    /// fund NEAR payout reserve
+    #[private]
+    #[payable]
+    pub fn deposit_payout_reserve(&mut self) {
+        self.internal_bump_update_seq();
+        let amount = env::attached_deposit();
+        assert!(amount > 0, "Attach NEAR to fund payout reserve");
+        self.internal_add_payout_reserve(&near_token_id(), amount);
+    }
+
+    pub fn get_payout_rate(&self, token_id: TokenContractId) -> Option<PayoutRate> {
+        self.payout_rates.get(&token_id)
+    }
+
+    pub fn get_payout_reserve(&self, token_id: TokenContractId) -> U128 {
+        self.payout_reserves.get(&token_id).unwrap_or(0).into()
+    }
+}
+
+impl Contract {
+    fn assert_payout_token(&self, token_id: &TokenContractId) {
+        assert!(
+            token_id == &near_token_id() || self.whitelisted_tokens.get(token_id).is_some(),
+            "Token {} is not whitelisted", token_id
+        );
+    }
+
+    pub (crate) fn internal_add_payout_reserve(&mut self, token_id: &TokenContractId, amount: Balance) {
+        let reserve = self.payout_reserves.get(token_id).unwrap_or(0);
+        self.payout_reserves.insert(token_id, &(reserve + amount));
+        log!("Payout reserve of {} is {}", token_id, reserve + amount);
+    }
+
+    /// Winner reward in payout token if rate is set for the stake token and reserve covers it.
+    /// Otherwise reward is paid in the stake token
+    pub (crate) fn internal_winner_payout(&mut self, token_id: &TokenContractId, reward: Balance) -> GameDeposit {
+        let stake_payout = GameDeposit { token_id: token_id.clone(), balance: reward.into() };
+        let payout_rate = match self.payout_rates.get(token_id) {
+            Some(payout_rate) => payout_rate,
+            None => return stake_payout,
+        };
+        let payout_token_id = payout_rate.payout_token_id.clone();
+        let reserve = self.payout_reserves.get(&payout_token_id).unwrap_or(0);
+        match payout_rate.convert(reward) {
+            Some(payout) if payout > 0 && payout <= reserve => {
+                self.payout_reserves.insert(&payout_token_id, &(reserve - payout));
+                GameDeposit { token_id: payout_token_id, balance: payout.into() }
+            },
+            _ => {
+                log!("Not enough {} in payout reserve. Reward is paid in {}", payout_token_id, token_id);
+                stake_payout
+            }
+        }
+    }
+}
@@ -23,6 +23,15 @@ impl Piece {
     }
 }
 
+impl std::fmt::Display for Piece {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Piece::X => write!(f, "x"),
+            Piece::O => write!(f, "o"),
+        }
+    }
+}
+
 /// Player struct with X/O and `AccountId`
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
 #[serde(crate = "near_sdk::serde")]
@@ -0,0 +1,126 @@
+use crate::*;
+
+/// Game waiting for opponent's `accept_game`. Both deposits are locked
+/// (players are removed from available players list) until game is accepted or expired
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct GameProposal {
+    pub proposer_id: AccountId,
+    pub proposer_config: GameConfig,
+    pub opponent_id: AccountId,
+    pub opponent_config: GameConfig,
+    pub proposed_at: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct GameProposalView {
+    pub game_id: GameId,
+    pub proposer_id: AccountId,
+    pub opponent_id: AccountId,
+    pub expires_at_sec: u32,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Two-phase start. Locks both deposits into not started game.
+    /// `opponent_id` must `accept_game` within ready check window, otherwise proposal is
+    /// cancelled on late `accept_game` or `cancel_proposal` and both players are back to available players list.
+    /// `invite_id` is opponent's invite (see `start_game`)
+    pub fn propose_game(&mut self, opponent_id: AccountId, invite_id: Option<InviteId>) -> GameId {
+        self.internal_bump_update_seq();
+
+        let proposer_id = env::predecessor_account_id();
+        let (proposer_config, opponent_config) = self.internal_take_players(&proposer_id, &opponent_id, invite_id)
+            .unwrap_or_else(|error| panic!("{}", error));
+        let game_id = self.internal_create_game(&proposer_id, &opponent_id, &opponent_config);
+        let proposal = GameProposal {
+            proposer_id,
+            proposer_config,
+            opponent_id,
+            opponent_config,
+            proposed_at: env::block_timestamp(),
+        };
+        Event::GameProposed {
+            game_id,
+            proposer_id: &proposal.proposer_id,
+            opponent_id: &proposal.opponent_id,
+            expires_at_sec: nano_to_sec(proposal.proposed_at + self.ready_check_duration),
+        }.emit();
+        self.proposals.insert(&game_id, &proposal);
+        game_id
+    }
+
+    /// only proposed opponent can accept the game. Expired proposal is cancelled instead
+    pub fn accept_game(&mut self, game_id: GameId) {
+        self.internal_bump_update_seq();
+
+        let proposal = self.proposals
+            .get(&game_id)
+            .expect("Game proposal not found or expired");
+        assert_eq!(env::predecessor_account_id(), proposal.opponent_id, "No access");
+        if self.internal_is_proposal_expired(&proposal) {
+            self.internal_cancel_proposal(&game_id, proposal);
+            return;
+        }
+        self.proposals.remove(&game_id);
+
+        self.internal_activate_game(
+            &game_id,
+            &proposal.proposer_id,
+            proposal.proposer_config,
+            &proposal.opponent_id,
+            proposal.opponent_config
+        );
+        Event::GameAccepted { game_id }.emit();
+    }
+
+    /// proposer returns both players to available players list if opponent didn't accept in time
+    pub fn cancel_proposal(&mut self, game_id: GameId) {
+        self.internal_bump_update_seq();
+        let proposal = self.proposals
+            .get(&game_id)
+            .expect("Game proposal not found");
+        assert_eq!(env::predecessor_account_id(), proposal.proposer_id, "No access");
+        assert!(self.internal_is_proposal_expired(&proposal), "Ready check isn't over yet");
+        self.internal_cancel_proposal(&game_id, proposal);
+    }
+
+    pub fn get_game_proposals(&self) -> Vec<GameProposalView> {
+        self.proposals
+            .iter()
+            .map(|(game_id, proposal)| GameProposalView {
+                game_id,
+                proposer_id: proposal.proposer_id,
+                opponent_id: proposal.opponent_id,
+                expires_at_sec: nano_to_sec(proposal.proposed_at + self.ready_check_duration),
+            })
+            .collect()
+    }
+}
+
+impl Contract {
+    pub (crate) fn internal_is_proposal_expired(&self, proposal: &GameProposal) -> bool {
+        env::block_timestamp().saturating_sub(proposal.proposed_at) > self.ready_check_duration
+    }
+
+    /// cancel proposal not accepted in time and return both players to available players list.
+    /// Deposit goes to pending refunds if player has no room for invite (see `internal_has_invite_room`)
+    pub (crate) fn internal_cancel_proposal(&mut self, game_id: &GameId, proposal: GameProposal) {
+        self.proposals.remove(game_id);
+        self.games.remove(game_id);
+        for (account_id, mut config) in [
+            (proposal.proposer_id, proposal.proposer_config),
+            (proposal.opponent_id, proposal.opponent_config)
+        ] {
+            if self.internal_has_invite_room(&account_id, &config) {
+                config.created_at = env::block_timestamp();
+                self.internal_add_invite(&account_id, &config);
+            } else if config.total_deposit() > 0 {
+                self.internal_add_pending_refund(&account_id, &config.token_id, config.total_deposit());
+            }
+        }
+        log!("GameId: {}. Game proposal expired", game_id);
+        Event::GameProposalCancelled { game_id: *game_id }.emit();
+    }
+}
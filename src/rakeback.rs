@@ -0,0 +1,48 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /// Claim all accrued rakeback in `token_id`
+    #[payable]
+    pub fn claim_rakeback(&mut self, token_id: TokenContractId) -> U128 {
+        assert_one_yocto();
+        self.internal_bump_update_seq();
+        let account_id = env::predecessor_account_id();
+        let amount = self.claimable_rakeback
+            .remove(&(account_id.clone(), token_id.clone()))
+            .expect("No rakeback to claim");
+        log!("Claim rakeback {} of {} for @{}", amount, token_id, account_id);
+        self.internal_batch_transfer(vec![
+            Transfer { receiver_id: account_id, token_id, amount: amount.into() }
+        ]);
+        amount.into()
+    }
+
+    pub fn get_claimable_rakeback(&self, account_id: AccountId, token_id: TokenContractId) -> U128 {
+        self.claimable_rakeback
+            .get(&(account_id, token_id))
+            .unwrap_or(0)
+            .into()
+    }
+}
+
+impl Contract {
+    /// Accrue `rakeback_ratio` part of service fee paid by each player (see `FeeSplit`).
+    /// Accrued amount is kept by contract until claimed
+    pub (crate) fn internal_accrue_rakeback(
+        &mut self,
+        game_id: &GameId,
+        token_id: &TokenContractId,
+        player_rakeback: Balance
+    ) {
+        if player_rakeback == 0 {
+            return;
+        }
+        let (player1, player2) = self.internal_get_game_players(game_id);
+        for account_id in [player1, player2] {
+            let key = (account_id, token_id.clone());
+            let rakeback = self.claimable_rakeback.get(&key).unwrap_or(0);
+            self.claimable_rakeback.insert(&key, &(rakeback + player_rakeback));
+        }
+    }
+}
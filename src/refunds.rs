@@ -0,0 +1,101 @@
+use crate::*;
+
+/// Transfer made by contract. Amount goes to pending refunds if it fails
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct Transfer {
+    pub receiver_id: AccountId,
+    pub token_id: TokenContractId,
+    pub amount: U128,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// All amounts contract owes to `account_id`: failed reward/refund
+    /// transfers and deposits of open invites
+    pub fn get_my_pending_refunds(&self, account_id: AccountId) -> Vec<(TokenContractId, U128)> {
+        let mut refunds = self.pending_refunds.get(&account_id).unwrap_or_default();
+        for (_, config) in self.internal_get_player_invites(&account_id) {
+            add_refund(&mut refunds, &config.token_id, config.total_deposit());
+        }
+        refunds
+            .into_iter()
+            .map(|(token_id, amount)| (token_id, U128(amount)))
+            .collect()
+    }
+
+    /// Transfer all pending refunds to caller. Deposits of open invites are refunded as well,
+    /// so caller is removed from available players list
+    #[payable]
+    pub fn claim_all_refunds(&mut self) {
+        assert_one_yocto();
+        self.internal_bump_update_seq();
+        let account_id = env::predecessor_account_id();
+        let refunds = self.pending_refunds.remove(&account_id).unwrap_or_default();
+        let invites = self.internal_get_player_invites(&account_id);
+        assert!(!refunds.is_empty() || !invites.is_empty(), "Nothing to claim");
+
+        let transfers = refunds
+            .into_iter()
+            .map(|(token_id, amount)| {
+                log!("Claim refund {} of {} for @{}", amount, token_id, account_id);
+                Transfer { receiver_id: account_id.clone(), token_id, amount: amount.into() }
+            })
+            .collect();
+        self.internal_batch_transfer(transfers);
+        for (invite_id, _) in invites {
+            self.internal_refund_available_deposit(&account_id, invite_id, 0);
+        }
+    }
+}
+
+impl Contract {
+    pub (crate) fn internal_add_pending_refund(&mut self, account_id: &AccountId, token_id: &TokenContractId, amount: Balance) {
+        let mut refunds = self.pending_refunds.get(account_id).unwrap_or_default();
+        add_refund(&mut refunds, token_id, amount);
+        self.pending_refunds.insert(account_id, &refunds);
+    }
+
+    /// All transfers are joined into one promise with single `transfers_callback`
+    /// which keeps failed amounts in pending refunds
+    pub (crate) fn internal_batch_transfer(&mut self, transfers: Vec<Transfer>) -> Option<Promise> {
+        let batch = transfers
+            .iter()
+            .map(|transfer| self.internal_transfer(&transfer.token_id, &transfer.receiver_id, transfer.amount))
+            .reduce(|batch, promise| batch.and(promise))?;
+        Some(batch.then(Self::ext(env::current_account_id())
+            .with_static_gas(CALLBACK_GAS)
+            .transfers_callback(transfers)
+        ))
+    }
+
+    /// remove player's invite from available players list and refund deposit.
+    /// Entry is removed before transfer, so deposit can't be claimed twice.
+    /// It's restored only in `transfer_deposit_callback` on failed transfer
+    /// `extra_near` is added to NEAR refund. Returns it back if invite is in other token.
+    /// Only the deposit is kept in `pending_refunds` if the transfer fails
+    pub (crate) fn internal_refund_available_deposit(&mut self, account_id: &AccountId, invite_id: InviteId, extra_near: Balance) -> Balance {
+        let config = self
+            .internal_remove_available_player(account_id, invite_id)
+            .expect("You are not available now");
+        let (amount, unused_extra) = if config.token_id == near_token_id() {
+            (config.total_deposit() + extra_near, 0)
+        } else {
+            (config.total_deposit(), extra_near)
+        };
+        self.internal_transfer(&config.token_id, account_id, amount.into())
+            .then(Self::ext(env::current_account_id())
+            .with_static_gas(CALLBACK_GAS)
+            .transfer_deposit_callback(account_id.clone(), invite_id, &config)
+        );
+        unused_extra
+    }
+}
+
+fn add_refund(refunds: &mut Vec<(TokenContractId, Balance)>, token_id: &TokenContractId, amount: Balance) {
+    match refunds.iter_mut().find(|(refund_token_id, _)| refund_token_id == token_id) {
+        Some((_, refund)) => *refund += amount,
+        None => refunds.push((token_id.clone(), amount)),
+    }
+}
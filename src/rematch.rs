@@ -0,0 +1,91 @@
+//! Automatic rematch. Player with `auto_rematch` prepays rematch escrow equal to his stake.
+//! If both players have escrow and the game is finished by a line or tie, new game between them
+//! is started with escrows as deposits. Players are checked as on game start (ban, earlier cooldown,
+//! accepted tokens). Otherwise escrows are refunded
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /// escrows held for rematch after `game_id` is finished
+    pub fn get_rematch_escrows(&self, game_id: GameId) -> Vec<(AccountId, U128)> {
+        self.rematch_escrows
+            .get(&game_id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(account_id, escrow)| (account_id, U128(escrow)))
+            .collect()
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_hold_rematch_escrows(&mut self, game_id: &GameId, escrows: Vec<(AccountId, Balance)>) {
+        let escrows: Vec<(AccountId, Balance)> = escrows
+            .into_iter()
+            .filter(|(_, escrow)| *escrow > 0)
+            .collect();
+        if !escrows.is_empty() {
+            self.rematch_escrows.insert(game_id, &escrows);
+        }
+    }
+
+    /// Start rematch or refund escrows. Called when finished game is already removed from active games.
+    /// Returns rematch `GameId`
+    pub(crate) fn internal_settle_rematch(&mut self, game_id: &GameId, game: &Game, is_normal_finish: bool) -> Option<GameId> {
+        let escrows = self.rematch_escrows.remove(game_id)?;
+        let token_id = game.reward.token_id.clone();
+        let players_are_free = escrows
+            .iter()
+            .all(|(account_id, _)| !self.internal_has_active_game(account_id));
+
+        let can_rematch = escrows.len() == PLAYERS_NUM && is_normal_finish && players_are_free
+            && match self.internal_check_players_eligible([&escrows[0].0, &escrows[1].0], &token_id) {
+                Ok(()) => true,
+                Err(err) => {
+                    log!("GameId: {}. Rematch is not started: {}", game_id, err);
+                    false
+                }
+            };
+
+        if can_rematch {
+            // stakes are equal, so escrows are equal too
+            let (player_1_id, deposit) = escrows[0].clone();
+            let player_2_id = escrows[1].0.clone();
+            let payout_to_1 = self.internal_get_payout_to(game_id, &player_1_id);
+            let payout_to_2 = self.internal_get_payout_to(game_id, &player_2_id);
+            let config = |opponent_id: &AccountId, payout_to: &AccountId| GameConfig {
+                token_id: token_id.clone(),
+                deposit,
+                opponent_id: Some(opponent_id.clone()),
+                referrer_id: None,
+                created_at: env::block_timestamp(),
+                rules: game.rules.clone(),
+                rematch_escrow: 0,
+                payout_to: Some(payout_to.clone()),
+            };
+            let rematch_id = self.internal_create_game(&player_1_id, &player_2_id, &config(&player_2_id, &payout_to_1));
+            self.internal_activate_game(
+                &rematch_id,
+                &player_1_id, config(&player_2_id, &payout_to_1),
+                &player_2_id, config(&player_1_id, &payout_to_2)
+            );
+            log!("GameId: {}. Rematch started. GameId: {}", game_id, rematch_id);
+            Some(rematch_id)
+        } else {
+            let transfers = escrows
+                .into_iter()
+                .map(|(receiver_id, escrow)| Transfer { receiver_id, token_id: token_id.clone(), amount: escrow.into() })
+                .collect();
+            self.internal_batch_transfer(transfers);
+            None
+        }
+    }
+}
+
+/// Returns stake and rematch escrow. With `auto_rematch` deposit is split in half
+pub(crate) fn split_rematch_escrow(deposit: Balance, auto_rematch: bool) -> (Balance, Balance) {
+    if !auto_rematch {
+        return (deposit, 0);
+    }
+    assert!(deposit.is_multiple_of(2), "Deposit with rematch escrow must be even, half of it is escrow");
+    (deposit / 2, deposit / 2)
+}
@@ -0,0 +1,168 @@
+//! Series of games between two players ("best of N"). Stakes are taken once, the pot is carried
+//! from game to game and is paid to the series winner after the deciding game (tie refund if wins are equal).
+//! First move alternates: the second player of the previous game moves first in the next one.
+//! Series is cancelled with stakes refund on cancelled game. Player who can't start the next game
+//! (ban, accepted tokens) forfeits the series. Rematch cooldown starts after the series
+use crate::*;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct SeriesState {
+    /// initiator and opponent
+    pub players: Vec<AccountId>,
+    pub best_of: u8,
+    /// won games in `players` order. Tie and cancelled games aren't counted
+    pub wins: [u8; PLAYERS_NUM],
+    pub games_played: u8,
+    pub current_game_id: GameId,
+}
+
+impl SeriesState {
+    fn is_over(&self) -> bool {
+        let to_win = self.best_of / 2 + 1;
+        self.games_played >= self.best_of || self.wins.iter().any(|wins| *wins >= to_win)
+    }
+    /// `None` if wins are equal
+    fn winner(&self) -> Option<&AccountId> {
+        match self.wins[0].cmp(&self.wins[1]) {
+            std::cmp::Ordering::Greater => Some(&self.players[0]),
+            std::cmp::Ordering::Less => Some(&self.players[1]),
+            std::cmp::Ordering::Equal => None,
+        }
+    }
+}
+
+/// result of finished game for its series
+pub(crate) enum SeriesProgress {
+    NotInSeries,
+    /// pot is carried to the next game
+    Continues(SeriesId),
+    /// pot goes to the series winner, `None` for tie
+    Over(Option<AccountId>),
+    /// stakes are refunded
+    Cancelled,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Start series of `best_of` games (odd, 3..`MAX_SERIES_BEST_OF`) with the stakes of one game.
+    /// Invites are matched like in `start_game`, auto rematch isn't supported. Returns id of the series
+    pub fn start_series(&mut self, player_2_id: AccountId, invite_id: Option<InviteId>, best_of: u8) -> SeriesId {
+        assert!(
+            (3..=MAX_SERIES_BEST_OF).contains(&best_of) && best_of % 2 == 1,
+            "Series must be best of odd number of games in range 3..{}", MAX_SERIES_BEST_OF
+        );
+        let player_1_id = env::predecessor_account_id();
+        let game_id = self.internal_start_game(player_2_id.clone(), invite_id)
+            .unwrap_or_else(|error| panic!("{}", error));
+        assert!(
+            self.rematch_escrows.get(&game_id).is_none(),
+            "Auto rematch isn't supported in series"
+        );
+
+        let series_id = self.next_series_id;
+        self.next_series_id += 1;
+        self.series.insert(&series_id, &SeriesState {
+            players: vec![player_1_id, player_2_id],
+            best_of,
+            wins: [0; PLAYERS_NUM],
+            games_played: 0,
+            current_game_id: game_id,
+        });
+        self.game_series.insert(&game_id, &series_id);
+        series_id
+    }
+
+    /// `None` if series is over or doesn't exist
+    pub fn get_series(&self, series_id: SeriesId) -> Option<SeriesState> {
+        self.series.get(&series_id)
+    }
+
+    /// series of active game, `None` for single game
+    pub fn get_game_series(&self, game_id: GameId) -> Option<SeriesId> {
+        self.game_series.get(&game_id)
+    }
+}
+
+impl Contract {
+    /// Count finished game in its series. Finished or cancelled series is removed
+    pub(crate) fn internal_record_series_game(&mut self, game_id: &GameId, game: &Game, game_result: &GameResult) -> SeriesProgress {
+        let series_id = match self.game_series.remove(game_id) {
+            Some(series_id) => series_id,
+            None => return SeriesProgress::NotInSeries,
+        };
+        let mut series = self.series.get(&series_id).expect("Series not found");
+        series.games_played += 1;
+        if matches!(game_result, GameResult::Cancelled) {
+            log!("Series {} is cancelled with game {}", series_id, game_id);
+            self.series.remove(&series_id);
+            return SeriesProgress::Cancelled;
+        }
+        if let Some(winner_id) = game_result.winner() {
+            let index = if *winner_id == series.players[0] { 0 } else { 1 };
+            series.wins[index] += 1;
+        }
+        let winner = if series.is_over() {
+            series.winner().cloned()
+        } else {
+            let ineligible: Vec<usize> = (0..PLAYERS_NUM)
+                .filter(|index| match self.internal_check_player_eligible(&series.players[*index], &game.reward.token_id) {
+                    Ok(()) => false,
+                    Err(error) => {
+                        log!("Series {}. {}", series_id, error);
+                        true
+                    }
+                })
+                .collect();
+            match ineligible.as_slice() {
+                [] => {
+                    self.series.insert(&series_id, &series);
+                    return SeriesProgress::Continues(series_id);
+                }
+                // player who can't start the next game forfeits the series
+                [index] => Some(series.players[1 - *index].clone()),
+                // nobody can continue, the pot is settled on current wins
+                _ => series.winner().cloned(),
+            }
+        };
+        log!("Series {} is over. Wins: {:?}", series_id, series.wins);
+        Event::SeriesFinished { series_id, winner_id: winner.as_ref() }.emit();
+        self.series.remove(&series_id);
+        SeriesProgress::Over(winner)
+    }
+
+    /// Start next game of the series with pot of the finished one. Called before
+    /// `payout_targets` of finished game are removed, players are checked in `internal_record_series_game`
+    pub(crate) fn internal_start_next_series_game(&mut self, series_id: SeriesId, game_id: &GameId, game: &Game) -> GameId {
+        // previous second player moves first
+        let (first_id, second_id) = (game.players[1].account_id.clone(), game.players[0].account_id.clone());
+        let mut next_game = Game::create_game(
+            first_id.clone(),
+            second_id.clone(),
+            game.reward(),
+            game.rules.clone(),
+            self.per_player_budget
+        );
+        next_game.change_state(GameState::Active);
+        next_game.initiated_at = env::block_timestamp();
+        let next_game_id = self.next_game_id;
+        self.next_game_id += 1;
+        self.games.insert(&next_game_id, &next_game);
+
+        if let Some(payout_targets) = self.payout_targets.get(game_id) {
+            self.payout_targets.insert(&next_game_id, &payout_targets);
+        }
+        if !game.is_practice() {
+            for player_id in [&first_id, &second_id] {
+                self.internal_update_stats(Some(&game.reward.token_id), player_id, UpdateStatsAction::AddPlayedGame, None, None);
+            }
+        }
+        let mut series = self.series.get(&series_id).expect("Series not found");
+        series.current_game_id = next_game_id;
+        self.series.insert(&series_id, &series);
+        self.game_series.insert(&next_game_id, &series_id);
+        log!("Series {}. Next game: {}", series_id, next_game_id);
+        next_game_id
+    }
+}
@@ -1,5 +1,6 @@
 use crate::*;
 
+#[allow(clippy::enum_variant_names)]
 #[derive(PartialEq)]
 pub enum UpdateStatsAction {
     AddPlayedGame,
@@ -9,6 +10,7 @@ pub enum UpdateStatsAction {
     AddTotalReward,
     AddAffiliateReward,
     AddPenaltyGame,
+    AddWageredAmount,
 }
 
 #[derive(BorshSerialize, BorshDeserialize)]
@@ -20,6 +22,8 @@ pub struct Stats {
     pub penalties_num: u64,
     pub total_reward: UnorderedMap<TokenContractId, Balance>,
     pub total_affiliate_reward: UnorderedMap<TokenContractId, Balance>,
+    /// sum of own deposits in all started games
+    pub total_wagered: UnorderedMap<TokenContractId, Balance>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -27,6 +31,8 @@ pub struct Stats {
 #[serde(crate = "near_sdk::serde")]
 pub struct UserPenalties {
     pub penalties_num: u64,
+    /// tier of the last penalty ban, 0 for the first penalty (see `Config.max_penalty_tier`)
+    pub severity_tier: u8,
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
@@ -39,23 +45,67 @@ pub struct StatsView {
     pub penalties_num: u64,
     pub total_reward: Vec<(TokenContractId, Balance)>,
     pub total_affiliate_reward: Vec<(AffiliateId, Balance)>,
+    pub total_wagered: Vec<(TokenContractId, Balance)>,
 }
-#[near_bindgen]
-impl Contract {
-    pub fn get_stats(&self, account_id: &AccountId) -> StatsView {
-        let stats = self.internal_get_stats(account_id);
+
+impl From<Stats> for StatsView {
+    fn from(stats: Stats) -> Self {
         StatsView { 
             referrer_id: stats.referrer_id, 
             games_played: stats.games_num, 
             victories_num: stats.victories_num, 
             penalties_num: stats.penalties_num, 
             total_reward: stats.total_reward.to_vec(), 
-            total_affiliate_reward: stats.total_affiliate_reward.to_vec() 
+            total_affiliate_reward: stats.total_affiliate_reward.to_vec(),
+            total_wagered: stats.total_wagered.to_vec(),
         }
     }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn get_stats(&self, account_id: &AccountId) -> StatsView {
+        StatsView::from(self.internal_get_stats(account_id))
+    }
+    /// `get_stats` of predecessor, zeroed if never played. Predecessor isn't set
+    /// in view calls, so it has to be called as a transaction
+    pub fn get_my_stats(&self) -> StatsView {
+        self.get_stats(&env::predecessor_account_id())
+    }
+    /// stats in the same order as `account_ids`. `None` for accounts never played
+    pub fn get_stats_batch(&self, account_ids: Vec<AccountId>) -> Vec<(AccountId, Option<StatsView>)> {
+        assert!(
+            account_ids.len() <= MAX_STATS_BATCH,
+            "Too many accounts. Max: {}", MAX_STATS_BATCH
+        );
+        account_ids
+            .into_iter()
+            .map(|account_id| {
+                let stats = self.stats.get(&account_id).map(StatsView::from);
+                (account_id, stats)
+            })
+            .collect()
+    }
     pub fn get_user_penalties(&self, account_id: &AccountId) -> UserPenalties {
         let stats = self.internal_get_stats(account_id);
-        UserPenalties { penalties_num: stats.penalties_num }
+        UserPenalties {
+            penalties_num: stats.penalties_num,
+            severity_tier: self.internal_penalty_tier(stats.penalties_num),
+        }
+    }
+    /// remaining matchmaking ban in nanoseconds after the last penalty, `None` if not banned
+    pub fn is_banned(&self, account_id: AccountId) -> Option<u64> {
+        let banned_until = self.banned_until.get(&account_id)?;
+        let now = env::block_timestamp();
+        (banned_until > now).then(|| banned_until - now)
+    }
+    /// remaining cooldown in nanoseconds after the last finished game (see `Config.rematch_cooldown_sec`),
+    /// `None` if account can play now
+    pub fn get_remaining_cooldown(&self, account_id: AccountId) -> Option<u64> {
+        let finished_at = self.last_game_finished_at.get(&account_id)?;
+        let cooldown_end = finished_at + self.rematch_cooldown;
+        let now = env::block_timestamp();
+        (cooldown_end > now).then(|| cooldown_end - now)
     }
     pub fn get_total_stats_num(&self) -> u32 {
         self.stats.len() as _
@@ -66,6 +116,61 @@ impl Contract {
             .keys()
             .collect()
     }
+    /// current season. Stats of previous seasons are in archive
+    pub fn get_season(&self) -> u32 {
+        self.season
+    }
+    /// live stats for current season, archived ones for previous seasons
+    pub fn get_stats_for_season(&self, account_id: AccountId, season: u32) -> Option<StatsView> {
+        if season == self.season {
+            self.stats.get(&account_id).map(StatsView::from)
+        } else {
+            season_archive(season).get(&account_id)
+        }
+    }
+    /// Snapshot stats of accounts in `from_index..from_index + limit` range into current season archive.
+    /// Counters and rewards are reset, referrer and affiliates are kept as referral fees depend on them.
+    /// Pages go one after another, `from_index` must be the value returned by the previous call (0 for the first one).
+    /// Returns `from_index` of the next page, or 0 when all accounts are done and new season is started
+    #[private]
+    pub fn archive_and_reset_stats(&mut self, from_index: u64, limit: u64) -> u64 {
+        self.internal_bump_update_seq();
+        assert_eq!(from_index, self.season_reset_from, "Reset of season {} continues from {}", self.season, self.season_reset_from);
+        assert!(limit > 0, "Limit must be positive");
+        let mut archive = season_archive(self.season);
+        let accounts_num = self.stats.len();
+        let to_index = std::cmp::min(from_index + limit.min(MAX_STATS_BATCH as u64), accounts_num);
+        let account_ids: Vec<AccountId> = {
+            let keys = self.stats.keys_as_vector();
+            (from_index..to_index).map(|index| keys.get(index).expect("Account not found")).collect()
+        };
+        for account_id in account_ids.iter() {
+            let stats = self.internal_get_stats(account_id);
+            archive.insert(account_id, &StatsView::from(stats));
+
+            let mut stats = self.internal_get_stats(account_id);
+            stats.games_num = 0;
+            stats.victories_num = 0;
+            stats.penalties_num = 0;
+            stats.total_reward.clear();
+            stats.total_affiliate_reward.clear();
+            stats.total_wagered.clear();
+            self.stats.insert(account_id, &stats);
+        }
+        if to_index < accounts_num {
+            self.season_reset_from = to_index;
+            return to_index;
+        }
+        log!("Season {} is archived. Stats of {} accounts are reset", self.season, accounts_num);
+        self.season_reset_from = 0;
+        self.season += 1;
+        0
+    }
+}
+
+/// archived stats of `season`
+fn season_archive(season: u32) -> LookupMap<AccountId, StatsView> {
+    LookupMap::new(StorageKey::SeasonArchive { season })
 }
 
 impl Stats {
@@ -78,16 +183,36 @@ impl Stats {
             penalties_num: 0,
             total_reward: UnorderedMap::new(StorageKey::TotalRewards { account_id: account_id.clone() }),
             total_affiliate_reward: UnorderedMap::new(StorageKey::TotalAffiliateRewards { account_id: account_id.clone() }),
+            total_wagered: UnorderedMap::new(StorageKey::TotalWagered { account_id: account_id.clone() }),
         }
     }
 }
 
 impl Contract {
+    pub(crate) fn internal_assert_not_banned(&self, account_id: &AccountId) {
+        if let Some(remaining) = self.is_banned(account_id.clone()) {
+            panic!("@{} is banned for penalties. {} sec left", account_id, nano_to_sec(remaining));
+        }
+    }
+    pub(crate) fn internal_assert_no_cooldown(&self, account_id: &AccountId) {
+        if let Some(remaining) = self.get_remaining_cooldown(account_id.clone()) {
+            panic!("{}", StartGameError::Cooldown { account_id: account_id.clone(), remaining_sec: nano_to_sec(remaining) });
+        }
+    }
+    /// severity tier of `penalties_num`-th penalty: one per repeat penalty up to `max_penalty_tier`
+    pub(crate) fn internal_penalty_tier(&self, penalties_num: u64) -> u8 {
+        penalties_num.saturating_sub(1).min(self.max_penalty_tier as u64) as u8
+    }
+    /// ban after `penalties_num`-th penalty, doubled on each tier up to `MAX_PENALTY_BAN_SEC`
+    pub(crate) fn internal_penalty_ban(&self, penalties_num: u64) -> Duration {
+        let tier = self.internal_penalty_tier(penalties_num);
+        (self.penalty_ban << tier).min(sec_to_nano(MAX_PENALTY_BAN_SEC))
+    }
     pub(crate) fn internal_get_stats(&self, account_id: &AccountId) -> Stats {
         if let Some(stats) = self.stats.get(account_id) {
-            stats.into()
+            stats
         } else {
-            Stats::new(&account_id)
+            Stats::new(account_id)
         }
     }
     pub(crate) fn internal_update_stats(&mut self,
@@ -135,6 +260,20 @@ impl Contract {
                 },
                 UpdateStatsAction::AddPenaltyGame => {
                     stats.penalties_num += 1;
+                    if self.penalty_ban > 0 {
+                        let ban = self.internal_penalty_ban(stats.penalties_num);
+                        self.banned_until.insert(account_id, &(env::block_timestamp() + ban));
+                    }
+                },
+                UpdateStatsAction::AddWageredAmount => {
+                    let token_id = match token_id {
+                        Some(id) => id,
+                        None => panic!("TokenId for update stats isn't set"),
+                    };
+                    if let Some(added_balance) = balance {
+                        let cur_balance = stats.total_wagered.get(token_id).unwrap_or(0);
+                        stats.total_wagered.insert(token_id, &(cur_balance + added_balance));
+                    }
                 },
             }
             self.stats.insert(account_id, &stats);
@@ -3,16 +3,27 @@ use near_contract_standards::{
     fungible_token::receiver::FungibleTokenReceiver,
 };
 
+#[allow(dead_code)]
 #[ext_contract(ext_ft)]
 pub trait ExtFungibleToken {
     fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>);
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Default)]
 #[serde(crate="near_sdk::serde")]
 pub struct GameConfigArgs {
     pub opponent_id: Option<AccountId>,
-    pub referrer_id: Option<AccountId>
+    pub referrer_id: Option<AccountId>,
+    pub rules: Option<GameRules>,
+    /// transferred amount is split in half: stake and rematch escrow (see `rematch.rs`)
+    pub auto_rematch: Option<bool>,
+    /// account to send winnings to instead of the sender
+    pub payout_to: Option<AccountId>,
+    /// add transferred amount to the deposit of existing invite instead of creating a new one.
+    /// Other fields are ignored then
+    pub top_up: Option<bool>,
+    /// invite to top up, required only if sender has several invites
+    pub invite_id: Option<InviteId>,
 }
 
 /// FT Receiver
@@ -23,7 +34,9 @@ impl FungibleTokenReceiver for Contract {
     /// Handles both farm deposits and stake deposits. For farm deposit (sending tokens
     /// to setup the farm) you must set "setup reward deposit" msg.
     /// Otherwise tokens will be staken.
-    /// Returns zero.
+    /// Returns zero. Empty msg means default config. Whole amount is returned for
+    /// malformed msg, not whitelisted token or deposit out of token deposit range.
+    /// `{"top_up": true}` msg adds the amount to sender's invite with the same token (see `internal_top_up_invite`)
     /// Panics when:
     /// - account is not registered
     /// - or receiving a wrong token
@@ -36,27 +49,75 @@ impl FungibleTokenReceiver for Contract {
         amount: U128,
         msg: String,
     ) -> PromiseOrValue<U128> {
+        self.internal_bump_update_seq();
         let token_id = env::predecessor_account_id();
 
+        // owner funds payout reserve (see `payout.rs`)
+        if sender_id == env::current_account_id() && msg == PAYOUT_RESERVE_MSG {
+            assert!(self.whitelisted_tokens.get(&token_id).is_some(), "Token {} is not whitelisted", &token_id);
+            self.internal_add_payout_reserve(&token_id, amount.0);
+            return PromiseOrValue::Value(U128(0));
+        }
+
+        // rejected deposits are returned as unused, so sender gets them back with `ft_resolve_transfer`
         let min_deposit = match self.min_deposit(&token_id) {
             Some(amount) => amount,
-            None => panic!("Token {} is not whitelisted", &token_id)
+            None => {
+                log!("Token {} is not whitelisted. Refund {} to @{}", &token_id, amount.0, sender_id);
+                return PromiseOrValue::Value(amount);
+            }
         };
 
-        assert!(
-            amount.0 >= min_deposit, 
-            "deposited amount must be more than {}",
-            min_deposit
-        );
-        
+        // only length is limited, JSON may be pretty-printed
+        if msg.chars().count() > MAX_TRANSFER_MSG_LEN {
+            let err = TextError::TooLong { max_len: MAX_TRANSFER_MSG_LEN };
+            log!("Msg is invalid: {}. Refund {} of {} to @{}", err, amount.0, token_id, sender_id);
+            return PromiseOrValue::Value(amount);
+        }
         let game_config = if msg.is_empty() {
             GameConfig::with_only_token_params(&token_id, amount.0)
         } else {
-            let game_args:GameConfigArgs = near_sdk::serde_json::from_str(&msg).expect("Config is invalid");
+            // malformed config - whole amount is returned as unused
+            let game_args: GameConfigArgs = match near_sdk::serde_json::from_str(&msg) {
+                Ok(game_args) => game_args,
+                Err(err) => {
+                    log!("Config is invalid: {}. Refund {} of {} to @{}", err, amount.0, token_id, sender_id);
+                    return PromiseOrValue::Value(amount);
+                }
+            };
+            if game_args.top_up.unwrap_or(false) {
+                return if self.internal_top_up_invite(&sender_id, game_args.invite_id, &token_id, amount.0) {
+                    PromiseOrValue::Value(U128(0))
+                } else {
+                    PromiseOrValue::Value(amount)
+                };
+            }
             GameConfig::from_transfer_msg(&token_id, amount.0, &game_args)
         };
 
-        log!("in deposit from @{} with token: {} amount {} ", sender_id, token_id, amount.0);
+        // rematch escrow isn't counted
+        if game_config.deposit < min_deposit {
+            log!("deposited amount must be more than {}. Refund {} of {} to @{}", min_deposit, amount.0, token_id, sender_id);
+            return PromiseOrValue::Value(amount);
+        }
+        if self.internal_exceeds_max_deposit(&token_id, game_config.deposit) {
+            log!("Deposit is too big. Refund {} of {} to @{}", amount.0, token_id, sender_id);
+            return PromiseOrValue::Value(amount);
+        }
+        if self.is_banned(sender_id.clone()).is_some() {
+            log!("@{} is banned for penalties. Refund {} of {}", sender_id, amount.0, token_id);
+            return PromiseOrValue::Value(amount);
+        }
+        if self.get_remaining_cooldown(sender_id.clone()).is_some() {
+            log!("@{} has just finished a game. Refund {} of {}", sender_id, amount.0, token_id);
+            return PromiseOrValue::Value(amount);
+        }
+        if !self.internal_accepts_token(&sender_id, &token_id) {
+            log!("@{} doesn't accept games in {}. Refund {}", sender_id, token_id, amount.0);
+            return PromiseOrValue::Value(amount);
+        }
+
+        self.internal_debug_log(|| format!("in deposit from @{} with token: {} amount {} ", sender_id, token_id, amount.0));
 
         let available_complete = self.internal_make_available(
             game_config,
@@ -77,37 +138,80 @@ impl Contract {
         game_config: GameConfig, 
         sender_id: &AccountId,
     ) -> bool {
-        let amount = game_config.deposit;
+        let amount = game_config.total_deposit();
         let token_id = game_config.token_id;
         let referrer_id:Option<AccountId> = game_config.referrer_id.clone();
-        assert!(self.available_players.get(&sender_id).is_none(), "Already in the waiting list the list");
+        self.internal_assert_invites_limit(sender_id);
+        if let Some(opponent_id) = game_config.opponent_id.as_ref() {
+            assert_not_self_play(sender_id, opponent_id);
+        }
+        assert_valid_referrer(sender_id, referrer_id.as_ref(), game_config.opponent_id.as_ref());
         
         //create config
-        self.available_players.insert(&sender_id,
+        self.internal_assert_waiting_capacity(&token_id);
+        let invite_id = self.internal_add_invite(sender_id,
             &GameConfig {
                 token_id: token_id.clone(),
-                deposit: amount,
+                deposit: game_config.deposit,
                 opponent_id: game_config.opponent_id,
                 referrer_id,
-                created_at: env::block_timestamp()
+                created_at: env::block_timestamp(),
+                rules: game_config.rules,
+                rematch_escrow: game_config.rematch_escrow,
+                payout_to: game_config.payout_to,
             }
         );
         
-        self.internal_check_player_available(&sender_id);
+        self.internal_check_player_available(sender_id);
         if let Some(referrer_id) = game_config.referrer_id {
-            self.internal_add_referrer(&sender_id, &referrer_id);
+            self.internal_add_referrer(sender_id, &referrer_id);
         }
-        log!("Success deposit from @{} with {} of `{}` ", sender_id, amount, token_id);
+        log!("Success deposit from @{} with {} of `{}`. InviteId: {}", sender_id, amount, token_id, invite_id);
         true 
     }
+    /// Adds `amount` to the deposit of sender's invite `invite_id` (or the only one if not set).
+    /// Returns false (amount is refunded) if there is no such invite, its token is different,
+    /// it has rematch escrow or the new deposit is too big
+    pub (crate) fn internal_top_up_invite(
+        &mut self,
+        sender_id: &AccountId,
+        invite_id: Option<InviteId>,
+        token_id: &TokenContractId,
+        amount: Balance,
+    ) -> bool {
+        let invites = self.internal_get_player_invites(sender_id);
+        let invite = match invite_id {
+            Some(invite_id) => invites.into_iter().find(|(id, _)| *id == invite_id),
+            None if invites.len() == 1 => invites.into_iter().next(),
+            None => None,
+        };
+        let (invite_id, mut config) = match invite {
+            Some(invite) => invite,
+            None => {
+                log!("@{} has no invite to top up. Refund {} of {}", sender_id, amount, token_id);
+                return false;
+            }
+        };
+        if &config.token_id != token_id {
+            log!("Invite {} is in {}. Refund {} of {} to @{}", invite_id, config.token_id, amount, token_id, sender_id);
+            return false;
+        }
+        if config.rematch_escrow > 0 {
+            log!("Invite {} has auto rematch. Refund {} of {} to @{}", invite_id, amount, token_id, sender_id);
+            return false;
+        }
+        config.deposit += amount;
+        if self.internal_exceeds_max_deposit(token_id, config.deposit) {
+            log!("Deposit is too big. Refund {} of {} to @{}", amount, token_id, sender_id);
+            return false;
+        }
+        self.internal_insert_available_player(sender_id, invite_id, &config);
+        log!("Top up from @{} with {} of `{}`. InviteId: {}, deposit: {}", sender_id, amount, token_id, invite_id, config.deposit);
+        true
+    }
     /// getting min deposit to check it on FT Receiver
     /// returns None if token isn't whitelisted
     pub (crate) fn min_deposit(&self, token_id: &TokenContractId) -> Option<Balance> {
-        match self.whitelisted_tokens.get(&token_id) {
-            Some(min_deposit) => {
-                Some(min_deposit)
-            },
-            None => None,
-        }
+        self.whitelisted_tokens.get(token_id)
     }
 }
\ No newline at end of file
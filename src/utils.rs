@@ -2,7 +2,7 @@ use std::convert::TryInto;
 
 use crate::*;
 
-pub (crate) const MAX_TIME_TO_BE_AVAILABLE: u64 = 24 * 60 * 60 * 1_000_000_000; // 1day in nanoseconds
+pub (crate) const DEFAULT_TIME_TO_BE_AVAILABLE: u64 = 24 * 60 * 60 * 1_000_000_000; // 1day in nanoseconds
 
 #[allow(unused)]
 pub(crate) const NO_DEPOSIT:u128 = 0;
@@ -14,25 +14,94 @@ pub(crate) const MAX_FEES: u32 = 1000; // 10%
 pub(crate) const BASIS_P: u32 = 10000; // 100%
 
 pub(crate) const MIN_DEPOSIT_NEAR: Balance = ONE_NEAR / 10; // 0.1 NEAR
+pub(crate) const NEAR_DECIMALS: u8 = 24;
 
 
 pub(crate) type TokenContractId = AccountId;
 pub(crate) type GameId = u64;
+/// id of player's open invite in available players list
+pub(crate) type InviteId = u64;
 pub(crate) type AffiliateId = AccountId;
+/// id of series of games between two players (see `series.rs`)
+pub(crate) type SeriesId = u64;
 
 /// This constant can be used to set the board size
 pub(crate) const BOARD_SIZE: usize = 5;
 pub(crate) const MAX_NUM_TURNS: u64 = 25;
 pub(crate) const PLAYERS_NUM: usize = 2;
+/// max number of games conceded in one `forfeit_all_my_games` call
+pub(crate) const MAX_FORFEITS_PER_CALL: usize = 10;
+/// max number of accounts in `get_stats_batch`
+pub(crate) const MAX_STATS_BATCH: usize = 100;
+/// max number of viewers registered for one game. Keeps cleanup on game finish bounded
+pub(crate) const MAX_VIEWERS_PER_GAME: u64 = 100;
+/// default max number of players waiting with the same token
+pub(crate) const DEFAULT_MAX_WAITING_PER_TOKEN: u32 = 100;
+/// max number of waiting practice invites. They aren't counted in `waiting_counts`
+pub(crate) const MAX_PRACTICE_WAITING: u32 = 10;
+/// max number of open invites of one player
+pub(crate) const MAX_INVITES_PER_PLAYER: usize = 5;
+/// max number of pre-placed pieces. Less than a line, so handicap never completes a line
+pub(crate) const MAX_HANDICAP_PIECES: usize = BOARD_SIZE - 1;
+/// max number of tokens in player's accepted tokens
+pub(crate) const MAX_ACCEPTED_TOKENS: usize = 10;
+/// max number of games in one series
+pub(crate) const MAX_SERIES_BEST_OF: u8 = 7;
+/// max chat message length in chars
+pub(crate) const MAX_CHAT_MESSAGE_LEN: usize = 140;
+/// max `ft_on_transfer` msg length in chars, enough for any `GameConfigArgs`
+pub(crate) const MAX_TRANSFER_MSG_LEN: usize = 1024;
+/// max number of chat messages kept for one game
+pub(crate) const MAX_CHAT_MESSAGES_PER_GAME: usize = 50;
+/// default time for opponent to accept proposed game. 5 minutes
+pub(crate) const DEFAULT_READY_CHECK_SEC: u32 = 5 * 60;
+/// `PayoutRate.rate` denominator
+pub(crate) const RATE_DENOMINATOR: u128 = 1_000_000_000_000;
+/// `ft_on_transfer` msg from contract itself to fund payout reserve
+pub(crate) const PAYOUT_RESERVE_MSG: &str = "payout_reserve";
+
+/// Check of free-text argument (e.g. chat message) before it's stored:
+/// up to `max_len` chars (not bytes) and no control chars, including line breaks
+pub (crate) fn validate_text(text: &str, max_len: usize) -> Result<(), TextError> {
+    if text.chars().count() > max_len {
+        return Err(TextError::TooLong { max_len });
+    }
+    if text.chars().any(char::is_control) {
+        return Err(TextError::ControlChars);
+    }
+    Ok(())
+}
+
+/// NEAR deposits are stored under `near` token id
+pub (crate) fn near_token_id() -> TokenContractId {
+    AccountId::new_unchecked("near".into())
+}
+
+/// Used by all game start paths and registration with `opponent_id`
+pub (crate) fn assert_not_self_play(account_id: &AccountId, opponent_id: &AccountId) {
+    assert_ne!(account_id, opponent_id, "Find a friend to play");
+}
+
+/// referrer can't be the player or the opponent, otherwise service fee is recycled between players
+pub (crate) fn assert_valid_referrer(account_id: &AccountId, referrer_id: Option<&AccountId>, opponent_id: Option<&AccountId>) {
+    if let Err(error) = check_referrer(account_id, referrer_id, opponent_id) {
+        panic!("{}", error);
+    }
+}
+
+pub (crate) fn check_referrer(account_id: &AccountId, referrer_id: Option<&AccountId>, opponent_id: Option<&AccountId>) -> Result<(), StartGameError> {
+    match referrer_id {
+        Some(referrer_id) if referrer_id == account_id => Err(StartGameError::SelfReferral),
+        Some(referrer_id) if opponent_id == Some(referrer_id) => Err(StartGameError::ReferrerIsOpponent),
+        _ => Ok(()),
+    }
+}
 
 /// Returns true if the promise was failed. Otherwise returns false.
 /// Fails if called outside a callback that received 1 promise result.
 pub (crate) fn promise_result_as_failed() -> bool {
     require!(env::promise_results_count() == 1, "Contract expected a result on the callback");
-    match env::promise_result(0) {
-        PromiseResult::Failed => true,
-        _ => false,
-    }
+    matches!(env::promise_result(0), PromiseResult::Failed)
 }
 
 pub (crate) fn sec_to_nano(sec: u32) -> Duration {
@@ -0,0 +1,44 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /// Register caller as a viewer of active game
+    pub fn register_viewer(&mut self, game_id: GameId) {
+        self.internal_bump_update_seq();
+        assert!(self.games.get(&game_id).is_some(), "Game not found");
+        let account_id = env::predecessor_account_id();
+        let mut viewers = self.viewers
+            .get(&game_id)
+            .unwrap_or_else(|| UnorderedSet::new(StorageKey::GameViewers { game_id }));
+        if !viewers.contains(&account_id) {
+            assert!(viewers.len() < MAX_VIEWERS_PER_GAME, "Too many viewers for this game");
+            viewers.insert(&account_id);
+            self.viewers.insert(&game_id, &viewers);
+        }
+    }
+
+    pub fn unregister_viewer(&mut self, game_id: GameId) {
+        self.internal_bump_update_seq();
+        let account_id = env::predecessor_account_id();
+        if let Some(mut viewers) = self.viewers.get(&game_id) {
+            viewers.remove(&account_id);
+            self.viewers.insert(&game_id, &viewers);
+        }
+    }
+
+    pub fn get_viewer_count(&self, game_id: GameId) -> u32 {
+        self.viewers
+            .get(&game_id)
+            .map(|viewers| viewers.len() as u32)
+            .unwrap_or(0)
+    }
+}
+
+impl Contract {
+    /// remove all viewers of the game. Bounded by `MAX_VIEWERS_PER_GAME`
+    pub (crate) fn internal_clear_viewers(&mut self, game_id: &GameId) {
+        if let Some(mut viewers) = self.viewers.remove(game_id) {
+            viewers.clear();
+        }
+    }
+}
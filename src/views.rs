@@ -1,11 +1,56 @@
 use crate::*;
 use std::collections::HashMap;
+use crate::internal::split_win_pot;
+
+/// How the game was won
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+#[serde(crate = "near_sdk::serde")]
+pub enum WinReason {
+    /// winner completed a line
+    Line,
+    /// opponent gave up
+    GiveUp,
+    /// opponent exceeded turn or game duration
+    Timeout,
+    /// game timed out on near-full board and winner had more open lines (see `Board::score_position`)
+    TieBreaker,
+}
 
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
 #[serde(crate = "near_sdk::serde")]
 pub enum GameResult {
-    Win(AccountId),
-    Tie
+    Win(AccountId, WinReason),
+    Tie,
+    /// first move timed out, stakes are refunded in full (see `first_move_grace_refund`)
+    Cancelled,
+}
+
+impl GameResult {
+    pub fn winner(&self) -> Option<&AccountId> {
+        match self {
+            GameResult::Win(winner, _) => Some(winner),
+            GameResult::Tie | GameResult::Cancelled => None,
+        }
+    }
+    pub fn kind(&self) -> GameResultKind {
+        match self {
+            GameResult::Win(..) => GameResultKind::Win,
+            GameResult::Tie => GameResultKind::Tie,
+            GameResult::Cancelled => GameResultKind::Cancelled,
+        }
+    }
+}
+
+/// `GameResult` without details to filter stored games (see `get_stored_games_by_result`)
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+#[serde(crate = "near_sdk::serde")]
+pub enum GameResultKind {
+    Win,
+    Tie,
+    Cancelled,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -13,11 +58,117 @@ pub enum GameResult {
 #[serde(crate = "near_sdk::serde")]
 pub struct ContractParams {
     pub games: HashMap<GameId, GameView>,
-    pub available_players: Vec<(AccountId, GameConfigView)>,
+    pub available_players: Vec<(AccountId, InviteId, GameConfigView)>,
     /* * */
     pub service_fee_percentage: u32,
     pub max_game_duration: u32,
-    pub last_update_timestamp_sec: u32
+    /// seconds invite stays in `available_players`
+    pub time_to_be_available: u32,
+    pub last_update_timestamp_sec: u32,
+    pub update_seq: u64,
+}
+
+/// allowed stake of whitelisted token
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct DepositRangeView {
+    pub min_deposit: U128,
+    /// `None` if there is no cap
+    pub max_deposit: Option<U128>,
+    /// referrer fees are paid in this token (see `set_referrer_eligible`)
+    pub referrer_eligible: bool,
+}
+
+/// winner payout of a hypothetical game, all amounts in stake token (see `preview_payout`)
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct PayoutPreview {
+    pub pot: U128,
+    pub winner_amount: U128,
+    /// part of the pot back to the loser (see `Config.consolation_ratio`)
+    pub loser_amount: U128,
+    /// service fee left after `referrer_fee`, rakeback and first threat bonus
+    pub protocol_fee: U128,
+    /// paid only if the winner has an eligible referrer, otherwise it stays in `protocol_fee`
+    pub referrer_fee: U128,
+    /// accrued to each player (see `Config.rakeback_ratio`)
+    pub rakeback_each: U128,
+    /// see `Config.first_threat_bonus`
+    pub first_threat_bonus: U128,
+}
+
+/// aggregated counters for dashboards
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct ProtocolStats {
+    pub total_games: u64,
+    pub active_games: u64,
+    /// accounts with stats (see `get_accounts_played`)
+    pub total_players: u64,
+    /// stakes of all started games
+    pub total_volume_per_token: Vec<(TokenContractId, U128)>,
+    /// service fees of all finished games (before referrer fees and rakeback) and turn extension prices
+    pub total_fees_per_token: Vec<(TokenContractId, U128)>,
+}
+
+/// whose turn it is in active game
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct TurnInfo {
+    pub total_turns: u8,
+    pub current_player: AccountId,
+    pub current_piece: Piece,
+    pub next_player: AccountId,
+}
+
+/// games of one account for "my games" screen
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AccountGamesView {
+    /// games in progress (including proposed ones)
+    pub active: Vec<(GameId, GameView)>,
+    /// stored finished games from the newest one, up to `max_stored_games`
+    pub recent: Vec<(GameId, GameLimitedView)>,
+}
+
+/// number of games by state
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct GamesCountsView {
+    pub total_created: u64,
+    pub active: u64,
+    /// proposed games waiting for acceptance (see `propose_game`)
+    pub not_started: u64,
+    /// finished or cancelled games
+    pub finished: u64,
+    /// finished games kept in `stored_games`
+    pub stored: u64,
+}
+
+/// empty tiles completing a line for each piece (see `Board::find_threats`)
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct ThreatsView {
+    pub x_threats: Vec<(u8, u8)>,
+    pub o_threats: Vec<(u8, u8)>,
+}
+
+/// deployed build to verify upgrades
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+#[serde(crate = "near_sdk::serde")]
+pub struct BuildInfo {
+    /// crate version
+    pub version: String,
+    /// time of init or the last state migration
+    pub upgraded_at_sec: u32,
+    pub last_update_timestamp_sec: u32,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -29,21 +180,79 @@ pub struct GameView {
     pub game_status: GameState,
     pub current_player: Player,
     pub reward: GameDeposit,
-    pub tiles: [[Option<Piece>; BOARD_SIZE]; BOARD_SIZE],
+    /// `size` rows of `size` tiles
+    pub tiles: Vec<Vec<Option<Piece>>>,
+    /// board side length
+    pub size: u8,
     /* * */
     pub initiated_at_sec: u32,
     pub last_turn_timestamp_sec: u32,
     pub current_duration_sec: u32,
+    pub rules: GameRules,
+    /// time budget left in seconds in `player1`, `player2` order
+    pub time_remaining_sec: [u32; PLAYERS_NUM],
+    /// completed lines in `player1`, `player2` order (see `GameRules.lines_to_win`)
+    pub lines_completed: [u32; PLAYERS_NUM],
+    /// the first player who had a line with one empty tile left
+    pub first_threat_by: Option<AccountId>,
+    /// zero-stake game (see `GameConfigNear.practice`)
+    pub is_practice: bool,
+    /// contract `update_seq` at the time of view
+    pub update_seq: u64,
 }
 
+/// Finished game. Players are stored in game order - `player1` made the first move
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
 pub struct GameLimitedView {
     pub game_result: GameResult,
     pub player1: AccountId,
     pub player2: AccountId,
+    /// `None` for tie
+    pub winner: Option<AccountId>,
+    /// `None` for tie
+    pub loser: Option<AccountId>,
     pub reward_or_tie_refund: GameDeposit,
-    pub board: [[Option<Piece>; BOARD_SIZE]; BOARD_SIZE],
+    /// `size` rows of `size` tiles
+    pub board: Vec<Vec<Option<Piece>>>,
+    /// board side length
+    pub size: u8,
+    /// same for boards equal up to rotation or reflection (see `Board::canonical_hash`)
+    pub canonical_hash: u64,
+    /// `Game.seed` to verify first player choice. Empty for legacy games
+    pub seed: Vec<u8>,
+    /// block timestamp when the game was finished. 0 for legacy games
+    pub finished_at: u64,
+    /// zero-stake game (see `GameConfigNear.practice`)
+    pub is_practice: bool,
+    /// tiles (row, col) of the line which finished the game. `None` unless finished by a line.
+    /// In misère game the line is completed by the loser
+    pub winning_line: Option<Vec<(u8, u8)>>,
+}
+
+impl GameLimitedView {
+    /// `reward` is winner payout or tie refund for each player
+    pub(crate) fn new(game: &Game, game_result: GameResult, reward: GameDeposit, winning_line: Option<Vec<(u8, u8)>>) -> Self {
+        let (winner, loser) = match game_result.winner() {
+            Some(winner) => (Some(winner.clone()), Some(game.other_player_account_id(winner))),
+            None => (None, None),
+        };
+        Self {
+            game_result,
+            player1: game.players[0].account_id.clone(),
+            player2: game.players[1].account_id.clone(),
+            winner,
+            loser,
+            reward_or_tie_refund: reward,
+            board: game.board.to_rows(),
+            size: BOARD_SIZE as u8,
+            canonical_hash: game.board.canonical_hash(),
+            seed: game.seed.clone(),
+            finished_at: env::block_timestamp(),
+            is_practice: game.is_practice(),
+            winning_line,
+        }
+    }
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
@@ -65,20 +274,31 @@ impl From<&Game> for RangedPlayersView {
     }
 }
 
-impl From<&Game> for GameView {
-    fn from(g: &Game) -> Self {
+impl GameView {
+    pub(crate) fn new(g: &Game, update_seq: u64) -> Self {
         let (player1, player2) = g.get_player_accounts();
-        let current_player = g.players[g.current_player_index as usize].clone();
+        let current_index = g.current_player_index as usize;
+        let current_player = g.players[current_index].clone();
         Self { 
             player1, 
             player2, 
             game_status: g.game_state,
             current_player,
             reward: g.reward(),
-            tiles: g.board.tiles,
+            tiles: g.board.to_rows(),
+            size: BOARD_SIZE as u8,
             initiated_at_sec: nano_to_sec(g.initiated_at),
             last_turn_timestamp_sec: nano_to_sec(g.last_turn_timestamp),
             current_duration_sec: nano_to_sec(g.current_duration),
+            rules: g.rules.clone(),
+            time_remaining_sec: [
+                nano_to_sec(g.time_remaining[current_index]),
+                nano_to_sec(g.time_remaining[1 - current_index]),
+            ],
+            lines_completed: [g.lines_completed[current_index], g.lines_completed[1 - current_index]],
+            first_threat_by: g.first_threat_by.clone(),
+            is_practice: g.is_practice(),
+            update_seq,
         }
     }
 }
@@ -87,7 +307,7 @@ impl From<&Game> for GameView {
 impl Contract {
     pub fn get_contract_params(&self) -> ContractParams {
         let games:HashMap<u64, GameView> = self.games.iter()
-            .map(|(game_id,game)| (game_id, GameView::from(&game)))
+            .map(|(game_id,game)| (game_id, GameView::new(&game, self.update_seq)))
             .collect();
         let available_players = self.get_available_players();
 
@@ -96,14 +316,127 @@ impl Contract {
             available_players, 
             service_fee_percentage: self.service_fee_percentage, 
             max_game_duration: nano_to_sec(self.max_game_duration),
-            last_update_timestamp_sec: nano_to_sec(self.last_update_timestamp)
+            time_to_be_available: nano_to_sec(self.time_to_be_available),
+            last_update_timestamp_sec: nano_to_sec(self.last_update_timestamp),
+            update_seq: self.update_seq,
         } 
     }
 
+    /// current settings in `Config` shape, can be passed into `new` as is
+    pub fn get_config(&self) -> Config {
+        Config {
+            service_fee_percentage: self.service_fee_percentage,
+            referrer_ratio: self.referrer_ratio,
+            max_game_duration_sec: nano_to_sec(self.max_game_duration),
+            max_stored_games: self.max_stored_games,
+            fee_schedule: Some(self.fee_schedule.clone()),
+            max_waiting_per_token: Some(self.max_waiting_per_token),
+            ready_check_sec: Some(nano_to_sec(self.ready_check_duration)),
+            min_turn_interval_sec: Some(nano_to_sec(self.min_turn_interval)),
+            rakeback_ratio: Some(self.rakeback_ratio),
+            debug_logs: Some(self.debug_logs),
+            payout_delay_sec: Some(nano_to_sec(self.payout_delay)),
+            tie_breaker_min_turns: self.tie_breaker_min_turns,
+            first_move_grace_refund: Some(self.first_move_grace_refund),
+            deterministic_first_move: Some(self.deterministic_first_move),
+            penalty_ban_sec: Some(nano_to_sec(self.penalty_ban)),
+            first_threat_bonus: Some(self.first_threat_bonus),
+            block_referrer_as_opponent: Some(self.block_referrer_as_opponent),
+            turn_extension_sec: Some(nano_to_sec(self.turn_extension)),
+            turn_extension_price: Some(U128(self.turn_extension_price)),
+            consolation_ratio: Some(self.consolation_ratio),
+            min_referrer_games: Some(self.min_referrer_games as u32),
+            rematch_cooldown_sec: Some(nano_to_sec(self.rematch_cooldown)),
+            stored_game_max_age_sec: Some(nano_to_sec(self.stored_game_max_age)),
+            timeout_grace_sec: Some(nano_to_sec(self.timeout_grace)),
+            max_penalty_tier: Some(self.max_penalty_tier),
+            time_to_be_available_sec: Some(nano_to_sec(self.time_to_be_available)),
+        }
+    }
+
+    /// crate version of deployed contract
+    pub fn get_version(&self) -> String {
+        env!("CARGO_PKG_VERSION").to_string()
+    }
+
+    pub fn get_build_info(&self) -> BuildInfo {
+        BuildInfo {
+            version: self.get_version(),
+            upgraded_at_sec: nano_to_sec(self.upgraded_at),
+            last_update_timestamp_sec: nano_to_sec(self.last_update_timestamp),
+        }
+    }
+
+    /// bumped on every state-mutating call. Unchanged value means nothing changed since last fetch
+    pub fn get_update_seq(&self) -> u64 {
+        self.update_seq
+    }
+
+    /// `GameId` which will be set for next created game
+    pub fn get_next_game_id(&self) -> GameId {
+        self.next_game_id
+    }
+
+    /// game ids start from 0, so it's equal to `next_game_id`
+    pub fn get_total_games_created(&self) -> u64 {
+        self.next_game_id
+    }
+
+    pub fn get_games_counts(&self) -> GamesCountsView {
+        let active = self.games
+            .values()
+            .filter(|game| game.game_state == GameState::Active)
+            .count() as u64;
+        GamesCountsView {
+            total_created: self.next_game_id,
+            active,
+            not_started: self.games.len() - active,
+            finished: self.next_game_id - self.games.len(),
+            stored: self.stored_games.len(),
+        }
+    }
+
+    /// health check of the whole protocol. Doesn't scan games or players
+    pub fn get_protocol_stats(&self) -> ProtocolStats {
+        ProtocolStats {
+            total_games: self.next_game_id,
+            // games which are not active are proposals waiting for acceptance
+            active_games: self.games.len() - self.proposals.len(),
+            total_players: self.stats.len(),
+            total_volume_per_token: self.total_volume
+                .iter()
+                .map(|(token_id, volume)| (token_id, U128(volume)))
+                .collect(),
+            total_fees_per_token: self.total_fees
+                .iter()
+                .map(|(token_id, fees)| (token_id, U128(fees)))
+                .collect(),
+        }
+    }
+
     pub fn get_game(&self, game_id: &GameId) -> GameLimitedView {
         self.stored_games.get(game_id).expect("Game not found")
     }
 
+    /// `stop_game` by `caller` would succeed now: game is active, caller is the player
+    /// waiting for opponent's move and game duration or opponent's time budget is over
+    pub fn can_stop_game(&self, game_id: GameId, caller: AccountId) -> bool {
+        self.games.get(&game_id).is_some_and(|game| {
+            game.game_state == GameState::Active
+                && game.contains_player_account_id(&caller)
+                && game.current_player_account_id() != caller
+                && self.internal_is_stoppable(&game, env::block_timestamp())
+        })
+    }
+
+    /// outcome of finished game. `None` if the game is still active or unknown
+    pub fn get_game_winner(&self, game_id: &GameId) -> Option<GameResult> {
+        // finished game is moved to stored games in the same call
+        self.stored_games
+            .get(game_id)
+            .map(|stored| stored.game_result)
+    }
+
     pub fn get_ordered_players(&self, game_id: &GameId) -> RangedPlayersView {
         self
             .games
@@ -120,8 +453,86 @@ impl Contract {
         self.internal_get_game(game_id).next_player_account_id()
     }
 
+    /// `None` if game doesn't exist
+    pub fn get_turn_info(&self, game_id: GameId) -> Option<TurnInfo> {
+        self.games.get(&game_id).map(|game| TurnInfo {
+            total_turns: game.total_turns,
+            current_player: game.current_player_account_id(),
+            current_piece: game.current_piece,
+            next_player: game.next_player_account_id(),
+        })
+    }
+
+    /// returns `None` if game doesn't exist or `account_id` isn't in this game
+    pub fn is_my_turn(&self, game_id: &GameId, account_id: AccountId) -> Option<bool> {
+        self.games
+            .get(game_id)
+            .filter(|game| game.contains_player_account_id(&account_id))
+            .map(|game| game.current_player_account_id() == account_id)
+    }
+
+    /// stored games from the oldest to the newest finished
     pub fn get_last_games(&self) -> Vec<(GameId, GameLimitedView)> {
-        self.stored_games.to_vec()
+        (self.stored_games_head..self.stored_games_tail)
+            .filter_map(|index| self.stored_games_order.get(&index))
+            .filter_map(|game_id| self.stored_games.get(&game_id).map(|game| (game_id, game)))
+            .collect()
+    }
+
+    /// stored games with `result_type` from the newest finished.
+    /// `from_index` and `limit` count only matching games
+    pub fn get_stored_games_by_result(&self, result_type: GameResultKind, from_index: u64, limit: u64) -> Vec<(GameId, GameLimitedView)> {
+        (self.stored_games_head..self.stored_games_tail)
+            .rev()
+            .filter_map(|index| self.stored_games_order.get(&index))
+            .filter_map(|game_id| self.stored_games.get(&game_id).map(|game| (game_id, game)))
+            .filter(|(_, game)| game.game_result.kind() == result_type)
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .collect()
+    }
+
+    /// active and recent stored games of `account_id` in one call
+    pub fn get_account_games(&self, account_id: AccountId) -> AccountGamesView {
+        let active = self.games
+            .iter()
+            .filter(|(_, game)| game.contains_player_account_id(&account_id))
+            .map(|(game_id, game)| (game_id, GameView::new(&game, self.update_seq)))
+            .collect();
+        let recent = (self.stored_games_head..self.stored_games_tail)
+            .rev()
+            .filter_map(|index| self.stored_games_order.get(&index))
+            .filter_map(|game_id| self.stored_games.get(&game_id).map(|game| (game_id, game)))
+            .filter(|(_, game)| game.player1 == account_id || game.player2 == account_id)
+            .take(self.max_stored_games as usize)
+            .collect();
+        AccountGamesView { active, recent }
+    }
+
+    /// distinct opponents of `account_id` in stored games from the most recent one.
+    /// Only last `max_stored_games` games are stored, so it's recent history only
+    pub fn get_recent_opponents(&self, account_id: AccountId, limit: u32) -> Vec<AccountId> {
+        let mut opponents: Vec<AccountId> = vec![];
+        for index in (self.stored_games_head..self.stored_games_tail).rev() {
+            if opponents.len() >= limit as usize {
+                break;
+            }
+            let game = match self.stored_games_order.get(&index).and_then(|game_id| self.stored_games.get(&game_id)) {
+                Some(game) => game,
+                None => continue,
+            };
+            let opponent_id = if game.player1 == account_id {
+                game.player2
+            } else if game.player2 == account_id {
+                game.player1
+            } else {
+                continue;
+            };
+            if !opponents.contains(&opponent_id) {
+                opponents.push(opponent_id);
+            }
+        }
+        opponents
     }
 
     pub fn get_current_tiles(&self, game_id: &GameId) -> [[Option<Piece>; BOARD_SIZE]; BOARD_SIZE]{
@@ -129,34 +540,170 @@ impl Contract {
         game.board.tiles
     }
 
-    pub fn get_whitelisted_tokens(&self) -> Vec<(TokenContractId, U128)> {
+    /// tiles (row, col) which complete a line for X and O by game's win rule, for hints and bots.
+    /// In misère mode completing a line loses. `None` if game isn't found
+    pub fn get_threats(&self, game_id: GameId) -> Option<ThreatsView> {
+        let game = self.games.get(&game_id)?;
+        Some(ThreatsView {
+            x_threats: game.board.find_threats(Piece::X, game.rules.win_rule),
+            o_threats: game.board.find_threats(Piece::O, game.rules.win_rule),
+        })
+    }
+
+    /// board rows like `"x.o.."`
+    pub fn get_board_compact(&self, game_id: &GameId) -> Vec<String> {
+        self.internal_get_game(game_id).board.to_compact()
+    }
+
+    /// (x, o) bitmasks of the board (see `Board::to_bitboards`)
+    pub fn get_board_bitboards(&self, game_id: &GameId) -> (u64, u64) {
+        self.internal_get_game(game_id).board.to_bitboards()
+    }
+
+    pub fn get_whitelisted_tokens(&self) -> Vec<(TokenContractId, DepositRangeView)> {
         self.whitelisted_tokens
             .to_vec()
             .iter()
-            .map(|(acc, min_dep)| (acc.clone(), U128(*min_dep)))
+            .map(|(acc, min_dep)| (acc.clone(), DepositRangeView {
+                min_deposit: U128(*min_dep),
+                max_deposit: self.max_deposits.get(acc).map(U128),
+                referrer_eligible: !self.referrer_ineligible_tokens.contains(acc),
+            }))
             .collect()
     }
 
-    pub fn get_token_min_deposit(&self, token_id: &TokenContractId) -> U128 {
+    /// Winner payout for game with `deposit_each` stake from both players, split the same way as
+    /// a finished game. `total_turns` picks the `fee_schedule` bucket, flat `service_fee_percentage` if not set.
+    /// Without `winner_id` the winner is assumed to have an eligible referrer.
+    /// `first_threat` - some player gets first threat bonus
+    pub fn preview_payout(
+        &self,
+        token_id: TokenContractId,
+        deposit_each: U128,
+        total_turns: Option<u8>,
+        winner_id: Option<AccountId>,
+        first_threat: Option<bool>
+    ) -> PayoutPreview {
+        let pot = deposit_each.0 * PLAYERS_NUM as u128;
+        let service_fee_percentage = total_turns
+            .map(|total_turns| self.internal_service_fee(total_turns))
+            .unwrap_or(self.service_fee_percentage);
+        let split = split_win_pot(pot, service_fee_percentage, self.consolation_ratio);
+        let pays_referrer = match winner_id {
+            Some(winner_id) => self.internal_eligible_referrer(&token_id, &winner_id).is_some(),
+            None => !self.referrer_ineligible_tokens.contains(&token_id),
+        };
+        let fee_split = self.internal_split_fee(split.fee, pays_referrer, first_threat.unwrap_or(false));
+        PayoutPreview {
+            pot: pot.into(),
+            winner_amount: split.winner_reward.into(),
+            loser_amount: split.consolation.into(),
+            protocol_fee: fee_split.protocol_fee.into(),
+            referrer_fee: fee_split.referrer_fee.into(),
+            rakeback_each: fee_split.rakeback_each.into(),
+            first_threat_bonus: fee_split.first_threat_bonus.into(),
+        }
+    }
+
+    /// `None` if token isn't whitelisted
+    pub fn get_token_min_deposit(&self, token_id: TokenContractId) -> Option<U128> {
         self.whitelisted_tokens
-            .get(token_id)
-            .expect("Token isn't whitelisted")
-            .into()
+            .get(&token_id)
+            .map(U128)
     }
 
-    pub fn get_available_players(&self) -> Vec<(AccountId, GameConfigView)> {
+    /// all open invites. Player may have several of them
+    pub fn get_available_players(&self) -> Vec<(AccountId, InviteId, GameConfigView)> {
         self.available_players
             .to_vec()
             .iter()
-            .map(|(acc, game_config)| (acc.clone(), GameConfigView::from(game_config)))
+            .map(|((acc, invite_id), game_config)| (acc.clone(), *invite_id, GameConfigView::from(game_config)))
             .collect()
     }
 
+    /// single open invite, e.g. from `player_available` event. `None` if it's already taken or cancelled
+    pub fn get_invite(&self, account_id: AccountId, invite_id: InviteId) -> Option<GameConfigView> {
+        self.available_players
+            .get(&(account_id, invite_id))
+            .map(|game_config| GameConfigView::from(&game_config))
+    }
+
+    /// open invites with `token_id` and `deposit` (stake without rematch escrow) if they are set
+    pub fn get_available_players_filtered(
+        &self,
+        token_id: Option<TokenContractId>,
+        deposit: Option<U128>,
+    ) -> Vec<(AccountId, InviteId, GameConfigView)> {
+        self.available_players
+            .iter()
+            .filter(|(_, game_config)| {
+                token_id.as_ref().is_none_or(|token_id| game_config.token_id == *token_id)
+                    && deposit.is_none_or(|deposit| game_config.deposit == deposit.0)
+            })
+            .map(|((acc, invite_id), game_config)| (acc, invite_id, GameConfigView::from(&game_config)))
+            .collect()
+    }
+
+    /// up to `limit` waiting players which can be started with `token_id` and `deposit` right now:
+    /// invite isn't for another opponent, player accepts the token and isn't playing, banned or on cooldown.
+    /// `account_id` is the caller, their own invites are skipped and invites for them are included
+    pub fn find_compatible_opponents(
+        &self,
+        token_id: TokenContractId,
+        deposit: U128,
+        limit: u64,
+        account_id: Option<AccountId>,
+    ) -> Vec<AccountId> {
+        let mut opponents: Vec<AccountId> = Vec::new();
+        for ((player_id, _), game_config) in self.available_players.iter() {
+            if opponents.len() as u64 >= limit {
+                break;
+            }
+            let is_compatible = game_config.token_id == token_id
+                && game_config.deposit == deposit.0
+                && game_config.opponent_id.as_ref().is_none_or(|opponent_id| Some(opponent_id) == account_id.as_ref())
+                && account_id.as_ref() != Some(&player_id)
+                && !opponents.contains(&player_id);
+            if is_compatible
+                && !self.internal_has_active_game(&player_id)
+                && self.internal_accepts_token(&player_id, &token_id)
+                && self.is_banned(player_id.clone()).is_none()
+                && self.get_remaining_cooldown(player_id.clone()).is_none() {
+                opponents.push(player_id);
+            }
+        }
+        opponents
+    }
+
+    /// same as `get_available_players` with deposit token decimals
+    pub fn get_available_players_humanized(&self) -> Vec<(AccountId, InviteId, HumanizedGameConfigView)> {
+        self.available_players
+            .iter()
+            .map(|((acc, invite_id), game_config)| {
+                let decimals = self.internal_get_token_decimals(&game_config.token_id);
+                (acc, invite_id, HumanizedGameConfigView { config: GameConfigView::from(&game_config), decimals })
+            })
+            .collect()
+    }
+
+    /// open invites of `account_id` ordered by id
+    pub fn get_player_invites(&self, account_id: AccountId) -> Vec<(InviteId, GameConfigView)> {
+        self.internal_get_player_invites(&account_id)
+            .iter()
+            .map(|(invite_id, game_config)| (*invite_id, GameConfigView::from(game_config)))
+            .collect()
+    }
+
+    /// number of available players by token
+    pub fn get_waiting_counts(&self) -> Vec<(TokenContractId, u32)> {
+        self.waiting_counts.to_vec()
+    }
+
     pub fn get_active_games(&self) -> Vec<(GameId, GameView)> {
         self.games
             .to_vec()
             .iter()
-            .map(|(game_id, game)| (*game_id, GameView::from(game)))
+            .map(|(game_id, game)| (*game_id, GameView::new(game, self.update_seq)))
             .collect()
     }
 